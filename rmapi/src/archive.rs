@@ -0,0 +1,223 @@
+use crate::error::Error;
+use crate::objects::Parent;
+use serde_json::{Map, Value};
+use std::io::{Cursor, Read, Write};
+use uuid::Uuid;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+/// The most the files of an `.rmdoc` archive may add up to once extracted.
+const MAX_EXTRACTED_SIZE: u64 = 1 << 30;
+
+/// Every file of a document, as packaged in the `.rmdoc` archives the desktop app exports.
+///
+/// Files keep the names they have in the document's index: `<id>.metadata`,
+/// `<id>.content`, `<id>.pagedata`, the source `<id>.pdf` or `<id>.epub`,
+/// the pages as `<id>/<page id>.rm` and their thumbnails as
+/// `<id>.thumbnails/<page id>.png`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Archive {
+    /// The id of the document.
+    pub id: Uuid,
+    /// The name and contents of every file of the document.
+    pub files: Vec<(String, Vec<u8>)>,
+}
+
+impl Archive {
+    /// Looks up a file of the archive by its name.
+    pub fn file(&self, name: &str) -> Option<&[u8]> {
+        self.files
+            .iter()
+            .find(|(file_name, _)| file_name == name)
+            .map(|(_, contents)| contents.as_slice())
+    }
+
+    /// Packages the archive as an `.rmdoc` zip.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `Error::InvalidArchive` if the zip cannot be written.
+    pub fn to_zip(&self) -> Result<Vec<u8>, Error> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in &self.files {
+            // Source files and thumbnails are compressed already.
+            let method =
+                if name.ends_with(".pdf") || name.ends_with(".epub") || name.ends_with(".png") {
+                    CompressionMethod::Stored
+                } else {
+                    CompressionMethod::Deflated
+                };
+            zip.start_file(
+                name.as_str(),
+                SimpleFileOptions::default().compression_method(method),
+            )
+            .map_err(zip_error)?;
+            zip.write_all(contents)?;
+        }
+        Ok(zip.finish().map_err(zip_error)?.into_inner())
+    }
+
+    /// Reads an `.rmdoc` zip, such as one written by `to_zip` or exported by the desktop app.
+    ///
+    /// The id of the document is taken from its `.metadata` file.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `Error::InvalidArchive` if the data is not a
+    /// zip, its files add up to more than 1 GiB, or it holds no `<id>.metadata` file.
+    pub fn from_zip(data: &[u8]) -> Result<Archive, Error> {
+        Archive::extract(data, MAX_EXTRACTED_SIZE)
+    }
+
+    /// Reads an `.rmdoc` zip, giving up once its files add up to more than `max_size` bytes.
+    ///
+    /// The sizes recorded in the zip are not trusted, since a crafted
+    /// archive can claim anything there.
+    fn extract(data: &[u8], max_size: u64) -> Result<Archive, Error> {
+        let mut zip = ZipArchive::new(Cursor::new(data)).map_err(zip_error)?;
+        let mut files = Vec::new();
+        let mut total = 0;
+        for i in 0..zip.len() {
+            let mut file = zip.by_index(i).map_err(zip_error)?;
+            if file.is_dir() {
+                continue;
+            }
+            let mut contents = Vec::new();
+            (&mut file)
+                .take(max_size - total + 1)
+                .read_to_end(&mut contents)?;
+            total += contents.len() as u64;
+            if total > max_size {
+                return Err(Error::InvalidArchive(format!(
+                    "the files of the archive add up to more than {} bytes",
+                    max_size
+                )));
+            }
+            files.push((file.name().to_string(), contents));
+        }
+
+        let id = files
+            .iter()
+            .find_map(|(name, _)| name.strip_suffix(".metadata"))
+            .and_then(|id| Uuid::parse_str(id).ok())
+            .ok_or_else(|| Error::InvalidArchive("no <id>.metadata file".to_string()))?;
+        Ok(Archive { id, files })
+    }
+
+    /// Gives the document a new id, renaming every file that belongs to it.
+    ///
+    /// Importing an archive under a new id keeps it from replacing the document it was exported from.
+    pub fn with_id(mut self, id: Uuid) -> Archive {
+        let old = self.id.to_string();
+        for (name, _) in &mut self.files {
+            if let Some(rest) = name.strip_prefix(&old) {
+                *name = format!("{}{}", id, rest);
+            }
+        }
+        self.id = id;
+        self
+    }
+
+    /// Sets the parent recorded in the archive's `.metadata` file.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the `.metadata` file is missing or not a JSON object.
+    pub fn with_parent(self, parent: Parent) -> Result<Archive, Error> {
+        self.with_metadata("parent", Value::String(parent.to_string()))
+    }
+
+    /// Sets the name recorded in the archive's `.metadata` file.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the `.metadata` file is missing or not a JSON object.
+    pub fn with_visible_name(self, name: &str) -> Result<Archive, Error> {
+        self.with_metadata("visibleName", Value::String(name.to_string()))
+    }
+
+    fn with_metadata(mut self, field: &str, value: Value) -> Result<Archive, Error> {
+        let name = format!("{}.metadata", self.id);
+        let (_, contents) = self
+            .files
+            .iter_mut()
+            .find(|(file_name, _)| *file_name == name)
+            .ok_or_else(|| Error::InvalidArchive(format!("no {} file", name)))?;
+        let mut metadata: Map<String, Value> = serde_json::from_slice(contents)?;
+        metadata.insert(field.to_string(), value);
+        *contents = serde_json::to_vec(&metadata)?;
+        Ok(self)
+    }
+}
+
+fn zip_error(err: zip::result::ZipError) -> Error {
+    match err {
+        zip::result::ZipError::Io(err) => Error::Io(err),
+        err => Error::InvalidArchive(err.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID: &str = "10000000-0000-0000-0000-000000000002";
+
+    fn archive() -> Archive {
+        Archive {
+            id: Uuid::parse_str(ID).unwrap(),
+            files: vec![
+                (
+                    format!("{}.metadata", ID),
+                    br#"{"visibleName":"Dune","parent":""}"#.to_vec(),
+                ),
+                (format!("{}.content", ID), b"{}".to_vec()),
+                (format!("{}.pdf", ID), b"%PDF-1.7".to_vec()),
+                (
+                    format!("{}/page.rm", ID),
+                    b"reMarkable .lines file".to_vec(),
+                ),
+            ],
+        }
+    }
+
+    #[test]
+    fn round_trips_through_zip() {
+        let archive = archive();
+        assert_eq!(
+            Archive::from_zip(&archive.to_zip().unwrap()).unwrap(),
+            archive
+        );
+    }
+
+    #[test]
+    fn renames_files_for_a_new_id() {
+        let id = Uuid::new_v4();
+        let archive = archive().with_id(id);
+        assert_eq!(archive.id, id);
+        assert!(archive.file(&format!("{}/page.rm", id)).is_some());
+        assert!(archive.files.iter().all(|(name, _)| !name.starts_with(ID)));
+    }
+
+    #[test]
+    fn limits_the_extracted_size() {
+        let zip = archive().to_zip().unwrap();
+        assert!(Archive::extract(&zip, 1024).is_ok());
+        assert!(matches!(
+            Archive::extract(&zip, 16),
+            Err(Error::InvalidArchive(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_zips_without_metadata() {
+        let archive = Archive {
+            id: Uuid::new_v4(),
+            files: vec![("notes.txt".to_string(), b"hello".to_vec())],
+        };
+        assert!(matches!(
+            Archive::from_zip(&archive.to_zip().unwrap()),
+            Err(Error::InvalidArchive(_))
+        ));
+    }
+}
@@ -0,0 +1,321 @@
+use crate::error::Error;
+use crate::hash;
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::SystemTime;
+
+/// An on-disk cache of sync blobs, keyed by their SHA-256 hash.
+///
+/// Blobs in the sync storage never change once uploaded, so a cached blob can
+/// be reused for as long as it is referenced. Blobs are checked against their
+/// hash every time they are read, and a blob that does not match is dropped
+/// and downloaded again.
+///
+/// When a size limit is set, the least recently used blobs are evicted
+/// whenever a write takes the cache over it, until it fits again. The total
+/// size is counted once and then kept up to date in memory, so the directory
+/// is only listed again when blobs have to be evicted.
+#[derive(Debug, Clone)]
+pub struct BlobCache {
+    dir: PathBuf,
+    max_size: Option<u64>,
+    /// The total size of the cached blobs as last counted, or `None` before it
+    /// is first needed. Clones of a cache share it.
+    tracked_size: Arc<Mutex<Option<u64>>>,
+}
+
+impl BlobCache {
+    /// Creates a cache that keeps its blobs in `dir`, without a size limit.
+    pub fn new(dir: impl Into<PathBuf>) -> BlobCache {
+        BlobCache {
+            dir: dir.into(),
+            max_size: None,
+            tracked_size: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Limits the total size of the cached blobs to `max_size` bytes.
+    pub fn with_max_size(mut self, max_size: u64) -> BlobCache {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    /// The directory the blobs are kept in.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The size limit of the cache in bytes, if there is one.
+    pub fn max_size(&self) -> Option<u64> {
+        self.max_size
+    }
+
+    /// Reads the blob with the given hash from the cache.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Some(blob))` if the blob is cached and matches its hash.
+    /// * `Ok(None)` if it is not cached, or was cached but did not match its hash.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the cached blob exists but cannot be read.
+    pub fn get(&self, hash: &str) -> Result<Option<Vec<u8>>, Error> {
+        let Some(path) = self.blob_path(hash) else {
+            return Ok(None);
+        };
+        let blob = match fs::read(&path) {
+            Ok(blob) => blob,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(Error::from(e)),
+        };
+        if let Err(e) = hash::verify(hash, &blob) {
+            log::warn!("Dropping cached blob: {}", e);
+            remove_blob(&path)?;
+            self.untrack(blob.len() as u64);
+            return Ok(None);
+        }
+        // The modification time doubles as the last use, so reads keep a blob from being evicted.
+        if let Err(e) = fs::File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|f| f.set_modified(SystemTime::now()))
+        {
+            log::debug!("Could not mark cached blob {} as used: {}", hash, e);
+        }
+        log::debug!("Read blob {} from the cache", hash);
+        Ok(Some(blob))
+    }
+
+    /// Stores a blob under its hash, then evicts old blobs if this takes the
+    /// cache over its size limit.
+    ///
+    /// The blob is written to a temporary file first and renamed into place,
+    /// so readers never see a partially written blob.
+    pub fn put(&self, hash: &str, blob: &[u8]) -> Result<(), Error> {
+        let Some(path) = self.blob_path(hash) else {
+            log::debug!("Not caching blob with unexpected hash {:?}", hash);
+            return Ok(());
+        };
+        fs::create_dir_all(&self.dir)?;
+        let replaced = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        let tmp_path = self.dir.join(format!("{}.tmp", hash));
+        let mut file = fs::File::create(&tmp_path)?;
+        file.write_all(blob)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, &path)?;
+        log::debug!("Cached blob {} ({} bytes)", hash, blob.len());
+
+        if let Some(max_size) = self.max_size {
+            let mut tracked = self.lock_size();
+            let size = match *tracked {
+                Some(size) => size.saturating_sub(replaced) + blob.len() as u64,
+                None => self.size()?,
+            };
+            *tracked = Some(if size > max_size {
+                self.evict(max_size)?
+            } else {
+                size
+            });
+        }
+        Ok(())
+    }
+
+    /// The total size of the cached blobs in bytes.
+    pub fn size(&self) -> Result<u64, Error> {
+        Ok(self.entries()?.iter().map(|e| e.size).sum())
+    }
+
+    /// Removes every cached blob whose hash is not in `reachable`.
+    ///
+    /// # Returns
+    ///
+    /// The number of blobs that were removed.
+    pub fn gc(&self, reachable: &HashSet<String>) -> Result<usize, Error> {
+        let mut removed = 0;
+        for entry in self.entries()? {
+            if !reachable.contains(&entry.hash) {
+                remove_blob(&entry.path)?;
+                removed += 1;
+            }
+        }
+        *self.lock_size() = None;
+        log::info!("Removed {} unreachable blobs from the cache", removed);
+        Ok(removed)
+    }
+
+    /// Removes the least recently used blobs until the cache holds at most `max_size` bytes.
+    ///
+    /// # Returns
+    ///
+    /// The total size of the blobs left in the cache.
+    fn evict(&self, max_size: u64) -> Result<u64, Error> {
+        let mut entries = self.entries()?;
+        let mut size: u64 = entries.iter().map(|e| e.size).sum();
+        if size <= max_size {
+            return Ok(size);
+        }
+        entries.sort_by_key(|e| e.last_used);
+        for entry in entries {
+            if size <= max_size {
+                break;
+            }
+            log::debug!("Evicting blob {} from the cache", entry.hash);
+            remove_blob(&entry.path)?;
+            size -= entry.size;
+        }
+        Ok(size)
+    }
+
+    /// Takes a removed blob of `size` bytes off the tracked size.
+    fn untrack(&self, size: u64) {
+        if let Some(ref mut tracked) = *self.lock_size() {
+            *tracked = tracked.saturating_sub(size);
+        }
+    }
+
+    fn lock_size(&self) -> MutexGuard<'_, Option<u64>> {
+        // The size is only ever a number, so it is still usable after a panic.
+        self.tracked_size
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Lists the blobs in the cache, skipping temporary and unrelated files.
+    fn entries(&self) -> Result<Vec<CacheEntry>, Error> {
+        let dir = match fs::read_dir(&self.dir) {
+            Ok(dir) => dir,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(Error::from(e)),
+        };
+        let mut entries = Vec::new();
+        for entry in dir {
+            let entry = entry?;
+            let Some(hash) = entry.file_name().to_str().map(str::to_string) else {
+                continue;
+            };
+            if !hash::is_sha256_hex(&hash) {
+                continue;
+            }
+            let metadata = entry.metadata()?;
+            entries.push(CacheEntry {
+                hash,
+                path: entry.path(),
+                size: metadata.len(),
+                last_used: metadata.modified()?,
+            });
+        }
+        Ok(entries)
+    }
+
+    /// The path of the blob with the given hash, or `None` if the hash is not a hex SHA-256 digest.
+    fn blob_path(&self, hash: &str) -> Option<PathBuf> {
+        hash::is_sha256_hex(hash).then(|| self.dir.join(hash))
+    }
+}
+
+struct CacheEntry {
+    hash: String,
+    path: PathBuf,
+    size: u64,
+    last_used: SystemTime,
+}
+
+fn remove_blob(path: &Path) -> Result<(), Error> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(Error::from(e)),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn temp_cache() -> BlobCache {
+        BlobCache::new(std::env::temp_dir().join(format!("rmapi-test-{}", Uuid::new_v4())))
+    }
+
+    #[test]
+    fn round_trips_blobs() {
+        let cache = temp_cache();
+        let hash = hash::sha256_hex(b"contents");
+        assert_eq!(cache.get(&hash).unwrap(), None);
+
+        cache.put(&hash, b"contents").unwrap();
+        assert_eq!(cache.get(&hash).unwrap(), Some(b"contents".to_vec()));
+        assert_eq!(cache.size().unwrap(), 8);
+
+        fs::remove_dir_all(cache.dir()).unwrap();
+    }
+
+    #[test]
+    fn drops_blobs_that_do_not_match_their_hash() {
+        let cache = temp_cache();
+        let hash = hash::sha256_hex(b"contents");
+        cache.put(&hash, b"tampered").unwrap();
+
+        assert_eq!(cache.get(&hash).unwrap(), None);
+        assert_eq!(cache.size().unwrap(), 0);
+
+        fs::remove_dir_all(cache.dir()).unwrap();
+    }
+
+    #[test]
+    fn evicts_least_recently_used_blobs() {
+        let cache = temp_cache().with_max_size(16);
+        let (first, second, third) = (
+            hash::sha256_hex(b"first..."),
+            hash::sha256_hex(b"second.."),
+            hash::sha256_hex(b"third..."),
+        );
+        cache.put(&first, b"first...").unwrap();
+        cache.put(&second, b"second..").unwrap();
+        let old = SystemTime::now() - std::time::Duration::from_secs(60);
+        fs::File::options()
+            .write(true)
+            .open(cache.dir().join(&second))
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+
+        cache.put(&third, b"third...").unwrap();
+        assert!(cache.get(&first).unwrap().is_some());
+        assert!(cache.get(&second).unwrap().is_none());
+        assert!(cache.get(&third).unwrap().is_some());
+
+        fs::remove_dir_all(cache.dir()).unwrap();
+    }
+
+    #[test]
+    fn tracks_the_size_without_counting_rewritten_blobs_twice() {
+        let cache = temp_cache().with_max_size(100);
+        let (first, second) = (hash::sha256_hex(b"first..."), hash::sha256_hex(b"second.."));
+        cache.put(&first, b"first...").unwrap();
+        cache.put(&first, b"first...").unwrap();
+        cache.put(&second, b"second..").unwrap();
+        assert_eq!(*cache.lock_size(), Some(16));
+        assert_eq!(cache.size().unwrap(), 16);
+
+        fs::remove_dir_all(cache.dir()).unwrap();
+    }
+
+    #[test]
+    fn gc_keeps_only_reachable_blobs() {
+        let cache = temp_cache();
+        let (kept, dropped) = (hash::sha256_hex(b"kept"), hash::sha256_hex(b"dropped"));
+        cache.put(&kept, b"kept").unwrap();
+        cache.put(&dropped, b"dropped").unwrap();
+
+        let removed = cache.gc(&HashSet::from([kept.clone()])).unwrap();
+        assert_eq!(removed, 1);
+        assert!(cache.get(&kept).unwrap().is_some());
+        assert!(cache.get(&dropped).unwrap().is_none());
+
+        fs::remove_dir_all(cache.dir()).unwrap();
+    }
+}
@@ -1,46 +1,259 @@
-use crate::endpoints;
+use crate::archive::Archive;
+use crate::cache::BlobCache;
+use crate::config::{ClientConfig, HttpConfig};
+use crate::endpoints::{self, RootInfo, UploadMeta, UploadResponse};
 
 use crate::error::Error;
+use crate::file_type::FileType;
+use crate::hash;
+use crate::highlights::Highlights;
+use crate::notebook::Notebook;
+use crate::objects::{self, Content, Parent};
+use crate::render::{self, RenderOptions};
+use crate::retry::RetryPolicy;
+use crate::sync::{EntryType, Index, Tree, TreeItem};
+use crate::token::{SyncVersion, UserToken};
+use crate::token_store::{MemoryTokenStore, TokenStore, Tokens};
+use crate::transaction::{self, Transaction};
+use crate::upload::{Destination, UploadOptions, UploadSource};
 
+use std::collections::HashSet;
+use std::future::Future;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::fs::File;
+use tokio::sync::RwLock;
 
 use log;
+use reqwest::Body;
+use uuid::Uuid;
+
+/// How long before its expiry a user token is refreshed, so requests never race the real expiry.
+const USER_TOKEN_REFRESH_MARGIN: Duration = Duration::from_secs(5 * 60);
+
+/// How many times a commit is rebased onto a newer root before giving up.
+const MAX_COMMIT_ATTEMPTS: u32 = 5;
+
+/// The tokens a client is currently using.
+struct Session {
+    device_token: String,
+    user_token: UserToken,
+    /// How many times the user token has been refreshed, so a request that
+    /// saw its token rejected can tell whether another one already replaced it.
+    refreshes: u64,
+}
 
 /// Represents a client for interacting with the reMarkable Cloud API.
 ///
-/// This struct holds the authentication token, the path to the token file,
-/// and an optional storage URL for the client.
+/// This struct holds the device and user tokens, the store they are persisted
+/// in, the hosts the client talks to and the HTTP client shared by every request. The user token is refreshed
+/// automatically before it expires and whenever the cloud rejects it.
 pub struct Client {
-    /// The authentication token used for API requests.
-    pub auth_token: String,
-    /// An optional URL for the storage API endpoint.
-    pub storage_url: String,
+    http: reqwest::Client,
+    config: ClientConfig,
+    retry: RetryPolicy,
+    session: RwLock<Session>,
+    token_store: Arc<dyn TokenStore>,
+    cache: Option<BlobCache>,
 }
 
-/// TODO: Token caching in library or in the app (feels like app but so many operations need to be atomic)?
-impl Client {
-    /// Creates a new `Client` instance from an existing auth token.
+/// Builds a `Client` that talks to custom hosts or keeps its tokens in a custom store.
+///
+/// ```no_run
+/// # async fn example() -> Result<(), rmapi::Error> {
+/// use rmapi::{Client, ClientConfig};
+///
+/// let client = Client::builder()
+///     .config(ClientConfig::local("http://127.0.0.1:8080"))
+///     .from_token("device-token")
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Default)]
+pub struct ClientBuilder {
+    config: ClientConfig,
+    http: HttpConfig,
+    retry: RetryPolicy,
+    token_store: Option<Arc<dyn TokenStore>>,
+    cache: Option<BlobCache>,
+}
+
+impl ClientBuilder {
+    /// Creates a builder for a client that talks to the production cloud and
+    /// keeps its tokens in memory.
+    pub fn new() -> ClientBuilder {
+        ClientBuilder::default()
+    }
+
+    /// Sets the hosts the client talks to.
+    pub fn config(mut self, config: ClientConfig) -> ClientBuilder {
+        self.config = config;
+        self
+    }
+
+    /// Sets the timeouts, proxy, root certificates and user agent used for requests.
+    pub fn http(mut self, http: HttpConfig) -> ClientBuilder {
+        self.http = http;
+        self
+    }
+
+    /// Sets when failed requests are retried.
+    pub fn retry(mut self, retry: RetryPolicy) -> ClientBuilder {
+        self.retry = retry;
+        self
+    }
+
+    /// Sets the store the client loads its tokens from and saves refreshed tokens to.
+    pub fn token_store(mut self, token_store: Arc<dyn TokenStore>) -> ClientBuilder {
+        self.token_store = Some(token_store);
+        self
+    }
+
+    /// Keeps downloaded blobs in `cache` so they are only fetched from the cloud once.
+    pub fn cache(mut self, cache: BlobCache) -> ClientBuilder {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Registers with the reMarkable Cloud using a provided code and keeps the
+    /// resulting tokens in the token store.
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - A string slice containing the registration code.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - The registration process with the reMarkable Cloud fails.
+    /// - Saving the device token or creating the client from it fails.
+    pub async fn register(self, code: &str) -> Result<Client, Error> {
+        log::debug!("Registering client with reMarkable Cloud");
+        let http = self.http.build_client()?;
+        let device_token =
+            endpoints::register_client(&http, &self.config, &self.retry, code).await?;
+        self.from_token(&device_token).await
+    }
+
+    /// Creates a client from an existing device token, saving it to the token store.
     ///
     /// # Arguments
     ///
-    /// * `auth_token` - A string slice containing the authentication token.
+    /// * `device_token` - A string slice containing the device token.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if saving the token or creating the
+    /// client from it fails.
+    pub async fn from_token(mut self, device_token: &str) -> Result<Client, Error> {
+        let tokens = Tokens::from_device_token(device_token);
+        match self.token_store {
+            Some(ref store) => store.save(&tokens)?,
+            None => self.token_store = Some(Arc::new(MemoryTokenStore::new(tokens))),
+        }
+        self.connect().await
+    }
+
+    /// Creates a client from the tokens kept in the token store.
+    ///
+    /// A stored user token is reused if it is not about to expire. Otherwise the
+    /// stored device token is exchanged for a fresh user token, which is then
+    /// saved back to the store.
     ///
     /// # Returns
     ///
     /// A `Result` containing:
-    /// - `Ok(Client)`: A new `Client` instance with the provided token.
-    /// - `Err(Error)`: An error if writing the token file fails.
+    /// - `Ok(Client)`: A new `Client` instance using the stored tokens.
+    /// - `Err(Error)`: An error if the store is empty or the token exchange fails.
     ///
     /// # Errors
     ///
-    /// This function will return an error if writing the token file fails.
-    pub async fn from_token(auth_token: &str) -> Result<Client, Error> {
-        log::debug!("New client with auth token: {:?}", auth_token);
+    /// This function will return an error if:
+    /// - The HTTP client cannot be built from the `HttpConfig`.
+    /// - There is no store, or it cannot be read or holds no tokens (`Error::NoStoredToken`).
+    /// - Exchanging the device token for a user token fails.
+    /// - The new user token cannot be decoded (`Error::InvalidToken`).
+    /// - Saving the new tokens fails.
+    pub async fn connect(self) -> Result<Client, Error> {
+        let http = self.http.build_client()?;
+        let token_store = self.token_store.ok_or(Error::NoStoredToken)?;
+        let tokens = token_store.load()?.ok_or(Error::NoStoredToken)?;
+        log::debug!("New client from stored tokens");
         //let storage_url = endpoints::discover_storage(auth_token).await?;
-        Ok(Client {
-            auth_token: auth_token.to_string(),
-            storage_url: endpoints::STORAGE_API_URL_ROOT.to_string(),
-        })
+        let stored_user_token = tokens
+            .user_token
+            .as_deref()
+            .and_then(|raw| UserToken::parse(raw).ok())
+            .filter(|token| !token.expires_within(USER_TOKEN_REFRESH_MARGIN));
+        let (user_token, exchanged) = match stored_user_token {
+            Some(token) => (token, false),
+            None => {
+                let raw = endpoints::refresh_token(
+                    &http,
+                    &self.config,
+                    &self.retry,
+                    &tokens.device_token,
+                )
+                .await?;
+                (UserToken::parse(&raw)?, true)
+            }
+        };
+        log::debug!("Using user token: {:?}", user_token);
+
+        let client = Client {
+            http,
+            config: self.config,
+            retry: self.retry,
+            session: RwLock::new(Session {
+                device_token: tokens.device_token,
+                user_token,
+                refreshes: 0,
+            }),
+            token_store,
+            cache: self.cache,
+        };
+        if exchanged {
+            client.save_tokens().await?;
+        }
+        Ok(client)
+    }
+}
+
+impl Client {
+    /// Creates a builder to configure a new `Client`.
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::new()
+    }
+
+    /// Creates a new `Client` instance from an existing device token.
+    ///
+    /// The tokens are only kept in memory. Use `Client::from_store` to have
+    /// refreshed tokens persisted.
+    ///
+    /// # Arguments
+    ///
+    /// * `device_token` - A string slice containing the device token.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing:
+    /// - `Ok(Client)`: A new `Client` instance with the provided token.
+    /// - `Err(Error)`: An error if exchanging the device token for a user token fails.
+    pub async fn from_token(device_token: &str) -> Result<Client, Error> {
+        Client::builder().from_token(device_token).await
+    }
+
+    /// Creates a new `Client` instance from the tokens kept in a `TokenStore`.
+    ///
+    /// See `ClientBuilder::connect` for how the stored tokens are used.
+    ///
+    /// # Arguments
+    ///
+    /// * `token_store` - The store to load tokens from and save refreshed tokens to.
+    pub async fn from_store(token_store: Arc<dyn TokenStore>) -> Result<Client, Error> {
+        Client::builder().token_store(token_store).connect().await
     }
 
     /// Creates a new `Client` instance by registering with the reMarkable Cloud using a provided code.
@@ -61,20 +274,70 @@ impl Client {
     /// - The registration process with the reMarkable Cloud fails.
     /// - Creating a new `Client` from the obtained token fails.
     pub async fn new(code: &str) -> Result<Client, Error> {
-        log::debug!(
-            "Registering client with reMarkable Cloud using code: {:?}",
-            code
-        );
-        let auth_token = endpoints::register_client(code).await?;
-        Ok(Client::from_token(&auth_token).await?)
+        Client::builder().register(code).await
+    }
+
+    /// Registers with the reMarkable Cloud using a provided code and keeps the
+    /// resulting tokens in `token_store`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - The registration process with the reMarkable Cloud fails.
+    /// - Saving the device token or creating the client from it fails.
+    pub async fn register(code: &str, token_store: Arc<dyn TokenStore>) -> Result<Client, Error> {
+        Client::builder()
+            .token_store(token_store)
+            .register(code)
+            .await
     }
 
-    /// Refreshes the authentication token for the client.
+    /// The hosts the client talks to.
+    pub fn config(&self) -> &ClientConfig {
+        &self.config
+    }
+
+    /// Returns the device token the client was registered with.
+    pub async fn device_token(&self) -> String {
+        self.session.read().await.device_token.clone()
+    }
+
+    /// Returns a user token to authenticate requests with, refreshing it first
+    /// if it is about to expire.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the token needs refreshing and the refresh fails.
+    pub async fn user_token(&self) -> Result<UserToken, Error> {
+        Ok(self.current_token().await?.0)
+    }
+
+    /// Returns a user token like `user_token`, together with the number of
+    /// refreshes it came from.
+    async fn current_token(&self) -> Result<(UserToken, u64), Error> {
+        let refreshes = {
+            let session = self.session.read().await;
+            if !session.user_token.expires_within(USER_TOKEN_REFRESH_MARGIN) {
+                return Ok((session.user_token.clone(), session.refreshes));
+            }
+            session.refreshes
+        };
+        self.refresh_token_after(Some(refreshes)).await?;
+        let session = self.session.read().await;
+        Ok((session.user_token.clone(), session.refreshes))
+    }
+
+    /// The sync protocol the account is on, according to the current user token.
+    pub async fn sync_version(&self) -> SyncVersion {
+        self.session.read().await.user_token.sync_version()
+    }
+
+    /// Refreshes the user token for the client.
     ///
     /// This method performs the following steps:
-    /// 1. Requests a new token from the server using the current token.
-    /// 2. Updates the client's auth_token with the new token.
-    /// 3. Saves the new token to the auth_token_file.
+    /// 1. Exchanges the device token for a new user token.
+    /// 2. Updates the client's user token with the new token.
+    /// 3. Saves the new tokens to the client's token store.
     ///
     /// # Returns
     ///
@@ -86,26 +349,650 @@ impl Client {
     ///
     /// This function will return an error if:
     /// - The token refresh request to the server fails.
-    /// - Writing the new token to the auth_token_file fails.
-    pub async fn refresh_token(&mut self) -> Result<(), Error> {
-        log::debug!("Refreshing auth token");
-        self.auth_token = endpoints::refresh_token(&self.auth_token).await?;
-        log::debug!("New auth token: {:?}", self.auth_token);
-        Ok(())
+    /// - The new user token cannot be decoded.
+    /// - Saving the new tokens to the token store fails.
+    pub async fn refresh_token(&self) -> Result<(), Error> {
+        self.refresh_token_after(None).await
     }
 
-    pub async fn sync_root(&self) -> Result<(), Error> {
+    /// Refreshes the user token, unless it has been refreshed since `seen`
+    /// refreshes were made.
+    ///
+    /// Requests that find their token expired or rejected at the same time
+    /// all end up here; the first one to take the lock refreshes the token
+    /// and the others go on with the new one.
+    async fn refresh_token_after(&self, seen: Option<u64>) -> Result<(), Error> {
+        {
+            let mut session = self.session.write().await;
+            if seen.is_some_and(|seen| seen != session.refreshes) {
+                log::debug!("User token was already refreshed");
+                return Ok(());
+            }
+            log::debug!("Refreshing user token");
+            let raw = endpoints::refresh_token(
+                &self.http,
+                &self.config,
+                &self.retry,
+                &session.device_token,
+            )
+            .await?;
+            session.user_token = UserToken::parse(&raw)?;
+            session.refreshes += 1;
+            log::debug!("New user token: {:?}", session.user_token);
+        }
+        self.save_tokens().await
+    }
+
+    async fn save_tokens(&self) -> Result<(), Error> {
+        let session = self.session.read().await;
+        self.token_store.save(&Tokens {
+            device_token: session.device_token.clone(),
+            user_token: Some(session.user_token.as_str().to_string()),
+        })
+    }
+
+    /// Runs `request` with a user token, refreshing the token and trying once
+    /// more if the cloud rejects it.
+    async fn authorized<T, F, Fut>(&self, request: F) -> Result<T, Error>
+    where
+        F: Fn(UserToken) -> Fut,
+        Fut: Future<Output = Result<T, Error>>,
+    {
+        let (token, refreshes) = self.current_token().await?;
+        match request(token).await {
+            Err(e) if e.is_unauthorized() => {
+                log::debug!("User token was rejected, refreshing it");
+                self.refresh_token_after(Some(refreshes)).await?;
+                request(self.user_token().await?).await
+            }
+            result => result,
+        }
+    }
+
+    /// Fetches the current root hash and generation of the sync tree.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - The account is not on sync 1.5 (`Error::UnsupportedSyncVersion`).
+    /// - The request to the storage API fails.
+    pub async fn sync_root(&self) -> Result<RootInfo, Error> {
+        log::debug!("Getting the root of the sync tree");
+        let sync_version = self.sync_version().await;
+        if sync_version != SyncVersion::V15 {
+            return Err(Error::UnsupportedSyncVersion(sync_version));
+        }
+        self.authorized(|token| async move {
+            endpoints::sync_root(&self.http, &self.config, &self.retry, token.as_str()).await
+        })
+        .await
+    }
+
+    /// Downloads a blob from the sync storage by its hash.
+    ///
+    /// Downloaded blobs are checked against `hash`, failing with
+    /// `Error::HashMismatch` if they do not match. If the client has a
+    /// `BlobCache`, the blob is read from it when possible and stored in it
+    /// after downloading.
+    pub async fn get_blob(&self, hash: &str) -> Result<Vec<u8>, Error> {
+        if let Some(ref cache) = self.cache {
+            if let Some(blob) = cache.get(hash)? {
+                return Ok(blob);
+            }
+        }
+        let blob = self
+            .authorized(|token| async move {
+                endpoints::get_file(&self.http, &self.config, &self.retry, token.as_str(), hash)
+                    .await
+            })
+            .await?;
+        hash::verify(hash, &blob)?;
+        if let Some(ref cache) = self.cache {
+            if let Err(e) = cache.put(hash, &blob) {
+                log::warn!("Could not cache blob {}: {}", hash, e);
+            }
+        }
+        Ok(blob)
+    }
+
+    /// Downloads and parses the index blob with the given hash.
+    pub async fn get_index(&self, hash: &str) -> Result<Index, Error> {
+        let blob = self.get_blob(hash).await?;
+        Index::parse(&blob)
+    }
+
+    /// Walks the sync tree and returns every item stored in the cloud.
+    ///
+    /// This follows the root hash to the root index, then downloads the index
+    /// of every document and collection listed there to get its files, and the
+    /// `.metadata` file of each item to describe it.
+    ///
+    /// Items that cannot be described, such as those with an invalid id, no
+    /// `.metadata` file or one of a type this crate does not know, are logged
+    /// and left out so the rest of the library can still be listed.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing:
+    /// - `Ok(Tree)`: The items reachable from the current root.
+    /// - `Err(Error)`: An error if any of the blobs cannot be fetched or parsed.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - Fetching the root or any index blob fails.
+    /// - The root index or a document index cannot be parsed.
+    pub async fn get_tree(&self) -> Result<Tree, Error> {
         log::debug!("Getting items stored in the cloud");
-        endpoints::sync_root(&self.storage_url, &self.auth_token).await?;
-        Ok(())
+        let root = self.sync_root().await?;
+        let root_index = self.get_index(&root.hash).await?;
+
+        let mut items = Vec::with_capacity(root_index.entries.len());
+        for entry in root_index.entries {
+            if entry.entry_type != EntryType::Index {
+                log::warn!("Skipping unexpected file {:?} in the root index", entry.id);
+                continue;
+            }
+            let id = match Uuid::parse_str(&entry.id) {
+                Ok(id) => id,
+                Err(e) => {
+                    log::warn!("Skipping item with invalid id {:?}: {}", entry.id, e);
+                    continue;
+                }
+            };
+            let index = self.get_index(&entry.hash).await?;
+            let metadata_name = format!("{}.metadata", entry.id);
+            let Some(metadata) = index.entries.iter().find(|f| f.id == metadata_name) else {
+                log::warn!("Skipping item {} without a .metadata file", entry.id);
+                continue;
+            };
+            let metadata = self.get_blob(&metadata.hash).await?;
+            let object = match objects::from_metadata(id, &entry.hash, &metadata) {
+                Ok(object) => object,
+                Err(e) => {
+                    log::warn!("Skipping item {} with unreadable metadata: {}", entry.id, e);
+                    continue;
+                }
+            };
+            items.push(TreeItem {
+                id: entry.id,
+                hash: entry.hash,
+                files: index.entries,
+                object,
+            });
+        }
+
+        Ok(Tree {
+            hash: root.hash,
+            generation: root.generation,
+            schema: root_index.schema,
+            items,
+        })
     }
 
-    pub async fn upload_file(&self, file: File) -> Result<(), Error> {
-        log::debug!("Uploading a file to the cloud");
-        endpoints::upload_file(&self.storage_url, &self.auth_token, file).await?;
+    /// Uploads a blob to the sync storage under its hash, and keeps a copy in the cache.
+    pub async fn put_blob(&self, hash: &str, filename: &str, blob: &[u8]) -> Result<(), Error> {
+        self.authorized(|token| async move {
+            endpoints::put_file(
+                &self.http,
+                &self.config,
+                &self.retry,
+                token.as_str(),
+                hash,
+                filename,
+                blob,
+            )
+            .await
+        })
+        .await?;
+        if let Some(ref cache) = self.cache {
+            if let Err(e) = cache.put(hash, blob) {
+                log::warn!("Could not cache blob {}: {}", hash, e);
+            }
+        }
         Ok(())
     }
 
+    /// Applies the changes staged in `transaction` to the tree in one root update.
+    ///
+    /// The changes are applied on top of the current root, every new blob is
+    /// uploaded, and the root is then swapped to the new root index. If another
+    /// device updated the root in the meantime, the changes are replayed on top
+    /// of its new root and the update is tried again, so concurrent edits are
+    /// never lost.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing:
+    /// - `Ok(RootInfo)`: The new root, or the current one if the changes did not change anything.
+    /// - `Err(Error)`: An error if the changes cannot be applied or uploaded.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - A change refers to an item that does not exist (`Error::PathNotFound`).
+    /// - Fetching or uploading any blob fails.
+    /// - The root keeps changing after several rebases (`Error::GenerationConflict`).
+    pub async fn commit(&self, transaction: &Transaction) -> Result<RootInfo, Error> {
+        let mut attempt = 1;
+        loop {
+            let root = self.sync_root().await?;
+            let staged = transaction::stage(self, &root, transaction.changes()).await?;
+            if staged.hash == root.hash {
+                log::info!("Nothing to commit, the tree is unchanged");
+                return Ok(root);
+            }
+            for upload in &staged.uploads {
+                self.put_blob(&upload.hash, &upload.filename, &upload.blob)
+                    .await?;
+            }
+
+            let result = self
+                .authorized(|token| {
+                    let hash = &staged.hash;
+                    async move {
+                        endpoints::put_root(
+                            &self.http,
+                            &self.config,
+                            &self.retry,
+                            token.as_str(),
+                            hash,
+                            root.generation,
+                        )
+                        .await
+                    }
+                })
+                .await;
+            match result {
+                Err(Error::GenerationConflict(res)) if attempt < MAX_COMMIT_ATTEMPTS => {
+                    log::warn!(
+                        "Root changed since generation {} ({}), rebasing",
+                        root.generation,
+                        res
+                    );
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Removes every blob from the cache that is not reachable from the current root.
+    ///
+    /// # Returns
+    ///
+    /// The number of blobs that were removed, or 0 if the client has no cache.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the tree cannot be fetched or the
+    /// cache cannot be cleaned up.
+    pub async fn gc_cache(&self) -> Result<usize, Error> {
+        let Some(ref cache) = self.cache else {
+            return Ok(0);
+        };
+        let tree = self.get_tree().await?;
+        let mut reachable = HashSet::from([tree.hash.clone()]);
+        for item in &tree.items {
+            reachable.insert(item.hash.clone());
+            reachable.extend(item.files.iter().map(|f| f.hash.clone()));
+        }
+        cache.gc(&reachable)
+    }
+
+    /// Uploads a PDF or EPUB from the local file at `path` through the web app API.
+    ///
+    /// The type of the document is detected from its first bytes and its
+    /// extension before anything is sent, and it is named after the file
+    /// unless `options` says otherwise.
+    ///
+    /// If the cloud rejects the user token, it is refreshed and the file is
+    /// opened and sent once more, so progress is reported from the start again.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file to upload.
+    /// * `options` - The name, destination, tags and progress callback of the upload.
+    ///
+    /// # Returns
+    ///
+    /// The id of the new document.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - The file cannot be opened or read.
+    /// - The file is neither a PDF nor an EPUB (`Error::UnsupportedFileType`).
+    /// - The destination path is not a collection (`Error::PathNotFound`).
+    /// - The upload fails.
+    pub async fn upload_file(&self, path: &Path, options: &UploadOptions) -> Result<Uuid, Error> {
+        log::debug!("Uploading {:?} to the cloud", path);
+        let file = File::open(path).await?;
+        let length = file.metadata().await?.len();
+        let source = UploadSource::from_reader(file, Some(length));
+        let visible_name = match options.visible_name {
+            Some(ref name) => Some(name.clone()),
+            None => path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned()),
+        };
+        self.upload_source(source, Some(path), visible_name.as_deref(), options)
+            .await
+    }
+
+    /// Uploads a PDF or EPUB held in memory or read from a stream.
+    ///
+    /// The type of the document is detected from its first bytes unless
+    /// `options` sets it. Documents without a `visible_name` are named by the cloud.
+    ///
+    /// If the cloud rejects the user token, it is refreshed and a document
+    /// held in memory is sent once more. A stream cannot be read twice, so for
+    /// `UploadSource::Reader` the refreshed token is only kept for later
+    /// requests and `Error::TokenExpired` is returned; upload it again to retry.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The contents of the document.
+    /// * `options` - The name, destination, tags and progress callback of the upload.
+    ///
+    /// # Returns
+    ///
+    /// The id of the new document.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - Reading from the source fails.
+    /// - The document is neither a PDF nor an EPUB (`Error::UnsupportedFileType`).
+    /// - The destination path is not a collection (`Error::PathNotFound`).
+    /// - The upload fails.
+    pub async fn upload(
+        &self,
+        source: UploadSource,
+        options: &UploadOptions,
+    ) -> Result<Uuid, Error> {
+        self.upload_source(source, None, options.visible_name.as_deref(), options)
+            .await
+    }
+
+    async fn upload_source(
+        &self,
+        source: UploadSource,
+        path: Option<&Path>,
+        visible_name: Option<&str>,
+        options: &UploadOptions,
+    ) -> Result<Uuid, Error> {
+        let (file_type, source) = match options.file_type {
+            Some(file_type) => (file_type, source),
+            None => source.detect(path.or(visible_name.map(Path::new))).await?,
+        };
+
+        let parent = match options.destination {
+            None => Parent::Root,
+            Some(Destination::Parent(parent)) => parent,
+            Some(Destination::Path(ref path)) => self
+                .get_tree()
+                .await?
+                .resolve_parent(path)
+                .ok_or_else(|| Error::PathNotFound(path.clone()))?,
+        };
+        let meta = UploadMeta {
+            file_name: visible_name.map(str::to_string),
+            parent: (parent != Parent::Root).then(|| parent.to_string()),
+            tags: options.tags.clone(),
+        };
+
+        // The upload is streamed, so it cannot go through `authorized`; a copy
+        // to send again is kept when the source can be read a second time.
+        let replay = match source {
+            UploadSource::Bytes(ref bytes) => Some(UploadSource::Bytes(bytes.clone())),
+            UploadSource::Reader { .. } => None,
+        };
+        let (token, refreshes) = self.current_token().await?;
+        let upload = match self
+            .send_upload(&token, source, file_type, &meta, options)
+            .await
+        {
+            Err(e) if e.is_unauthorized() => {
+                log::debug!("User token was rejected, refreshing it");
+                self.refresh_token_after(Some(refreshes)).await?;
+                let source = match (replay, path) {
+                    (Some(source), _) => source,
+                    (None, Some(path)) => {
+                        let file = File::open(path).await?;
+                        let length = file.metadata().await?.len();
+                        UploadSource::from_reader(file, Some(length))
+                    }
+                    (None, None) => return Err(e),
+                };
+                let token = self.user_token().await?;
+                self.send_upload(&token, source, file_type, &meta, options)
+                    .await?
+            }
+            result => result?,
+        };
+        match visible_name {
+            Some(name) => log::info!("Uploaded {:?} as {}", name, upload.doc_id),
+            None => log::info!("Uploaded a document as {}", upload.doc_id),
+        }
+        Ok(upload.doc_id)
+    }
+
+    /// Sends a document to the upload endpoint with the given user token.
+    async fn send_upload(
+        &self,
+        token: &UserToken,
+        source: UploadSource,
+        file_type: FileType,
+        meta: &UploadMeta,
+        options: &UploadOptions,
+    ) -> Result<UploadResponse, Error> {
+        let length = source.len();
+        let body = Body::wrap_stream(source.into_stream(options.progress.clone()));
+        endpoints::upload_file(
+            &self.http,
+            &self.config,
+            token.as_str(),
+            body,
+            length,
+            file_type,
+            meta,
+        )
+        .await
+    }
+
+    /// Downloads and parses the `.content` file of a document.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - The item has no `.content` file (`Error::InvalidMetadata`).
+    /// - Downloading or parsing the file fails.
+    pub async fn get_content(&self, item: &TreeItem) -> Result<Content, Error> {
+        let file = item
+            .file_with_extension("content")
+            .ok_or_else(|| Error::InvalidMetadata(format!("{} has no .content file", item.id)))?;
+        Content::from_bytes(&self.get_blob(&file.hash).await?)
+    }
+
+    /// Downloads the PDF or EPUB a document was created from.
+    ///
+    /// Annotations are not included, see `export_archive` for every file of a document.
+    ///
+    /// # Returns
+    ///
+    /// The type of the document and its contents.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - The item has no PDF or EPUB file, like notebooks and collections (`Error::NoSourceFile`).
+    /// - Downloading the file fails.
+    pub async fn download(&self, item: &TreeItem) -> Result<(FileType, Vec<u8>), Error> {
+        for file_type in [FileType::Pdf, FileType::Epub] {
+            if let Some(file) = item.file_with_extension(file_type.extension()) {
+                log::debug!("Downloading {} of {}", file_type, item.id);
+                return Ok((file_type, self.get_blob(&file.hash).await?));
+            }
+        }
+        Err(Error::NoSourceFile(item.id.clone()))
+    }
+
+    /// Downloads every file of a document: its metadata, content, pages,
+    /// thumbnails and source file.
+    ///
+    /// The result can be written as an `.rmdoc` file with `Archive::to_zip`,
+    /// and uploaded again with `import_archive`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the item id is not a UUID or downloading a file fails.
+    pub async fn export_archive(&self, item: &TreeItem) -> Result<Archive, Error> {
+        let archive = self.get_files(item, |_| true).await?;
+        log::debug!("Exported {} files of {}", archive.files.len(), item.id);
+        Ok(archive)
+    }
+
+    /// Downloads the highlights and typed text of a document.
+    ///
+    /// Only the files that hold them are downloaded, not the PDF or EPUB.
+    /// See `Highlights::from_archive` for what is extracted.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if downloading a file fails, or the
+    /// document's metadata, content or pages cannot be parsed.
+    pub async fn get_highlights(&self, item: &TreeItem) -> Result<Highlights, Error> {
+        let archive = self
+            .get_files(item, |name| {
+                [".metadata", ".content", ".rm"]
+                    .iter()
+                    .any(|extension| name.ends_with(extension))
+            })
+            .await?;
+        Highlights::from_archive(&archive)
+    }
+
+    /// Downloads the files of a document whose names match `filter`.
+    async fn get_files(
+        &self,
+        item: &TreeItem,
+        filter: impl Fn(&str) -> bool,
+    ) -> Result<Archive, Error> {
+        let id = Uuid::parse_str(&item.id)
+            .map_err(|e| Error::InvalidIndex(format!("invalid item id {:?}: {}", item.id, e)))?;
+        let mut files = Vec::with_capacity(item.files.len());
+        for file in item.files.iter().filter(|file| filter(&file.id)) {
+            files.push((file.id.clone(), self.get_blob(&file.hash).await?));
+        }
+        Ok(Archive { id, files })
+    }
+
+    /// Renders a PDF document with everything drawn on it on the tablet, or a
+    /// notebook, as a PDF.
+    ///
+    /// See `render::to_pdf` for how pages are laid out.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - The item is an EPUB (`Error::Render`).
+    /// - Downloading its files fails.
+    /// - Its pages or PDF cannot be read.
+    pub async fn export_pdf(
+        &self,
+        item: &TreeItem,
+        options: &RenderOptions,
+    ) -> Result<Vec<u8>, Error> {
+        let archive = self.export_archive(item).await?;
+        render::to_pdf(&archive, options)
+    }
+
+    /// Uploads a document exported with `export_archive` or by the desktop app.
+    ///
+    /// The document is given a new id, so importing an archive never replaces
+    /// the document it was exported from.
+    ///
+    /// # Arguments
+    ///
+    /// * `archive` - The files of the document.
+    /// * `parent` - The collection to put the document in.
+    ///
+    /// # Returns
+    ///
+    /// The id of the new document.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the archive has no valid
+    /// `.metadata` file or committing the new document fails.
+    pub async fn import_archive(&self, archive: Archive, parent: Parent) -> Result<Uuid, Error> {
+        let id = Uuid::new_v4();
+        let archive = archive.with_id(id).with_parent(parent)?;
+        let mut transaction = Transaction::new();
+        transaction.put_files(id, archive.files);
+        self.commit(&transaction).await?;
+        log::info!("Imported archive as {}", id);
+        Ok(id)
+    }
+
+    /// Creates a notebook with the pages built in `notebook`.
+    ///
+    /// Its pages are written as version 6 `.rm` files and committed like any
+    /// other change, so the notebook syncs to the tablet as if it had been
+    /// created there.
+    ///
+    /// # Returns
+    ///
+    /// The id of the new notebook.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if committing the notebook fails.
+    pub async fn create_notebook(&self, notebook: &Notebook) -> Result<Uuid, Error> {
+        let mut transaction = Transaction::new();
+        let id = transaction.create_notebook(notebook)?;
+        self.commit(&transaction).await?;
+        log::info!("Created notebook {:?} as {}", notebook.visible_name, id);
+        Ok(id)
+    }
+
+    /// Resolves a slash-separated path such as `/Books/Fiction/Dune` to an item.
+    ///
+    /// See `Tree::resolve` for how the trash and duplicate names are handled.
+    ///
+    /// This fetches the whole tree with `get_tree` on every call. To look up
+    /// several paths, fetch the tree once and use `Tree::resolve` instead.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - Fetching the tree fails.
+    /// - There is no item at `path` (`Error::PathNotFound`).
+    pub async fn get_by_path(&self, path: &str) -> Result<TreeItem, Error> {
+        let tree = self.get_tree().await?;
+        tree.resolve(path)
+            .cloned()
+            .ok_or_else(|| Error::PathNotFound(path.to_string()))
+    }
+
+    /// Builds the full path of the item with the given id.
+    ///
+    /// See `Tree::path_of` for how the path relates to duplicate names. Like
+    /// `get_by_path`, this fetches the whole tree on every call; use
+    /// `Tree::path_of` on a tree fetched once to build several paths.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - Fetching the tree fails.
+    /// - There is no item with that id (`Error::PathNotFound`).
+    pub async fn path_of(&self, id: &str) -> Result<String, Error> {
+        let tree = self.get_tree().await?;
+        tree.path_of(id)
+            .ok_or_else(|| Error::PathNotFound(id.to_string()))
+    }
+
     // pub async fn get_storage_url(&mut self) -> Result<String, Error> {
     //     let client = reqwest::Client::new();
     //     let response = client
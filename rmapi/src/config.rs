@@ -0,0 +1,187 @@
+use crate::endpoints::{
+    AUTH_API_URL_ROOT, SERVICE_DISCOVERY_API_URL_ROOT, STORAGE_API_URL_ROOT, WEBAPP_API_URL_ROOT,
+};
+use crate::error::Error;
+use std::time::Duration;
+
+/// The user agent sent when none is configured.
+const DEFAULT_USER_AGENT: &str = concat!("rmapi-rs/", env!("CARGO_PKG_VERSION"));
+
+/// The region of the reMarkable Cloud an account lives in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    Eu,
+    Us,
+}
+
+impl Region {
+    fn name(&self) -> &'static str {
+        match *self {
+            Region::Eu => "eu",
+            Region::Us => "us",
+        }
+    }
+}
+
+/// The hosts a `Client` talks to.
+///
+/// The default points at the production cloud. Every host can be overridden,
+/// for example to target a region or a local mock server in tests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientConfig {
+    /// The root URL of the authentication API, used to register and refresh tokens.
+    pub auth_url: String,
+    /// The root URL of the service discovery API.
+    pub service_discovery_url: String,
+    /// The root URL of the storage API, used for the sync protocol.
+    pub storage_url: String,
+    /// The root URL of the web app API, used for document uploads.
+    pub webapp_url: String,
+}
+
+impl Default for ClientConfig {
+    fn default() -> ClientConfig {
+        ClientConfig {
+            auth_url: AUTH_API_URL_ROOT.to_string(),
+            service_discovery_url: SERVICE_DISCOVERY_API_URL_ROOT.to_string(),
+            storage_url: STORAGE_API_URL_ROOT.to_string(),
+            webapp_url: WEBAPP_API_URL_ROOT.to_string(),
+        }
+    }
+}
+
+impl ClientConfig {
+    /// The production hosts for accounts in `region`.
+    pub fn region(region: Region) -> ClientConfig {
+        ClientConfig {
+            webapp_url: format!("https://web.{}.tectonic.remarkable.com", region.name()),
+            ..ClientConfig::default()
+        }
+    }
+
+    /// Points every host at `base_url`, e.g. `http://127.0.0.1:8080` for a mock server.
+    pub fn local(base_url: &str) -> ClientConfig {
+        let base_url = base_url.trim_end_matches('/');
+        ClientConfig {
+            auth_url: base_url.to_string(),
+            service_discovery_url: base_url.to_string(),
+            storage_url: base_url.to_string(),
+            webapp_url: base_url.to_string(),
+        }
+    }
+
+    /// Overrides the root URL of the authentication API.
+    pub fn with_auth_url(mut self, url: &str) -> ClientConfig {
+        self.auth_url = url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Overrides the root URL of the service discovery API.
+    pub fn with_service_discovery_url(mut self, url: &str) -> ClientConfig {
+        self.service_discovery_url = url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Overrides the root URL of the storage API.
+    pub fn with_storage_url(mut self, url: &str) -> ClientConfig {
+        self.storage_url = url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Overrides the root URL of the web app API.
+    pub fn with_webapp_url(mut self, url: &str) -> ClientConfig {
+        self.webapp_url = url.trim_end_matches('/').to_string();
+        self
+    }
+}
+
+/// How a `Client` makes HTTP requests.
+///
+/// A single HTTP client is built from this and shared by every request, so
+/// connections are pooled across calls.
+#[derive(Debug, Clone)]
+pub struct HttpConfig {
+    /// How long to wait for a connection to be established.
+    pub connect_timeout: Option<Duration>,
+    /// How long to wait for each read from an established connection.
+    pub read_timeout: Option<Duration>,
+    /// A proxy to send HTTPS requests through, e.g. `http://proxy.example.com:3128`.
+    ///
+    /// When unset, the `HTTPS_PROXY` environment variable is still honored.
+    pub https_proxy: Option<String>,
+    /// Extra PEM encoded root certificates to trust, e.g. a corporate CA bundle.
+    pub root_certificates: Vec<Vec<u8>>,
+    /// The `User-Agent` header sent with every request.
+    pub user_agent: String,
+}
+
+impl Default for HttpConfig {
+    fn default() -> HttpConfig {
+        HttpConfig {
+            connect_timeout: Some(Duration::from_secs(30)),
+            read_timeout: Some(Duration::from_secs(60)),
+            https_proxy: None,
+            root_certificates: Vec::new(),
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+        }
+    }
+}
+
+impl HttpConfig {
+    /// Sets how long to wait for a connection to be established.
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> HttpConfig {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets how long to wait for each read from an established connection.
+    pub fn with_read_timeout(mut self, timeout: Duration) -> HttpConfig {
+        self.read_timeout = Some(timeout);
+        self
+    }
+
+    /// Sends HTTPS requests through the proxy at `url`.
+    pub fn with_https_proxy(mut self, url: &str) -> HttpConfig {
+        self.https_proxy = Some(url.to_string());
+        self
+    }
+
+    /// Trusts the PEM encoded root certificates in `pem`, which may hold several.
+    pub fn with_root_certificate(mut self, pem: &[u8]) -> HttpConfig {
+        self.root_certificates.push(pem.to_vec());
+        self
+    }
+
+    /// Sets the `User-Agent` header sent with every request.
+    pub fn with_user_agent(mut self, user_agent: &str) -> HttpConfig {
+        self.user_agent = user_agent.to_string();
+        self
+    }
+
+    /// Builds the HTTP client described by this configuration.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - The proxy URL is invalid.
+    /// - A root certificate cannot be parsed.
+    /// - The TLS backend cannot be initialized.
+    pub fn build_client(&self) -> Result<reqwest::Client, Error> {
+        let mut builder = reqwest::Client::builder().user_agent(&self.user_agent);
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(timeout) = self.read_timeout {
+            builder = builder.read_timeout(timeout);
+        }
+        if let Some(ref proxy) = self.https_proxy {
+            builder = builder.proxy(reqwest::Proxy::https(proxy)?);
+        }
+        for pem in &self.root_certificates {
+            for certificate in reqwest::Certificate::from_pem_bundle(pem)? {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+        Ok(builder.build()?)
+    }
+}
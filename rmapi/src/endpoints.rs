@@ -1,24 +1,26 @@
-use crate::error::Error;
+use crate::config::ClientConfig;
+use crate::error::{ApiResponse, Error};
+use crate::file_type::FileType;
+use crate::retry::{self, Idempotency, RetryPolicy};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
 use const_format::formatcp;
 use log;
-use reqwest::{self, Body};
+use reqwest::{self, Body, Response, StatusCode};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use tokio::fs::File;
-use tokio_util::codec::{BytesCodec, FramedRead};
 use uuid::Uuid;
 
-const AUTH_API_URL_ROOT: &str = "https://webapp-prod.cloud.remarkable.engineering";
+pub const AUTH_API_URL_ROOT: &str = "https://webapp-prod.cloud.remarkable.engineering";
 const AUTH_API_VERSION: &str = "2";
-const NEW_CLIENT_URL: &str =
-    formatcp!("{AUTH_API_URL_ROOT}/token/json/{AUTH_API_VERSION}/device/new");
-const NEW_TOKEN_URL: &str = formatcp!("{AUTH_API_URL_ROOT}/token/json/{AUTH_API_VERSION}/user/new");
+const NEW_CLIENT_ENDPOINT: &str = formatcp!("token/json/{AUTH_API_VERSION}/device/new");
+const NEW_TOKEN_ENDPOINT: &str = formatcp!("token/json/{AUTH_API_VERSION}/user/new");
 
-const SERVICE_DISCOVERY_API_URL_ROOT: &str =
+pub const SERVICE_DISCOVERY_API_URL_ROOT: &str =
     "https://service-manager-production-dot-remarkable-production.appspot.com";
 const STORAGE_API_VERSION: &str = "1";
-const STORAGE_DISCOVERY_API_URL: &str = formatcp!(
-    "{SERVICE_DISCOVERY_API_URL_ROOT}/service/json/{STORAGE_API_VERSION}/document-storage"
-);
+const STORAGE_DISCOVERY_ENDPOINT: &str =
+    formatcp!("service/json/{STORAGE_API_VERSION}/document-storage");
 const GROUP_AUTH: &str = "auth0%7C5a68dc51cb30df1234567890";
 const STORAGE_DISCOVERY_API_VERSION: &str = "2";
 
@@ -27,14 +29,38 @@ pub const WEBAPP_API_URL_ROOT: &str = "https://web.eu.tectonic.remarkable.com";
 
 const DOC_UPLOAD_ENDPOINT: &str = "doc/v2/files";
 const ROOT_SYNC_ENDPOINT: &str = "sync/v4/root";
+const ROOT_UPDATE_ENDPOINT: &str = "sync/v3/root";
 const FILE_SYNC_ENDPOINT: &str = "sync/v3/files";
 
+// Legacy (sync 1.0) document storage endpoints
+#[allow(dead_code)]
 const ITEM_LIST_ENDPOINT: &str = "document-storage/json/2/docs";
+#[allow(dead_code)]
 const ITEM_ENDPOINT: &str = "document-storage/json/2/";
+#[allow(dead_code)]
 const UPLOAD_REQUEST_ENDPOINT: &str = "document-storage/json/2/upload/request";
+#[allow(dead_code)]
 const UPLOAD_STATUS_ENDPOINT: &str = "document-storage/json/2/upload/update-status";
 //const DELETE_ENDPOINT: &str = "/document-storage/json/2/delete";
 
+/// Turns an error status into the matching `Error`, reading the body the server sent with it.
+async fn check_status(response: Response) -> Result<Response, Error> {
+    if response.status().is_success() {
+        Ok(response)
+    } else {
+        Err(Error::from_response(ApiResponse::read(response).await))
+    }
+}
+
+/// Deserializes a JSON response, keeping the body around if it does not match `T`.
+async fn parse_json<T: DeserializeOwned>(response: Response) -> Result<T, Error> {
+    let response = ApiResponse::read(response).await;
+    serde_json::from_str(&response.body).map_err(|e| Error::SchemaParse {
+        reason: e.to_string(),
+        response,
+    })
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[allow(non_snake_case)]
 struct ClientRegistation {
@@ -51,6 +77,9 @@ struct ClientRegistation {
 ///
 /// # Arguments
 ///
+/// * `client` - The HTTP client to send the request with.
+/// * `config` - The hosts to send the request to.
+/// * `retry` - When to retry the request if it fails.
 /// * `code` - A string that holds the registration code provided by reMarkable.
 ///
 /// # Returns
@@ -62,36 +91,50 @@ struct ClientRegistation {
 ///
 /// This function will return an error if:
 /// * The HTTP request fails
-/// * The server responds with an error status
+/// * The code is rejected (`Error::InvalidCode`)
+/// * The server responds with another error status
 /// * The response cannot be parsed
-
-pub async fn register_client(code: &str) -> Result<String, Error> {
-    log::info!("Registering client with code: {}", code);
+pub async fn register_client(
+    client: &reqwest::Client,
+    config: &ClientConfig,
+    retry: &RetryPolicy,
+    code: &str,
+) -> Result<String, Error> {
+    log::info!("Registering client");
     let registration_info = ClientRegistation {
         code: code.to_string(),
         deviceDesc: "desktop-windows".to_string(),
         deviceID: Uuid::new_v4().to_string(),
     };
 
-    let client = reqwest::Client::new();
-    let response = client
-        .post(NEW_CLIENT_URL)
-        .header("Content-Type", "application/json")
-        .json(&registration_info)
-        .send()
-        .await?;
+    let response = retry::send(retry, Idempotency::NotIdempotent, || {
+        client
+            .post(format!("{}/{}", config.auth_url, NEW_CLIENT_ENDPOINT))
+            .header("Content-Type", "application/json")
+            .json(&registration_info)
+    })
+    .await?;
 
     log::debug!("{:?}", response);
 
-    match response.error_for_status() {
+    match check_status(response).await {
         Ok(res) => {
             let token = res.text().await?;
-            log::debug!("Token: {}", token);
+            log::debug!("Received a device token of {} bytes", token.len());
             Ok(token)
         }
+        Err(Error::Api(res) | Error::TokenExpired(res))
+            if matches!(
+                res.status,
+                StatusCode::BAD_REQUEST | StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN
+            ) =>
+        {
+            log::error!("Registration code rejected: {}", res);
+            Err(Error::InvalidCode(res))
+        }
         Err(e) => {
             log::error!("Error registering client: {}", e);
-            Err(Error::from(e))
+            Err(e)
         }
     }
 }
@@ -103,6 +146,9 @@ pub async fn register_client(code: &str) -> Result<String, Error> {
 ///
 /// # Arguments
 ///
+/// * `client` - The HTTP client to send the request with.
+/// * `config` - The hosts to send the request to.
+/// * `retry` - When to retry the request if it fails.
 /// * `auth_token` - A string that holds the current authentication token.
 ///
 /// # Returns
@@ -114,30 +160,40 @@ pub async fn register_client(code: &str) -> Result<String, Error> {
 ///
 /// This function will return an error if:
 /// * The HTTP request fails
-/// * The server responds with an error status
+/// * The device token is rejected (`Error::TokenExpired`)
+/// * The server responds with another error status
 /// * The response cannot be parsed
-pub async fn refresh_token(auth_token: &str) -> Result<String, Error> {
+pub async fn refresh_token(
+    client: &reqwest::Client,
+    config: &ClientConfig,
+    retry: &RetryPolicy,
+    auth_token: &str,
+) -> Result<String, Error> {
     log::info!("Refreshing token");
-    let client = reqwest::Client::new();
-    let response = client
-        .post(NEW_TOKEN_URL)
-        .bearer_auth(auth_token)
-        .header("Accept", "application/json")
-        .header("Content-Length", "0")
-        .send()
-        .await?;
+    let response = retry::send(retry, Idempotency::Idempotent, || {
+        client
+            .post(format!("{}/{}", config.auth_url, NEW_TOKEN_ENDPOINT))
+            .bearer_auth(auth_token)
+            .header("Accept", "application/json")
+            .header("Content-Length", "0")
+    })
+    .await?;
 
     log::debug!("{:?}", response);
 
-    match response.error_for_status() {
+    match check_status(response).await {
         Ok(res) => {
             let token = res.text().await?;
-            log::debug!("New Token: {}", token);
+            log::debug!("Received a user token of {} bytes", token.len());
             Ok(token)
         }
+        Err(Error::Api(res)) if res.status == StatusCode::FORBIDDEN => {
+            log::error!("Device token rejected: {}", res);
+            Err(Error::TokenExpired(res))
+        }
         Err(e) => {
             log::error!("Error refreshing token: {}", e);
-            Err(Error::from(e))
+            Err(e)
         }
     }
 }
@@ -149,176 +205,444 @@ struct StorageInfo {
     Host: String,
 }
 
-pub async fn discover_storage(auth_token: &str) -> Result<String, Error> {
+pub async fn discover_storage(
+    client: &reqwest::Client,
+    config: &ClientConfig,
+    retry: &RetryPolicy,
+    auth_token: &str,
+) -> Result<String, Error> {
     log::info!("Discovering storage host");
     let discovery_request = vec![
         ("enviorment", "production"),
         ("group", GROUP_AUTH),
         ("apiVer", STORAGE_DISCOVERY_API_VERSION),
     ];
-    let client = reqwest::Client::new();
-    let response = client
-        .get(STORAGE_DISCOVERY_API_URL)
-        .bearer_auth(auth_token)
-        .header("Content-Type", "application/json")
-        .header("Accept", "application/json")
-        .query(&discovery_request)
-        .send()
-        .await?;
+    let response = retry::send(retry, Idempotency::Idempotent, || {
+        client
+            .get(format!(
+                "{}/{}",
+                config.service_discovery_url, STORAGE_DISCOVERY_ENDPOINT
+            ))
+            .bearer_auth(auth_token)
+            .header("Content-Type", "application/json")
+            .header("Accept", "application/json")
+            .query(&discovery_request)
+    })
+    .await?;
 
     log::debug!("{:?}", response);
 
-    match response.error_for_status() {
+    match check_status(response).await {
         Ok(res) => {
-            let storage_info = res.json::<StorageInfo>().await?;
+            let storage_info = parse_json::<StorageInfo>(res).await?;
             log::debug!("Storage Info: {:?}", storage_info);
             Ok(format!("https://{0}", storage_info.Host))
         }
         Err(e) => {
             log::error!("Error discovering storage: {}", e);
-            Err(Error::from(e))
+            Err(e)
         }
     }
 }
 
-pub async fn sync_root(storage_url: &str, auth_token: &str) -> Result<String, Error> {
-    log::info!("Listing items in the rmCloud");
-    let client = reqwest::Client::new();
-    let response = client
-        .get(format!("{}/{}", storage_url, ROOT_SYNC_ENDPOINT))
-        .bearer_auth(auth_token)
-        .header("Accept", "application/json")
-        .header("rm-filename", "roothash")
-        .send()
-        .await?;
+/// The current root of the sync tree as reported by the storage API.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RootInfo {
+    /// The hash of the root index blob.
+    pub hash: String,
+    /// The generation of the root, incremented by the server on every root update.
+    pub generation: u64,
+    /// The schema version of the root index, if reported.
+    #[serde(default)]
+    pub schema_version: Option<u32>,
+}
+
+/// Fetches the current root hash and generation of the sync tree.
+///
+/// # Arguments
+///
+/// * `client` - The HTTP client to send the request with.
+/// * `config` - The hosts to send the request to.
+/// * `retry` - When to retry the request if it fails.
+/// * `auth_token` - The user token used to authenticate the request.
+///
+/// # Returns
+///
+/// * `Result<RootInfo, Error>` - Returns Ok with the root hash and generation on success,
+///   or an Error if the request fails.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// * The HTTP request fails
+/// * The server responds with an error status
+/// * The response does not match `RootInfo` (`Error::SchemaParse`)
+pub async fn sync_root(
+    client: &reqwest::Client,
+    config: &ClientConfig,
+    retry: &RetryPolicy,
+    auth_token: &str,
+) -> Result<RootInfo, Error> {
+    log::info!("Fetching the root of the sync tree");
+    let response = retry::send(retry, Idempotency::Idempotent, || {
+        client
+            .get(format!("{}/{}", config.storage_url, ROOT_SYNC_ENDPOINT))
+            .bearer_auth(auth_token)
+            .header("Accept", "application/json")
+            .header("rm-filename", "roothash")
+    })
+    .await?;
+
+    log::debug!("{:?}", response);
+
+    match check_status(response).await {
+        Ok(res) => {
+            let root = parse_json::<RootInfo>(res).await?;
+            log::debug!("Root: {:?}", root);
+            Ok(root)
+        }
+        Err(e) => {
+            log::error!("Error fetching the sync root: {}", e);
+            Err(e)
+        }
+    }
+}
+
+/// Downloads a blob (an index or a file) from the sync storage by its hash.
+///
+/// # Arguments
+///
+/// * `client` - The HTTP client to send the request with.
+/// * `config` - The hosts to send the request to.
+/// * `retry` - When to retry the request if it fails.
+/// * `auth_token` - The user token used to authenticate the request.
+/// * `hash` - The hash of the blob to download.
+///
+/// # Returns
+///
+/// * `Result<Vec<u8>, Error>` - Returns Ok with the contents of the blob on success,
+///   or an Error if the download fails.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// * The HTTP request fails
+/// * There is no blob with that hash (`Error::NotFound`)
+/// * The server responds with another error status
+pub async fn get_file(
+    client: &reqwest::Client,
+    config: &ClientConfig,
+    retry: &RetryPolicy,
+    auth_token: &str,
+    hash: &str,
+) -> Result<Vec<u8>, Error> {
+    log::info!("Downloading blob {} from the rmCloud", hash);
+    let response = retry::send(retry, Idempotency::Idempotent, || {
+        client
+            .get(format!(
+                "{}/{}/{}",
+                config.storage_url, FILE_SYNC_ENDPOINT, hash
+            ))
+            .bearer_auth(auth_token)
+    })
+    .await?;
+
+    log::debug!("{:?}", response);
+
+    match check_status(response).await {
+        Ok(res) => {
+            let blob = res.bytes().await?;
+            log::debug!("Downloaded {} bytes for blob {}", blob.len(), hash);
+            Ok(blob.to_vec())
+        }
+        Err(e) => {
+            log::error!("Error downloading blob {}: {}", hash, e);
+            Err(e)
+        }
+    }
+}
+
+/// Uploads a blob (an index or a file) to the sync storage under its hash.
+///
+/// Blobs are content addressed, so uploading the same blob twice is harmless
+/// and the request is retried like a download.
+///
+/// # Arguments
+///
+/// * `client` - The HTTP client to send the request with.
+/// * `config` - The hosts to send the request to.
+/// * `retry` - When to retry the request if it fails.
+/// * `auth_token` - The user token used to authenticate the request.
+/// * `hash` - The hash of the blob.
+/// * `filename` - The name of the file the blob holds, such as `<id>.metadata` or `root.docSchema`.
+/// * `blob` - The contents of the blob.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// * The HTTP request fails
+/// * The server responds with an error status
+pub async fn put_file(
+    client: &reqwest::Client,
+    config: &ClientConfig,
+    retry: &RetryPolicy,
+    auth_token: &str,
+    hash: &str,
+    filename: &str,
+    blob: &[u8],
+) -> Result<(), Error> {
+    log::info!("Uploading blob {} ({}) to the rmCloud", hash, filename);
+    let response = retry::send(retry, Idempotency::Idempotent, || {
+        client
+            .put(format!(
+                "{}/{}/{}",
+                config.storage_url, FILE_SYNC_ENDPOINT, hash
+            ))
+            .bearer_auth(auth_token)
+            .header("Content-Type", "application/octet-stream")
+            .header("rm-filename", filename)
+            .body(blob.to_vec())
+    })
+    .await?;
 
     log::debug!("{:?}", response);
 
-    match response.error_for_status() {
+    match check_status(response).await {
+        Ok(_) => {
+            log::debug!("Uploaded {} bytes for blob {}", blob.len(), hash);
+            Ok(())
+        }
+        Err(e) => {
+            log::error!("Error uploading blob {}: {}", hash, e);
+            Err(e)
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct RootUpdate<'a> {
+    hash: &'a str,
+    generation: u64,
+    broadcast: bool,
+}
+
+/// Points the root of the sync tree at a new root index.
+///
+/// The update only succeeds if the root is still at `generation`, so changes
+/// made by another device since the root was read are never overwritten.
+///
+/// # Arguments
+///
+/// * `client` - The HTTP client to send the request with.
+/// * `config` - The hosts to send the request to.
+/// * `retry` - When to retry the request if it fails.
+/// * `auth_token` - The user token used to authenticate the request.
+/// * `hash` - The hash of the new root index blob, which must already be uploaded.
+/// * `generation` - The generation of the root the new index was built from.
+///
+/// # Returns
+///
+/// * `Result<RootInfo, Error>` - Returns Ok with the new root hash and generation on success,
+///   or an Error if the update fails.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// * The HTTP request fails
+/// * The root has moved on from `generation` (`Error::GenerationConflict`)
+/// * The server responds with another error status
+/// * The response does not match `RootInfo` (`Error::SchemaParse`)
+pub async fn put_root(
+    client: &reqwest::Client,
+    config: &ClientConfig,
+    retry: &RetryPolicy,
+    auth_token: &str,
+    hash: &str,
+    generation: u64,
+) -> Result<RootInfo, Error> {
+    log::info!("Updating the root of the sync tree to {}", hash);
+    let update = RootUpdate {
+        hash,
+        generation,
+        broadcast: true,
+    };
+    let response = retry::send(retry, Idempotency::NotIdempotent, || {
+        client
+            .put(format!("{}/{}", config.storage_url, ROOT_UPDATE_ENDPOINT))
+            .bearer_auth(auth_token)
+            .header("Accept", "application/json")
+            .json(&update)
+    })
+    .await?;
+
+    log::debug!("{:?}", response);
+
+    match check_status(response).await {
         Ok(res) => {
-            let root_hash = res.text().await?;
-            log::debug!("Root Hash: {}", root_hash);
-            Ok(root_hash)
+            let root = parse_json::<RootInfo>(res).await?;
+            log::debug!("New root: {:?}", root);
+            Ok(root)
         }
         Err(e) => {
-            log::error!("Error listing items: {}", e);
-            Err(Error::from(e))
+            log::error!("Error updating the sync root: {}", e);
+            Err(e)
         }
     }
 }
 
-// pub async fn put_content(storage_url: &str, auth_token: &str, content) {
-//     log::info!("Listing items in the rmCloud");
-//     let client = reqwest::Client::new();
-//     let response = client
-//         .get(format!("{}/{}", storage_url, ROOT_SYNC_ENDPOINT))
-//         .bearer_auth(auth_token)
-//         .header("Accept", "application/json")
-//         .header("rm-filename", "roothash")
-//         .send()
-//         .await?;
-
-//     log::debug!("{:?}", response);
-
-//     match response.error_for_status() {
-//         Ok(res) => {
-//             let root_hash = res.text().await?;
-//             log::debug!("Root Hash: {}", root_hash);
-//             Ok(root_hash)
-//         }
-//         Err(e) => {
-//             log::error!("Error listing items: {}", e);
-//             Err(Error::from(e))
-//         }
-//     }
-// }
-
-pub async fn upload_request(_: &str, auth_token: &str) -> Result<String, Error> {
+pub async fn upload_request(
+    client: &reqwest::Client,
+    config: &ClientConfig,
+    retry: &RetryPolicy,
+    auth_token: &str,
+    file_type: FileType,
+) -> Result<String, Error> {
     log::info!("Requesting to upload a document to the rmCloud");
-    let client = reqwest::Client::new();
-    let response = client
-        .get(format!("{}/{}", WEBAPP_API_URL_ROOT, DOC_UPLOAD_ENDPOINT))
-        .bearer_auth(auth_token)
-        .header("Accept", "application/json")
-        .header("rm-Source", "WebLibrary")
-        .header("Content-Type", "application/pdf")
-        .send()
-        .await?;
+    let response = retry::send(retry, Idempotency::Idempotent, || {
+        client
+            .get(format!("{}/{}", config.webapp_url, DOC_UPLOAD_ENDPOINT))
+            .bearer_auth(auth_token)
+            .header("Accept", "application/json")
+            .header("rm-Source", "WebLibrary")
+            .header("Content-Type", file_type.content_type())
+    })
+    .await?;
 
     log::debug!("{:?}", response);
 
-    match response.error_for_status() {
+    match check_status(response).await {
         Ok(res) => {
             let upload_request_resp = res.text().await?;
             log::debug!("Upload request response: {}", upload_request_resp);
             Ok(upload_request_resp)
         }
         Err(e) => {
-            log::error!("Error listing items: {}", e);
-            Err(Error::from(e))
+            log::error!("Error requesting an upload: {}", e);
+            Err(e)
         }
     }
 }
 
-pub async fn upload_file(_: &str, auth_token: &str, file: File) -> Result<String, Error> {
-    log::info!("Requesting to upload a document to the rmCloud");
-    let stream = FramedRead::new(file, BytesCodec::new());
-    let body = Body::wrap_stream(stream);
+/// How an uploaded document should appear, sent base64 encoded in the `rm-Meta` header.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UploadMeta {
+    /// The name shown on the tablet, or `None` to let the cloud pick one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_name: Option<String>,
+    /// The id of the collection to upload into, `"trash"`, or `None` for the root.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+    /// Tags to attach to the document.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
 
-    let client = reqwest::Client::new();
-    let response = client
-        .post(format!("{}/{}", WEBAPP_API_URL_ROOT, DOC_UPLOAD_ENDPOINT))
+/// The answer of the web app API to an upload.
+#[derive(Debug, Clone, Deserialize)]
+pub struct UploadResponse {
+    /// The id the new document was given.
+    #[serde(rename = "docID")]
+    pub doc_id: Uuid,
+    /// The hash of the new document's index blob, if reported.
+    #[serde(default)]
+    pub hash: Option<String>,
+}
+
+/// Uploads a document through the web app API.
+///
+/// The upload is never retried since its body is streamed and can only be sent once.
+///
+/// # Arguments
+///
+/// * `client` - The HTTP client to send the request with.
+/// * `config` - The hosts to send the request to.
+/// * `auth_token` - The user token used to authenticate the request.
+/// * `body` - The contents of the document.
+/// * `length` - The size of the document, sent as its `Content-Length` if known.
+/// * `file_type` - The type of the document, which decides its `Content-Type`.
+/// * `meta` - The name, parent and tags of the document.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// * The HTTP request fails
+/// * The server responds with an error status
+/// * The response does not match `UploadResponse` (`Error::SchemaParse`)
+pub async fn upload_file(
+    client: &reqwest::Client,
+    config: &ClientConfig,
+    auth_token: &str,
+    body: Body,
+    length: Option<u64>,
+    file_type: FileType,
+    meta: &UploadMeta,
+) -> Result<UploadResponse, Error> {
+    match meta.file_name {
+        Some(ref name) => log::info!(
+            "Uploading a {} document named {:?} to the rmCloud",
+            file_type,
+            name
+        ),
+        None => log::info!("Uploading a {} document to the rmCloud", file_type),
+    }
+    let meta = STANDARD.encode(serde_json::to_vec(meta)?);
+    let mut request = client
+        .post(format!("{}/{}", config.webapp_url, DOC_UPLOAD_ENDPOINT))
         .bearer_auth(auth_token)
         .header("Accept-Encoding", "gzip, deflate, br")
         .header("rm-Source", "WebLibrary")
-        .header(
-            "rm-Meta",
-            ""
-        )
-        .header("Content-Type", "application/pdf")
-        .body(body)
-        .send()
-        .await?;
+        .header("rm-Meta", meta)
+        .header("Content-Type", file_type.content_type());
+    if let Some(length) = length {
+        request = request.header("Content-Length", length);
+    }
+    let response = request.body(body).send().await?;
 
     log::debug!("{:?}", response);
 
-    match response.error_for_status() {
+    match check_status(response).await {
         Ok(res) => {
-            let upload_request_resp = res.text().await?;
-            log::debug!("Upload file response: {}", upload_request_resp);
-            Ok(upload_request_resp)
+            let upload = parse_json::<UploadResponse>(res).await?;
+            log::debug!("Upload file response: {:?}", upload);
+            Ok(upload)
         }
         Err(e) => {
-            log::error!("Error listing items: {}", e);
-            Err(Error::from(e))
+            log::error!("Error uploading the document: {}", e);
+            Err(e)
         }
     }
 }
 
-pub async fn get_files(_: &str, auth_token: &str) -> Result<String, Error> {
+pub async fn get_files(
+    client: &reqwest::Client,
+    config: &ClientConfig,
+    retry: &RetryPolicy,
+    auth_token: &str,
+) -> Result<String, Error> {
     log::info!("Requesting files on the rmCloud");
 
-    let client = reqwest::Client::new();
-    let response = client
-        .get(format!("{}/{}", WEBAPP_API_URL_ROOT, DOC_UPLOAD_ENDPOINT))
-        .bearer_auth(auth_token)
-        .header("Accept", "application/json")
-        .header("rm-Source", "WebLibrary")
-        .header("Content-Type", "application/pdf")
-        .send()
-        .await?;
+    let response = retry::send(retry, Idempotency::Idempotent, || {
+        client
+            .get(format!("{}/{}", config.webapp_url, DOC_UPLOAD_ENDPOINT))
+            .bearer_auth(auth_token)
+            .header("Accept", "application/json")
+            .header("rm-Source", "WebLibrary")
+            .header("Content-Type", "application/pdf")
+    })
+    .await?;
 
     log::debug!("{:?}", response);
 
-    match response.error_for_status() {
+    match check_status(response).await {
         Ok(res) => {
             let upload_request_resp = res.text().await?;
             log::debug!("Get files request response: {}", upload_request_resp);
             Ok(upload_request_resp)
         }
         Err(e) => {
-            log::error!("Error listing items: {}", e);
-            Err(Error::from(e))
+            log::error!("Error requesting files: {}", e);
+            Err(e)
         }
     }
 }
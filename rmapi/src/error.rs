@@ -1,12 +1,110 @@
+use crate::retry;
+use crate::token::SyncVersion;
 use reqwest;
+use reqwest::StatusCode;
 use std::error;
 use std::fmt;
 use std::io;
+use std::time::Duration;
+
+/// The status and body of a response the cloud sent back.
+#[derive(Debug, Clone)]
+pub struct ApiResponse {
+    /// The HTTP status of the response.
+    pub status: StatusCode,
+    /// The body of the response, usually a short message from the server.
+    pub body: String,
+    /// How long the server asked to wait before trying again, if it did.
+    pub retry_after: Option<Duration>,
+}
+
+impl ApiResponse {
+    /// Reads the status, `Retry-After` header and body of `response`.
+    pub(crate) async fn read(response: reqwest::Response) -> ApiResponse {
+        let status = response.status();
+        let retry_after = retry::retry_after(&response);
+        let body = response.text().await.unwrap_or_default();
+        ApiResponse {
+            status,
+            body,
+            retry_after,
+        }
+    }
+}
+
+impl fmt::Display for ApiResponse {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let body = self.body.trim();
+        if body.is_empty() {
+            self.status.fmt(f)
+        } else {
+            write!(f, "{}: {}", self.status, body)
+        }
+    }
+}
 
 #[derive(Debug)]
 pub enum Error {
     Io(io::Error),
     Reqwest(reqwest::Error),
+    Json(serde_json::Error),
+    /// The one-time code used to register was rejected.
+    InvalidCode(ApiResponse),
+    /// The device or user token was rejected, usually because it expired or was revoked.
+    TokenExpired(ApiResponse),
+    /// The requested blob or document does not exist.
+    NotFound(ApiResponse),
+    /// The root was changed by someone else since it was read.
+    GenerationConflict(ApiResponse),
+    /// Too many requests were sent, see `ApiResponse::retry_after`.
+    RateLimited(ApiResponse),
+    /// The uploaded document is larger than the cloud accepts.
+    PayloadTooLarge(ApiResponse),
+    /// The document is not of a type the cloud accepts, either as detected
+    /// before uploading it or as reported by the cloud.
+    UnsupportedFileType {
+        reason: String,
+        response: Option<ApiResponse>,
+    },
+    /// Any other error status returned by the cloud.
+    Api(ApiResponse),
+    /// The cloud answered with a body that does not match the expected schema.
+    SchemaParse {
+        response: ApiResponse,
+        reason: String,
+    },
+    /// A sync index or root blob could not be parsed or built.
+    InvalidIndex(String),
+    /// A downloaded blob does not match the hash it was requested by.
+    HashMismatch {
+        expected: String,
+        actual: String,
+    },
+    /// The `.metadata` or `.content` of an item is missing or could not be parsed.
+    InvalidMetadata(String),
+    /// No item exists at the given path or id.
+    PathNotFound(String),
+    /// An item was to be moved into itself or into a collection inside it.
+    InvalidMove {
+        id: String,
+        parent: String,
+    },
+    /// The document has no source PDF or EPUB, such as a notebook.
+    NoSourceFile(String),
+    /// A `.rmdoc` archive could not be read or written.
+    InvalidArchive(String),
+    /// An `.rm` file could not be parsed.
+    InvalidLinesFile(String),
+    /// A page could not be rendered.
+    Render(String),
+    /// A PDF could not be read or written.
+    InvalidPdf(String),
+    /// The client was built from a token store that is missing or holds no tokens.
+    NoStoredToken,
+    /// A user token is not a JWT whose claims could be read.
+    InvalidToken(String),
+    /// The account does not use sync 1.5, the only sync protocol supported.
+    UnsupportedSyncVersion(SyncVersion),
 }
 
 impl fmt::Display for Error {
@@ -14,15 +112,123 @@ impl fmt::Display for Error {
         match *self {
             Error::Io(ref err) => err.fmt(f),
             Error::Reqwest(ref err) => err.fmt(f),
+            Error::Json(ref err) => err.fmt(f),
+            Error::InvalidCode(ref res) => write!(f, "Invalid registration code ({})", res),
+            Error::TokenExpired(ref res) => write!(f, "Token expired or invalid ({})", res),
+            Error::NotFound(ref res) => write!(f, "Not found ({})", res),
+            Error::GenerationConflict(ref res) => {
+                write!(f, "The root was modified concurrently ({})", res)
+            }
+            Error::RateLimited(ref res) => write!(f, "Rate limited ({})", res),
+            Error::PayloadTooLarge(ref res) => write!(f, "Document too large ({})", res),
+            Error::UnsupportedFileType {
+                ref reason,
+                response: None,
+            } => write!(f, "Unsupported file type: {}", reason),
+            Error::UnsupportedFileType {
+                ref reason,
+                response: Some(ref res),
+            } => write!(f, "Unsupported file type: {} ({})", reason, res),
+            Error::Api(ref res) => write!(f, "The cloud returned an error ({})", res),
+            Error::SchemaParse {
+                ref response,
+                ref reason,
+            } => write!(
+                f,
+                "Unexpected response from the cloud: {} ({})",
+                reason, response
+            ),
+            Error::InvalidIndex(ref msg) => write!(f, "Invalid sync index: {}", msg),
+            Error::HashMismatch {
+                ref expected,
+                ref actual,
+            } => write!(
+                f,
+                "Blob does not match its hash: expected {}, got {}",
+                expected, actual
+            ),
+            Error::InvalidMetadata(ref msg) => write!(f, "Invalid item metadata: {}", msg),
+            Error::PathNotFound(ref path) => write!(f, "No item found at {:?}", path),
+            Error::InvalidMove { ref id, ref parent } => {
+                write!(f, "Cannot move {} into {}, which is inside it", id, parent)
+            }
+            Error::NoSourceFile(ref id) => write!(f, "Document {} has no PDF or EPUB file", id),
+            Error::InvalidArchive(ref msg) => write!(f, "Invalid document archive: {}", msg),
+            Error::InvalidLinesFile(ref msg) => write!(f, "Invalid .rm file: {}", msg),
+            Error::Render(ref msg) => write!(f, "Rendering failed: {}", msg),
+            Error::InvalidPdf(ref msg) => write!(f, "Invalid PDF: {}", msg),
+            Error::NoStoredToken => write!(f, "No token found in the token store"),
+            Error::InvalidToken(ref msg) => write!(f, "Invalid user token: {}", msg),
+            Error::UnsupportedSyncVersion(ref version) => {
+                write!(
+                    f,
+                    "The account uses an unsupported sync protocol: {:?}",
+                    version
+                )
+            }
         }
     }
 }
 
+impl Error {
+    /// Classifies an error status returned by the cloud.
+    pub(crate) fn from_response(response: ApiResponse) -> Error {
+        match response.status {
+            StatusCode::UNAUTHORIZED => Error::TokenExpired(response),
+            StatusCode::NOT_FOUND => Error::NotFound(response),
+            StatusCode::CONFLICT | StatusCode::PRECONDITION_FAILED => {
+                Error::GenerationConflict(response)
+            }
+            StatusCode::TOO_MANY_REQUESTS => Error::RateLimited(response),
+            StatusCode::PAYLOAD_TOO_LARGE => Error::PayloadTooLarge(response),
+            StatusCode::UNSUPPORTED_MEDIA_TYPE => Error::UnsupportedFileType {
+                reason: "rejected by the cloud".to_string(),
+                response: Some(response),
+            },
+            _ => Error::Api(response),
+        }
+    }
+
+    /// The response the cloud sent back, if this error was caused by one.
+    pub fn response(&self) -> Option<&ApiResponse> {
+        match *self {
+            Error::InvalidCode(ref res)
+            | Error::TokenExpired(ref res)
+            | Error::NotFound(ref res)
+            | Error::GenerationConflict(ref res)
+            | Error::RateLimited(ref res)
+            | Error::PayloadTooLarge(ref res)
+            | Error::Api(ref res) => Some(res),
+            Error::UnsupportedFileType {
+                response: Some(ref response),
+                ..
+            } => Some(response),
+            Error::SchemaParse { ref response, .. } => Some(response),
+            _ => None,
+        }
+    }
+
+    /// The HTTP status the cloud answered with, if this error was caused by a response.
+    pub fn status(&self) -> Option<StatusCode> {
+        match *self {
+            Error::Reqwest(ref err) => err.status(),
+            _ => self.response().map(|res| res.status),
+        }
+    }
+
+    /// Whether the cloud rejected the request because the token was missing, invalid or expired.
+    pub fn is_unauthorized(&self) -> bool {
+        matches!(*self, Error::TokenExpired(_))
+    }
+}
+
 impl error::Error for Error {
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         match *self {
             Error::Io(ref err) => Some(err),
             Error::Reqwest(ref err) => Some(err),
+            Error::Json(ref err) => Some(err),
+            _ => None,
         }
     }
 }
@@ -38,3 +244,15 @@ impl From<reqwest::Error> for Error {
         Error::Reqwest(err)
     }
 }
+
+impl From<lopdf::Error> for Error {
+    fn from(err: lopdf::Error) -> Error {
+        Error::InvalidPdf(err.to_string())
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Error {
+        Error::Json(err)
+    }
+}
@@ -0,0 +1,150 @@
+use crate::error::Error;
+use std::fmt;
+use std::path::Path;
+
+/// Magic bytes every PDF starts with.
+const PDF_MAGIC: &[u8] = b"%PDF-";
+/// Magic bytes of a zip local file header, which every EPUB starts with.
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+/// The uncompressed `mimetype` entry an EPUB should start with, at offset 30 of the zip.
+const EPUB_MIMETYPE_ENTRY: &[u8] = b"mimetypeapplication/epub+zip";
+const EPUB_MIMETYPE_OFFSET: usize = 30;
+
+/// How many bytes from the start of a file `FileType::detect` needs to look at.
+pub const DETECT_LEN: usize = EPUB_MIMETYPE_OFFSET + EPUB_MIMETYPE_ENTRY.len();
+
+/// The kinds of documents the cloud accepts for upload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FileType {
+    Pdf,
+    Epub,
+}
+
+impl FileType {
+    /// The `Content-Type` the document is uploaded with.
+    pub fn content_type(&self) -> &'static str {
+        match *self {
+            FileType::Pdf => "application/pdf",
+            FileType::Epub => "application/epub+zip",
+        }
+    }
+
+    /// The file extension of the document, which is also its `fileType` in `.content` files.
+    pub fn extension(&self) -> &'static str {
+        match *self {
+            FileType::Pdf => "pdf",
+            FileType::Epub => "epub",
+        }
+    }
+
+    /// Detects the type of a document from its first bytes and its file name.
+    ///
+    /// The magic bytes decide: files starting with `%PDF-` are PDFs, and zip
+    /// files are EPUBs if they start with the EPUB `mimetype` entry or are named
+    /// `*.epub`. The extension alone is never trusted, since the cloud rejects
+    /// files whose contents do not match their type.
+    ///
+    /// # Arguments
+    ///
+    /// * `head` - The first bytes of the file, at least `DETECT_LEN` of them if the file is that long.
+    /// * `path` - The name or path of the file, if known.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `Error::UnsupportedFileType` if the file is
+    /// neither a PDF nor an EPUB.
+    pub fn detect(head: &[u8], path: Option<&Path>) -> Result<FileType, Error> {
+        let extension = path
+            .and_then(|p| p.extension())
+            .and_then(|e| e.to_str())
+            .map(str::to_ascii_lowercase);
+
+        if head.starts_with(PDF_MAGIC) {
+            return Ok(FileType::Pdf);
+        }
+        if head.starts_with(ZIP_MAGIC) {
+            let has_epub_mimetype = head
+                .get(EPUB_MIMETYPE_OFFSET..DETECT_LEN)
+                .is_some_and(|entry| entry == EPUB_MIMETYPE_ENTRY);
+            if has_epub_mimetype || extension.as_deref() == Some("epub") {
+                return Ok(FileType::Epub);
+            }
+        }
+
+        let name = path.map_or_else(|| "the document".to_string(), |p| format!("{:?}", p));
+        let reason = match extension.as_deref() {
+            Some(ext @ ("pdf" | "epub")) => {
+                format!("{} is named *.{} but its contents do not match", name, ext)
+            }
+            _ => format!("{} is neither a PDF nor an EPUB", name),
+        };
+        Err(Error::UnsupportedFileType {
+            reason,
+            response: None,
+        })
+    }
+}
+
+impl fmt::Display for FileType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.extension())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn epub_head(with_mimetype: bool) -> Vec<u8> {
+        let mut head = ZIP_MAGIC.to_vec();
+        head.resize(EPUB_MIMETYPE_OFFSET, 0);
+        if with_mimetype {
+            head.extend_from_slice(EPUB_MIMETYPE_ENTRY);
+        } else {
+            head.extend_from_slice(b"META-INF/container.xml");
+        }
+        head
+    }
+
+    #[test]
+    fn detects_by_magic_bytes() {
+        assert_eq!(
+            FileType::detect(b"%PDF-1.7\n", None).unwrap(),
+            FileType::Pdf
+        );
+        assert_eq!(
+            FileType::detect(&epub_head(true), Some(Path::new("book.zip"))).unwrap(),
+            FileType::Epub
+        );
+        assert_eq!(
+            FileType::detect(b"%PDF-1.4", Some(Path::new("mislabeled.epub"))).unwrap(),
+            FileType::Pdf
+        );
+    }
+
+    #[test]
+    fn accepts_epubs_without_a_leading_mimetype_by_extension() {
+        assert_eq!(
+            FileType::detect(&epub_head(false), Some(Path::new("Book.EPUB"))).unwrap(),
+            FileType::Epub
+        );
+        assert!(FileType::detect(&epub_head(false), Some(Path::new("archive.zip"))).is_err());
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        for (head, name) in [
+            (&b"hello"[..], "notes.txt"),
+            (&b"hello"[..], "fake.pdf"),
+            (&b"\x89PNG\r\n"[..], "image.png"),
+        ] {
+            match FileType::detect(head, Some(Path::new(name))) {
+                Err(Error::UnsupportedFileType { reason, response }) => {
+                    assert!(reason.contains(name));
+                    assert!(response.is_none());
+                }
+                other => panic!("expected UnsupportedFileType, got {:?}", other),
+            }
+        }
+    }
+}
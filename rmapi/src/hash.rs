@@ -0,0 +1,177 @@
+use crate::error::Error;
+use crate::sync::{EntryType, Index, IndexEntry, SchemaVersion};
+use sha2::{Digest, Sha256};
+
+/// Computes the hex encoded SHA-256 hash of `data`, which is how file blobs are addressed.
+pub fn sha256_hex(data: &[u8]) -> String {
+    to_hex(&Sha256::digest(data))
+}
+
+/// Builds the index entry for a file of a document.
+///
+/// # Arguments
+///
+/// * `name` - The name of the file, such as `<document id>.metadata`.
+/// * `data` - The contents of the file.
+pub fn file_entry(name: &str, data: &[u8]) -> IndexEntry {
+    IndexEntry {
+        hash: sha256_hex(data),
+        entry_type: EntryType::File,
+        id: name.to_string(),
+        subfiles: 0,
+        size: data.len() as u64,
+    }
+}
+
+/// Computes the hash of a schema 3 index from the hashes of its entries.
+///
+/// The entries are sorted by id, and the hash is taken over their decoded
+/// hashes concatenated in that order, the same way the tablet does it.
+///
+/// # Errors
+///
+/// This function will return `Error::InvalidIndex` if an entry hash is not hex encoded.
+pub fn document_hash(entries: &[IndexEntry]) -> Result<String, Error> {
+    let mut entries: Vec<&IndexEntry> = entries.iter().collect();
+    entries.sort_by(|a, b| a.id.cmp(&b.id));
+
+    let mut hasher = Sha256::new();
+    for entry in entries {
+        let bytes = from_hex(&entry.hash).ok_or_else(|| {
+            Error::InvalidIndex(format!(
+                "hash of {:?} is not hex encoded: {:?}",
+                entry.id, entry.hash
+            ))
+        })?;
+        hasher.update(bytes);
+    }
+    Ok(to_hex(&hasher.finalize()))
+}
+
+/// Computes the hash an index is addressed by.
+///
+/// Schema 3 indexes are addressed by `document_hash` of their entries, and
+/// schema 4 indexes by the hash of their serialized contents.
+pub fn index_hash(index: &Index) -> Result<String, Error> {
+    match index.schema {
+        SchemaVersion::V3 => document_hash(&index.entries),
+        SchemaVersion::V4 => Ok(sha256_hex(&index.to_bytes())),
+    }
+}
+
+/// Builds an index blob listing `entries` and computes the hash to upload it under.
+///
+/// `document_id` is the document the index belongs to, or `None` for the root index.
+///
+/// # Returns
+///
+/// A `Result` containing:
+/// - `Ok((hash, blob))`: The hash of the index and its serialized contents.
+/// - `Err(Error)`: An error if an entry hash is not hex encoded.
+pub fn build_index(
+    schema: SchemaVersion,
+    document_id: Option<&str>,
+    entries: Vec<IndexEntry>,
+) -> Result<(String, Vec<u8>), Error> {
+    let index = Index {
+        schema,
+        document_id: document_id.map(str::to_string),
+        entries,
+    };
+    let hash = index_hash(&index)?;
+    Ok((hash, index.to_bytes()))
+}
+
+/// Checks that a downloaded blob matches the hash it was requested by.
+///
+/// A blob matches if it hashes to `expected`, or if it is a schema 3 index
+/// whose `document_hash` is `expected`.
+///
+/// # Errors
+///
+/// This function will return `Error::HashMismatch` if the blob does not match.
+pub fn verify(expected: &str, blob: &[u8]) -> Result<(), Error> {
+    let expected = expected.to_ascii_lowercase();
+    let actual = sha256_hex(blob);
+    if actual == expected {
+        return Ok(());
+    }
+    if let Ok(index) = Index::parse(blob) {
+        if index.schema == SchemaVersion::V3
+            && document_hash(&index.entries).ok() == Some(expected.clone())
+        {
+            return Ok(());
+        }
+    }
+    Err(Error::HashMismatch { expected, actual })
+}
+
+/// Whether `hash` looks like a hex encoded SHA-256 hash.
+pub fn is_sha256_hex(hash: &str) -> bool {
+    hash.len() == 64 && hash.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_files_by_contents() {
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        let entry = file_entry("doc.pdf", b"abc");
+        assert_eq!(entry.hash, sha256_hex(b"abc"));
+        assert_eq!(entry.size, 3);
+    }
+
+    #[test]
+    fn document_hash_ignores_entry_order() {
+        let a = file_entry("doc.pdf", b"a");
+        let b = file_entry("doc.content", b"b");
+        let hash = document_hash(&[a.clone(), b.clone()]).unwrap();
+        assert_eq!(hash, document_hash(&[b.clone(), a.clone()]).unwrap());
+
+        let mut concatenated = from_hex(&b.hash).unwrap();
+        concatenated.extend(from_hex(&a.hash).unwrap());
+        assert_eq!(hash, sha256_hex(&concatenated));
+    }
+
+    #[test]
+    fn built_indexes_parse_and_verify() {
+        for schema in [SchemaVersion::V3, SchemaVersion::V4] {
+            let entries = vec![file_entry("doc.pdf", b"a"), file_entry("doc.content", b"b")];
+            let (hash, blob) = build_index(schema, Some("doc"), entries).unwrap();
+            let index = Index::parse(&blob).unwrap();
+            assert_eq!(index.schema, schema);
+            assert_eq!(index.entries[0].id, "doc.content");
+            verify(&hash, &blob).unwrap();
+        }
+    }
+
+    #[test]
+    fn rejects_mismatched_blobs() {
+        match verify(&sha256_hex(b"expected"), b"actual") {
+            Err(Error::HashMismatch { expected, actual }) => {
+                assert_eq!(expected, sha256_hex(b"expected"));
+                assert_eq!(actual, sha256_hex(b"actual"));
+            }
+            other => panic!("expected HashMismatch, got {:?}", other),
+        }
+    }
+}
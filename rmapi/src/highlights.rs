@@ -0,0 +1,377 @@
+//! Extraction of the highlights and typed text of a document as reading notes.
+
+use crate::archive::Archive;
+use crate::error::Error;
+use crate::lines::{Color, LinesFile};
+use crate::objects::{Content, Document};
+use serde::Serialize;
+
+/// What was marked in a document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExcerptKind {
+    /// Text of a PDF or EPUB highlighted with the highlighter.
+    Highlight,
+    /// Text typed on a page with the keyboard.
+    Text,
+}
+
+/// A highlight or a block of typed text, with where it was found.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Excerpt {
+    #[serde(rename = "type")]
+    pub kind: ExcerptKind,
+    /// The page it is on, counted from 1. For pages of a PDF or EPUB this is
+    /// the page of the source file, so pages inserted on the tablet do not
+    /// shift the numbers of the pages after them. Inserted pages have the
+    /// number of the source page they follow, or 0 before the first, and are
+    /// told apart by `inserted`.
+    pub page: u32,
+    /// For a page inserted on the tablet between the pages of a PDF or EPUB,
+    /// which of the pages inserted after `page` it is, counted from 1.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inserted: Option<u32>,
+    pub text: String,
+    /// The name of the colour of a highlight, such as `yellow`; `None` for typed text.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+}
+
+/// The highlights and typed text of a document, in page order.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Highlights {
+    /// The name of the document.
+    pub title: String,
+    pub excerpts: Vec<Excerpt>,
+}
+
+impl Highlights {
+    /// Collects the highlights and typed text of every page of a document.
+    ///
+    /// Highlights are read from the glyph blocks of version 6 pages; older
+    /// pages only ever give typed text. Highlights on a page are ordered by
+    /// where they start in the page's text, when the file records it.
+    ///
+    /// # Arguments
+    ///
+    /// * `archive` - The files of the document. Only its `.metadata`,
+    ///   `.content` and `.rm` files are read.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - The `.metadata` or `.content` file is missing (`Error::InvalidArchive`) or invalid.
+    /// - A page cannot be parsed (`Error::InvalidLinesFile`).
+    pub fn from_archive(archive: &Archive) -> Result<Highlights, Error> {
+        let id = archive.id;
+        let file = |extension: &str| {
+            archive
+                .file(&format!("{}.{}", id, extension))
+                .ok_or_else(|| Error::InvalidArchive(format!("no {}.{} file", id, extension)))
+        };
+        let title = Document::from_metadata(id, "", file("metadata")?)?.visible_name;
+        let content = Content::from_bytes(file("content")?)?;
+
+        let pages = content.pages();
+        // Notebooks have no source pages, so their pages are numbered by position.
+        let has_source = pages.iter().any(|page| page.redirect.is_some());
+        let mut excerpts = Vec::new();
+        let (mut last_source, mut inserted_count) = (0, 0);
+        for (i, page) in pages.iter().enumerate() {
+            let (number, inserted) = match page.redirect {
+                Some(redirect) => {
+                    last_source = redirect + 1;
+                    inserted_count = 0;
+                    (last_source, None)
+                }
+                None if has_source => {
+                    inserted_count += 1;
+                    (last_source, Some(inserted_count))
+                }
+                None => (i as u32 + 1, None),
+            };
+            let Some(data) = archive.file(&format!("{}/{}.rm", id, page.id)) else {
+                continue;
+            };
+            let lines = LinesFile::parse(data)?;
+
+            let mut highlights: Vec<_> = lines.highlights().collect();
+            highlights.sort_by_key(|highlight| highlight.start.unwrap_or(u32::MAX));
+            for highlight in highlights {
+                let text = highlight.text.trim();
+                if text.is_empty() {
+                    continue;
+                }
+                excerpts.push(Excerpt {
+                    kind: ExcerptKind::Highlight,
+                    page: number,
+                    inserted,
+                    text: text.to_string(),
+                    color: Some(color_name(highlight.color)),
+                });
+            }
+            if let Some(ref text) = lines.text {
+                let text = text.plain_text();
+                if !text.trim().is_empty() {
+                    excerpts.push(Excerpt {
+                        kind: ExcerptKind::Text,
+                        page: number,
+                        inserted,
+                        text: text.trim_end().to_string(),
+                        color: None,
+                    });
+                }
+            }
+        }
+        log::debug!("Found {} excerpts in {}", excerpts.len(), id);
+        Ok(Highlights { title, excerpts })
+    }
+
+    /// Writes the excerpts as Markdown, under a heading for each page.
+    ///
+    /// Pages inserted on the tablet are headed by where they were inserted,
+    /// such as `Inserted page 1 after page 3`.
+    ///
+    /// Highlights are quoted and tagged with their colour, such as `#yellow`,
+    /// so notes apps can filter them. Typed text is written as it is.
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = format!("# {}\n", self.title);
+        let mut page = None;
+        for excerpt in &self.excerpts {
+            if page != Some((excerpt.page, excerpt.inserted)) {
+                page = Some((excerpt.page, excerpt.inserted));
+                markdown.push_str(&format!("\n## {}\n", heading(excerpt)));
+            }
+            markdown.push('\n');
+            match excerpt.kind {
+                ExcerptKind::Highlight => {
+                    let quote = excerpt.text.lines().collect::<Vec<_>>().join("\n> ");
+                    markdown.push_str(&format!("> {}", quote));
+                    if let Some(ref color) = excerpt.color {
+                        markdown.push_str(&format!(" #{}", color));
+                    }
+                    markdown.push('\n');
+                }
+                ExcerptKind::Text => {
+                    markdown.push_str(&excerpt.text);
+                    markdown.push('\n');
+                }
+            }
+        }
+        markdown
+    }
+
+    /// Writes the title and excerpts as pretty-printed JSON.
+    pub fn to_json(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+/// The heading of the page an excerpt is on.
+fn heading(excerpt: &Excerpt) -> String {
+    match excerpt.inserted {
+        None => format!("Page {}", excerpt.page),
+        Some(n) if excerpt.page == 0 => format!("Inserted page {} before page 1", n),
+        Some(n) => format!("Inserted page {} after page {}", n, excerpt.page),
+    }
+}
+
+/// The name a colour is given in notes, usable as a tag.
+fn color_name(color: Color) -> String {
+    let name = match color {
+        Color::Black => "black",
+        Color::Gray | Color::GrayOverlap => "gray",
+        Color::White => "white",
+        Color::Yellow | Color::Highlight | Color::YellowHighlight => "yellow",
+        Color::Green | Color::GreenHighlight => "green",
+        Color::Pink => "pink",
+        Color::Blue => "blue",
+        Color::Red => "red",
+        Color::Cyan => "cyan",
+        Color::Magenta => "magenta",
+        Color::Other(id) => return format!("color-{}", id),
+    };
+    name.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lines::{Highlight, Layer, Rectangle};
+
+    fn highlights() -> Highlights {
+        let excerpt = |kind, page, text: &str, color: Option<&str>| Excerpt {
+            kind,
+            page,
+            inserted: None,
+            text: text.to_string(),
+            color: color.map(str::to_string),
+        };
+        Highlights {
+            title: "Dune".to_string(),
+            excerpts: vec![
+                excerpt(
+                    ExcerptKind::Highlight,
+                    3,
+                    "Fear is the mind-killer.",
+                    Some("yellow"),
+                ),
+                excerpt(ExcerptKind::Text, 3, "Litany\nagainst fear", None),
+                excerpt(
+                    ExcerptKind::Highlight,
+                    7,
+                    "The spice\nmust flow.",
+                    Some("green"),
+                ),
+            ],
+        }
+    }
+
+    #[test]
+    fn writes_markdown_by_page() {
+        assert_eq!(
+            highlights().to_markdown(),
+            "# Dune\n\
+             \n## Page 3\n\
+             \n> Fear is the mind-killer. #yellow\n\
+             \nLitany\nagainst fear\n\
+             \n## Page 7\n\
+             \n> The spice\n> must flow. #green\n"
+        );
+    }
+
+    #[test]
+    fn writes_json() {
+        let json: serde_json::Value =
+            serde_json::from_str(&highlights().to_json().unwrap()).unwrap();
+        assert_eq!(json["title"], "Dune");
+        assert_eq!(
+            json["excerpts"][0],
+            serde_json::json!({"type": "highlight", "page": 3, "text": "Fear is the mind-killer.", "color": "yellow"})
+        );
+        assert!(json["excerpts"][1].get("color").is_none());
+    }
+
+    #[test]
+    fn reads_the_title_and_skips_plain_pages() {
+        let id = uuid::Uuid::new_v4();
+        let mut page = b"reMarkable .lines file, version=3          ".to_vec();
+        page.extend_from_slice(&0u32.to_le_bytes());
+        let mut archive = Archive {
+            id,
+            files: vec![
+                (
+                    format!("{}.content", id),
+                    br#"{"fileType": "notebook", "pages": ["a"]}"#.to_vec(),
+                ),
+                (format!("{}/a.rm", id), page),
+            ],
+        };
+        assert!(matches!(
+            Highlights::from_archive(&archive),
+            Err(Error::InvalidArchive(_))
+        ));
+
+        archive.files.push((
+            format!("{}.metadata", id),
+            br#"{"visibleName": "Diary", "type": "DocumentType", "lastModified": "1712349999123"}"#
+                .to_vec(),
+        ));
+        let highlights = Highlights::from_archive(&archive).unwrap();
+        assert_eq!(highlights.title, "Diary");
+        assert!(highlights.excerpts.is_empty());
+        assert_eq!(highlights.to_markdown(), "# Diary\n");
+    }
+
+    #[test]
+    fn numbers_inserted_pages_apart_from_the_source_pages() {
+        let id = uuid::Uuid::new_v4();
+        let page = |text: &str, color| {
+            LinesFile {
+                version: 6,
+                layers: vec![Layer {
+                    name: "Layer 1".to_string(),
+                    visible: true,
+                    strokes: Vec::new(),
+                    highlights: vec![Highlight {
+                        text: text.to_string(),
+                        color,
+                        start: Some(0),
+                        length: text.len() as u32,
+                        rectangles: vec![Rectangle {
+                            x: 10.0,
+                            y: 20.0,
+                            width: 300.0,
+                            height: 40.0,
+                        }],
+                    }],
+                }],
+                text: None,
+            }
+            .to_bytes()
+        };
+        let content = r#"{"fileType": "pdf", "cPages": {"pages": [
+            {"id": "a", "idx": {"timestamp": "1:2", "value": "ba"}, "redir": {"timestamp": "1:3", "value": 0}},
+            {"id": "b", "idx": {"timestamp": "1:4", "value": "bb"}},
+            {"id": "c", "idx": {"timestamp": "1:5", "value": "bc"}, "redir": {"timestamp": "1:6", "value": 1}}
+        ]}}"#;
+        let archive = Archive {
+            id,
+            files: vec![
+                (format!("{}.content", id), content.as_bytes().to_vec()),
+                (
+                    format!("{}.metadata", id),
+                    br#"{"visibleName": "Dune", "type": "DocumentType", "lastModified": "1712349999123"}"#
+                        .to_vec(),
+                ),
+                (format!("{}/a.rm", id), page("Fear is the mind-killer.", Color::YellowHighlight)),
+                (format!("{}/b.rm", id), page("My own notes", Color::GreenHighlight)),
+                (format!("{}/c.rm", id), page("The spice must flow.", Color::YellowHighlight)),
+            ],
+        };
+
+        let highlights = Highlights::from_archive(&archive).unwrap();
+        let summary: Vec<_> = highlights
+            .excerpts
+            .iter()
+            .map(|e| {
+                (
+                    e.kind,
+                    e.page,
+                    e.inserted,
+                    e.text.as_str(),
+                    e.color.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (
+                    ExcerptKind::Highlight,
+                    1,
+                    None,
+                    "Fear is the mind-killer.",
+                    Some("yellow")
+                ),
+                (
+                    ExcerptKind::Highlight,
+                    1,
+                    Some(1),
+                    "My own notes",
+                    Some("green")
+                ),
+                (
+                    ExcerptKind::Highlight,
+                    2,
+                    None,
+                    "The spice must flow.",
+                    Some("yellow")
+                ),
+            ]
+        );
+        assert!(highlights
+            .to_markdown()
+            .contains("\n## Page 1\n\n> Fear is the mind-killer. #yellow\n\n## Inserted page 1 after page 1\n"));
+    }
+}
@@ -1,8 +1,52 @@
+pub mod archive;
 pub mod endpoints;
 pub mod error;
+pub mod cache;
 pub mod client;
+pub mod config;
+pub mod file_type;
+pub mod hash;
+pub mod highlights;
+pub mod lines;
+pub mod notebook;
+pub mod objects;
+pub mod render;
+pub mod retry;
+pub mod sync;
+pub mod token;
+pub mod token_store;
+pub mod transaction;
+pub mod upload;
 
+/// Re-exports the `Archive` from the `archive` module.
+pub use archive::Archive;
+/// Re-exports the `BlobCache` from the `cache` module.
+pub use cache::BlobCache;
 /// Re-exports the `Client` struct from the `client` module.
-pub use client::Client;
+pub use client::{Client, ClientBuilder};
 /// Re-exports the `Error` type from the `error` module.
-pub use error::Error;
+pub use error::{ApiResponse, Error};
+/// Re-exports the HTTP status type carried by `ApiResponse`.
+pub use reqwest::StatusCode;
+/// Re-exports the item models from the `objects` module.
+pub use objects::{Collection, Content, Document};
+/// Re-exports the `FileType` from the `file_type` module.
+pub use file_type::FileType;
+/// Re-exports the `Highlights` from the `highlights` module.
+pub use highlights::Highlights;
+/// Re-exports the `LinesFile` from the `lines` module.
+pub use lines::LinesFile;
+/// Re-exports the notebook builder from the `notebook` module.
+pub use notebook::{Notebook, NotebookPage};
+/// Re-exports the `RenderOptions` from the `render` module.
+pub use render::RenderOptions;
+/// Re-exports the host configuration from the `config` module.
+pub use config::{ClientConfig, HttpConfig, Region};
+/// Re-exports the `RetryPolicy` from the `retry` module.
+pub use retry::RetryPolicy;
+/// Re-exports the token stores from the `token_store` module.
+pub use token_store::{FileTokenStore, MemoryTokenStore, TokenStore};
+/// Re-exports the `Transaction` from the `transaction` module.
+pub use transaction::{Change, Transaction};
+/// Re-exports the upload options from the `upload` module.
+pub use upload::{Destination, UploadOptions, UploadProgress, UploadSource};
@@ -0,0 +1,192 @@
+use crate::error::Error;
+use std::collections::HashMap;
+use std::fmt;
+
+/// The id of an item in a v6 file: the author who created it and a counter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct CrdtId {
+    pub author: u8,
+    pub counter: u64,
+}
+
+impl CrdtId {
+    /// The id marking either end of a sequence.
+    pub const END: CrdtId = CrdtId::new(0, 0);
+    /// The group every layer of a page hangs off.
+    pub const ROOT: CrdtId = CrdtId::new(0, 1);
+
+    pub const fn new(author: u8, counter: u64) -> CrdtId {
+        CrdtId { author, counter }
+    }
+
+    /// The id `n` places after this one, which is how the characters of a text item are addressed.
+    pub fn offset(&self, n: u64) -> CrdtId {
+        // Counters come straight from the file, so they may be close to overflowing.
+        CrdtId::new(self.author, self.counter.wrapping_add(n))
+    }
+}
+
+impl fmt::Display for CrdtId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.author, self.counter)
+    }
+}
+
+/// An item of a sequence that several devices can edit at once.
+///
+/// Items don't store their position, only the items they were inserted
+/// between, so the order has to be worked out with `order`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SequenceItem<T> {
+    pub id: CrdtId,
+    pub left: CrdtId,
+    pub right: CrdtId,
+    /// Non-zero once the item has been deleted.
+    pub deleted_length: u32,
+    /// The contents of the item, `None` for deleted items.
+    pub value: Option<T>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Key {
+    Start,
+    End,
+    Item(CrdtId),
+}
+
+/// Puts the items of a sequence in order.
+///
+/// Every item has to come after the item on its left and before the item on
+/// its right. Items whose order is not decided by that are ordered by id,
+/// the same way the tablet does it.
+///
+/// # Errors
+///
+/// This function will return `Error::InvalidLinesFile` if the items contradict each other.
+pub fn order<T>(items: Vec<SequenceItem<T>>) -> Result<Vec<SequenceItem<T>>, Error> {
+    let side = |id: CrdtId, end: Key| {
+        if id == CrdtId::END {
+            end
+        } else {
+            Key::Item(id)
+        }
+    };
+
+    // Maps every key to the keys that have to come after it, and counts how
+    // many keys each one still has to wait for.
+    let mut successors: HashMap<Key, Vec<Key>> = HashMap::new();
+    let mut waiting: HashMap<Key, usize> = HashMap::new();
+    for item in &items {
+        let key = Key::Item(item.id);
+        for (before, after) in [
+            (side(item.left, Key::Start), key),
+            (key, side(item.right, Key::End)),
+        ] {
+            successors.entry(before).or_default().push(after);
+            waiting.entry(before).or_default();
+            *waiting.entry(after).or_default() += 1;
+        }
+    }
+
+    let mut by_id: HashMap<CrdtId, SequenceItem<T>> =
+        items.into_iter().map(|item| (item.id, item)).collect();
+    let mut ordered = Vec::with_capacity(by_id.len());
+    // Keys are taken in rounds of everything that is ready, so that items
+    // whose order is not otherwise decided can be ordered by id.
+    let mut ready: Vec<Key> = waiting
+        .iter()
+        .filter(|(_, &count)| count == 0)
+        .map(|(key, _)| *key)
+        .collect();
+    let mut done = 0;
+    while !ready.is_empty() {
+        done += ready.len();
+        let mut ids: Vec<CrdtId> = ready
+            .iter()
+            .filter_map(|key| match *key {
+                Key::Item(id) => Some(id),
+                _ => None,
+            })
+            .collect();
+        ids.sort_unstable();
+        ordered.extend(ids.into_iter().filter_map(|id| by_id.remove(&id)));
+
+        let mut next = Vec::new();
+        for key in ready {
+            for successor in successors.remove(&key).unwrap_or_default() {
+                if let Some(count) = waiting.get_mut(&successor) {
+                    *count -= 1;
+                    if *count == 0 {
+                        next.push(successor);
+                    }
+                }
+            }
+        }
+        ready = next;
+    }
+    if done < waiting.len() {
+        return Err(Error::InvalidLinesFile(
+            "the items of a sequence depend on each other in a cycle".to_string(),
+        ));
+    }
+    Ok(ordered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(id: u64, left: u64, right: u64) -> SequenceItem<u64> {
+        SequenceItem {
+            id: CrdtId::new(1, id),
+            left: CrdtId::new(if left == 0 { 0 } else { 1 }, left),
+            right: CrdtId::new(if right == 0 { 0 } else { 1 }, right),
+            deleted_length: 0,
+            value: Some(id),
+        }
+    }
+
+    fn values(items: Vec<SequenceItem<u64>>) -> Vec<u64> {
+        order(items)
+            .unwrap()
+            .into_iter()
+            .filter_map(|item| item.value)
+            .collect()
+    }
+
+    #[test]
+    fn orders_items_by_their_neighbours() {
+        // 12 was inserted between 10 and 11, and 13 at the very start.
+        let items = vec![
+            item(11, 10, 0),
+            item(12, 10, 11),
+            item(10, 0, 0),
+            item(13, 0, 10),
+        ];
+        assert_eq!(values(items), [13, 10, 12, 11]);
+    }
+
+    #[test]
+    fn orders_concurrent_inserts_by_id() {
+        let items = vec![item(21, 0, 0), item(20, 0, 0)];
+        assert_eq!(values(items), [20, 21]);
+    }
+
+    #[test]
+    fn orders_long_chains_quickly() {
+        // Every character of a long text is an item of its own.
+        let count = 100_000;
+        let items: Vec<_> = (1..=count)
+            .rev()
+            .map(|id| item(id, id - 1, if id == count { 0 } else { id + 1 }))
+            .collect();
+        let values = values(items);
+        assert_eq!(values.len(), count as usize);
+        assert!(values.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn rejects_cycles() {
+        assert!(order(vec![item(1, 2, 0), item(2, 1, 0)]).is_err());
+    }
+}
@@ -0,0 +1,389 @@
+//! Parsing of the `.rm` files that hold the pen strokes of each page.
+//!
+//! Current firmware writes version 6 files, a sequence of tagged blocks that
+//! describe a tree of layers, strokes, highlights and text which several
+//! devices can edit at once. Older firmware wrote the much simpler versions 3
+//! and 5, which only hold layers of strokes.
+
+mod crdt;
+mod reader;
+mod v5;
+mod v6;
+mod writer;
+
+use crate::error::Error;
+
+/// Every `.rm` file starts with this, followed by its version and padded with spaces.
+const HEADER_PREFIX: &[u8] = b"reMarkable .lines file, version=";
+/// The length of the header, including the padding.
+const HEADER_LEN: usize = 43;
+
+/// The width of a page on the tablet, in the units points are given in.
+pub const PAGE_WIDTH: f32 = 1404.0;
+/// The height of a page on the tablet, in the units points are given in.
+pub const PAGE_HEIGHT: f32 = 1872.0;
+
+/// The contents of a page, as parsed from its `.rm` file.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct LinesFile {
+    /// The version of the `.rm` format the file was written with: 3, 5 or 6.
+    pub version: u8,
+    /// The layers of the page, bottom first.
+    pub layers: Vec<Layer>,
+    /// The text typed on the page, if any.
+    pub text: Option<Text>,
+}
+
+/// A layer of a page.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Layer {
+    pub name: String,
+    /// Whether the layer is shown. Hidden layers are still part of the file.
+    pub visible: bool,
+    /// The strokes of the layer, in the order they are drawn.
+    pub strokes: Vec<Stroke>,
+    /// The text highlighted in the layer, in PDFs and EPUBs.
+    pub highlights: Vec<Highlight>,
+}
+
+/// A single pen stroke.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stroke {
+    pub pen: Pen,
+    pub color: Color,
+    /// The thickness chosen for the pen, around `1.0` to `3.0` depending on the pen.
+    pub thickness_scale: f64,
+    pub points: Vec<Point>,
+}
+
+/// A point of a stroke.
+///
+/// `y` is measured from the top of the page and `x` from its left edge in
+/// version 3 and 5 files, but from its horizontal centre in version 6 files,
+/// both in units of `PAGE_WIDTH` and `PAGE_HEIGHT`. Check `LinesFile::version`
+/// before comparing points of files of different versions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Point {
+    pub x: f32,
+    pub y: f32,
+    /// How fast the pen was moving.
+    pub speed: f32,
+    /// The direction the pen was tilted in, in radians.
+    pub direction: f32,
+    /// The width of the stroke at this point.
+    pub width: f32,
+    /// How hard the pen was pressed, from `0.0` to `1.0`.
+    pub pressure: f32,
+}
+
+/// Text highlighted in a PDF or EPUB.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Highlight {
+    pub text: String,
+    pub color: Color,
+    /// Where the highlighted text starts in the text of the page, if known.
+    pub start: Option<u32>,
+    /// The number of characters highlighted.
+    pub length: u32,
+    /// The areas of the page covered by the highlight.
+    pub rectangles: Vec<Rectangle>,
+}
+
+/// An area of a page, in the same units as `Point`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rectangle {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// The text typed on a page with the keyboard.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Text {
+    /// Where the text box starts, in the same units as `Point`.
+    pub x: f64,
+    pub y: f64,
+    pub width: f32,
+    pub paragraphs: Vec<Paragraph>,
+}
+
+/// A line of typed text, ended by a newline.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Paragraph {
+    pub style: ParagraphStyle,
+    pub text: String,
+}
+
+/// How a paragraph of typed text is formatted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParagraphStyle {
+    Basic,
+    Plain,
+    Heading,
+    Bold,
+    Bullet,
+    Bullet2,
+    Checkbox,
+    CheckboxChecked,
+    Other(u8),
+}
+
+impl ParagraphStyle {
+    pub fn from_id(id: u8) -> ParagraphStyle {
+        match id {
+            0 => ParagraphStyle::Basic,
+            1 => ParagraphStyle::Plain,
+            2 => ParagraphStyle::Heading,
+            3 => ParagraphStyle::Bold,
+            4 => ParagraphStyle::Bullet,
+            5 => ParagraphStyle::Bullet2,
+            6 => ParagraphStyle::Checkbox,
+            7 => ParagraphStyle::CheckboxChecked,
+            id => ParagraphStyle::Other(id),
+        }
+    }
+
+    pub fn id(&self) -> u8 {
+        match *self {
+            ParagraphStyle::Basic => 0,
+            ParagraphStyle::Plain => 1,
+            ParagraphStyle::Heading => 2,
+            ParagraphStyle::Bold => 3,
+            ParagraphStyle::Bullet => 4,
+            ParagraphStyle::Bullet2 => 5,
+            ParagraphStyle::Checkbox => 6,
+            ParagraphStyle::CheckboxChecked => 7,
+            ParagraphStyle::Other(id) => id,
+        }
+    }
+}
+
+/// The pen a stroke was drawn with.
+///
+/// Firmware 2.0 introduced new versions of most pens under new ids; both map
+/// to the same variant, and `id` gives the newer one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Pen {
+    Paintbrush,
+    Pencil,
+    Ballpoint,
+    Marker,
+    Fineliner,
+    Highlighter,
+    Eraser,
+    MechanicalPencil,
+    EraseArea,
+    Calligraphy,
+    Shader,
+    Other(u32),
+}
+
+impl Pen {
+    pub fn from_id(id: u32) -> Pen {
+        match id {
+            0 | 12 => Pen::Paintbrush,
+            1 | 14 => Pen::Pencil,
+            2 | 15 => Pen::Ballpoint,
+            3 | 16 => Pen::Marker,
+            4 | 17 => Pen::Fineliner,
+            5 | 18 => Pen::Highlighter,
+            6 => Pen::Eraser,
+            7 | 13 => Pen::MechanicalPencil,
+            8 => Pen::EraseArea,
+            21 => Pen::Calligraphy,
+            23 => Pen::Shader,
+            id => Pen::Other(id),
+        }
+    }
+
+    pub fn id(&self) -> u32 {
+        match *self {
+            Pen::Paintbrush => 12,
+            Pen::Pencil => 14,
+            Pen::Ballpoint => 15,
+            Pen::Marker => 16,
+            Pen::Fineliner => 17,
+            Pen::Highlighter => 18,
+            Pen::Eraser => 6,
+            Pen::MechanicalPencil => 13,
+            Pen::EraseArea => 8,
+            Pen::Calligraphy => 21,
+            Pen::Shader => 23,
+            Pen::Other(id) => id,
+        }
+    }
+}
+
+/// The colour of a stroke or highlight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Color {
+    Black,
+    Gray,
+    White,
+    Yellow,
+    Green,
+    Pink,
+    Blue,
+    Red,
+    GrayOverlap,
+    /// The default colour of highlights.
+    Highlight,
+    GreenHighlight,
+    Cyan,
+    Magenta,
+    YellowHighlight,
+    Other(u32),
+}
+
+impl Color {
+    pub fn from_id(id: u32) -> Color {
+        match id {
+            0 => Color::Black,
+            1 => Color::Gray,
+            2 => Color::White,
+            3 => Color::Yellow,
+            4 => Color::Green,
+            5 => Color::Pink,
+            6 => Color::Blue,
+            7 => Color::Red,
+            8 => Color::GrayOverlap,
+            9 => Color::Highlight,
+            10 => Color::GreenHighlight,
+            11 => Color::Cyan,
+            12 => Color::Magenta,
+            13 => Color::YellowHighlight,
+            id => Color::Other(id),
+        }
+    }
+
+    pub fn id(&self) -> u32 {
+        match *self {
+            Color::Black => 0,
+            Color::Gray => 1,
+            Color::White => 2,
+            Color::Yellow => 3,
+            Color::Green => 4,
+            Color::Pink => 5,
+            Color::Blue => 6,
+            Color::Red => 7,
+            Color::GrayOverlap => 8,
+            Color::Highlight => 9,
+            Color::GreenHighlight => 10,
+            Color::Cyan => 11,
+            Color::Magenta => 12,
+            Color::YellowHighlight => 13,
+            Color::Other(id) => id,
+        }
+    }
+}
+
+impl LinesFile {
+    /// Parses the contents of an `.rm` file.
+    ///
+    /// Blocks of a v6 file that are not understood are skipped, so files
+    /// written by newer firmware still give every stroke that can be read.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `Error::InvalidLinesFile` if the data is not
+    /// an `.rm` file of version 3, 5 or 6, or is truncated or malformed.
+    pub fn parse(data: &[u8]) -> Result<LinesFile, Error> {
+        let version = version(data)?;
+        let body = &data[HEADER_LEN..];
+        let file = match version {
+            3 | 5 => v5::parse(version, body)?,
+            6 => v6::parse(body)?,
+            version => {
+                return Err(Error::InvalidLinesFile(format!(
+                    "unsupported version {}",
+                    version
+                )))
+            }
+        };
+        log::debug!(
+            "Parsed v{} .rm file with {} layers and {} strokes",
+            version,
+            file.layers.len(),
+            file.strokes().count()
+        );
+        Ok(file)
+    }
+
+    /// Writes the page as an `.rm` file of version 6, whatever version it was parsed from.
+    ///
+    /// Points are written as they are. Version 6 measures `x` from the
+    /// centre of the page rather than its left edge, so points of a version
+    /// 3 or 5 file have to be moved by half of `PAGE_WIDTH` first.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = HEADER_PREFIX.to_vec();
+        data.push(b'6');
+        data.resize(HEADER_LEN, b' ');
+        data.extend_from_slice(&v6::write(self));
+        data
+    }
+
+    /// Iterates over the strokes of every layer, bottom layer first.
+    pub fn strokes(&self) -> impl Iterator<Item = &Stroke> {
+        self.layers.iter().flat_map(|layer| layer.strokes.iter())
+    }
+
+    /// Iterates over the highlights of every layer, bottom layer first.
+    pub fn highlights(&self) -> impl Iterator<Item = &Highlight> {
+        self.layers.iter().flat_map(|layer| layer.highlights.iter())
+    }
+}
+
+impl Text {
+    /// The typed text with paragraphs separated by newlines.
+    pub fn plain_text(&self) -> String {
+        self.paragraphs
+            .iter()
+            .map(|paragraph| paragraph.text.as_str())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Reads the version from the header of an `.rm` file.
+fn version(data: &[u8]) -> Result<u8, Error> {
+    let header = data
+        .get(..HEADER_LEN)
+        .filter(|header| header.starts_with(HEADER_PREFIX))
+        .ok_or_else(|| Error::InvalidLinesFile("missing .rm file header".to_string()))?;
+    std::str::from_utf8(&header[HEADER_PREFIX.len()..])
+        .ok()
+        .and_then(|version| version.trim_end().parse().ok())
+        .ok_or_else(|| Error::InvalidLinesFile("invalid version in .rm file header".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_version_from_the_header() {
+        let header = format!("{:<43}", "reMarkable .lines file, version=6");
+        assert_eq!(version(header.as_bytes()).unwrap(), 6);
+        assert!(version(b"%PDF-1.7").is_err());
+    }
+
+    #[test]
+    fn rejects_unsupported_versions() {
+        let header = format!("{:<43}", "reMarkable .lines file, version=4");
+        assert!(matches!(
+            LinesFile::parse(header.as_bytes()),
+            Err(Error::InvalidLinesFile(_))
+        ));
+    }
+
+    #[test]
+    fn maps_pen_and_color_ids() {
+        assert_eq!(Pen::from_id(2), Pen::Ballpoint);
+        assert_eq!(Pen::from_id(Pen::Ballpoint.id()), Pen::Ballpoint);
+        assert_eq!(Pen::from_id(99), Pen::Other(99));
+        for id in 0..15 {
+            assert_eq!(Color::from_id(id).id(), id);
+        }
+    }
+}
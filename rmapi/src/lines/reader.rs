@@ -0,0 +1,208 @@
+use crate::error::Error;
+use crate::lines::crdt::CrdtId;
+
+/// The kinds of values a tag can introduce in a v6 file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TagType {
+    Id = 0xf,
+    Length4 = 0xc,
+    Byte8 = 0x8,
+    Byte4 = 0x4,
+    Byte1 = 0x1,
+}
+
+/// A cursor over the little-endian data of an `.rm` file, or of a block or
+/// subblock in it.
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Reader<'a> {
+        Reader { data, pos: 0 }
+    }
+
+    /// The number of bytes left to read.
+    pub fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.remaining() == 0
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if len > self.remaining() {
+            return Err(Error::InvalidLinesFile(format!(
+                "expected {} more bytes at offset {}, only {} left",
+                len,
+                self.pos,
+                self.remaining()
+            )));
+        }
+        let bytes = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        Ok(self
+            .bytes(N)?
+            .try_into()
+            .expect("slice has the requested length"))
+    }
+
+    pub fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    pub fn f32(&mut self) -> Result<f32, Error> {
+        Ok(f32::from_le_bytes(self.array()?))
+    }
+
+    pub fn f64(&mut self) -> Result<f64, Error> {
+        Ok(f64::from_le_bytes(self.array()?))
+    }
+
+    /// Reads an unsigned LEB128 number.
+    pub fn varuint(&mut self) -> Result<u64, Error> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(Error::InvalidLinesFile(
+            "variable-length number is too long".to_string(),
+        ))
+    }
+
+    /// Reads an untagged id.
+    pub fn crdt_id(&mut self) -> Result<CrdtId, Error> {
+        Ok(CrdtId::new(self.u8()?, self.varuint()?))
+    }
+
+    fn peek_tag(&self) -> Option<(u64, u8)> {
+        let mut peek = Reader {
+            data: self.data,
+            pos: self.pos,
+        };
+        let tag = peek.varuint().ok()?;
+        Some((tag >> 4, (tag & 0xf) as u8))
+    }
+
+    /// Whether the next value is tagged with `index` and `tag_type`.
+    pub fn has_tag(&self, index: u64, tag_type: TagType) -> bool {
+        self.peek_tag() == Some((index, tag_type as u8))
+    }
+
+    fn tag(&mut self, index: u64, tag_type: TagType) -> Result<(), Error> {
+        let offset = self.pos;
+        let tag = self.varuint()?;
+        if (tag >> 4, tag & 0xf) != (index, tag_type as u64) {
+            return Err(Error::InvalidLinesFile(format!(
+                "expected tag {} of type {:?} at offset {}, found tag {} of type {:#x}",
+                index,
+                tag_type,
+                offset,
+                tag >> 4,
+                tag & 0xf
+            )));
+        }
+        Ok(())
+    }
+
+    /// Reads the subblock tagged with `index`, returning a reader over its contents.
+    pub fn subblock(&mut self, index: u64) -> Result<Reader<'a>, Error> {
+        self.tag(index, TagType::Length4)?;
+        let len = self.u32()? as usize;
+        Ok(Reader::new(self.bytes(len)?))
+    }
+
+    pub fn id(&mut self, index: u64) -> Result<CrdtId, Error> {
+        self.tag(index, TagType::Id)?;
+        self.crdt_id()
+    }
+
+    pub fn bool(&mut self, index: u64) -> Result<bool, Error> {
+        self.tag(index, TagType::Byte1)?;
+        Ok(self.u8()? != 0)
+    }
+
+    pub fn int(&mut self, index: u64) -> Result<u32, Error> {
+        self.tag(index, TagType::Byte4)?;
+        self.u32()
+    }
+
+    pub fn float(&mut self, index: u64) -> Result<f32, Error> {
+        self.tag(index, TagType::Byte4)?;
+        self.f32()
+    }
+
+    pub fn double(&mut self, index: u64) -> Result<f64, Error> {
+        self.tag(index, TagType::Byte8)?;
+        self.f64()
+    }
+
+    /// Reads the contents of a string subblock.
+    pub fn string_contents(&mut self) -> Result<String, Error> {
+        let len = self.varuint()? as usize;
+        let _is_ascii = self.u8()?;
+        let bytes = self.bytes(len)?;
+        String::from_utf8(bytes.to_vec())
+            .map_err(|e| Error::InvalidLinesFile(format!("string is not valid UTF-8: {}", e)))
+    }
+
+    pub fn string(&mut self, index: u64) -> Result<String, Error> {
+        self.subblock(index)?.string_contents()
+    }
+
+    /// Reads a last-writer-wins value: the time it was written at, followed by the value itself.
+    pub fn lww<T>(
+        &mut self,
+        index: u64,
+        value: impl FnOnce(&mut Reader<'a>) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let mut block = self.subblock(index)?;
+        let _timestamp = block.id(1)?;
+        value(&mut block)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_tagged_values() {
+        let data = [
+            0x1f, 0x01, 0x96, 0x01, // id 1: 1:150
+            0x24, 0x2a, 0, 0, 0, // int 2: 42
+            0x3c, 6, 0, 0, 0, 3, 1, b'a', b'b', b'c',
+            0xff, // string 3: "abc", then one stray byte
+        ];
+        let mut reader = Reader::new(&data);
+        assert_eq!(reader.id(1).unwrap(), CrdtId::new(1, 150));
+        assert!(reader.has_tag(2, TagType::Byte4));
+        assert_eq!(reader.int(2).unwrap(), 42);
+        assert_eq!(reader.string(3).unwrap(), "abc");
+        assert_eq!(reader.remaining(), 0);
+    }
+
+    #[test]
+    fn rejects_unexpected_tags_and_short_data() {
+        assert!(Reader::new(&[0x24, 1, 0, 0, 0]).id(2).is_err());
+        assert!(Reader::new(&[0x24, 1, 0]).int(2).is_err());
+    }
+}
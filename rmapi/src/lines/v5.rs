@@ -0,0 +1,102 @@
+use crate::error::Error;
+use crate::lines::reader::Reader;
+use crate::lines::{Color, Layer, LinesFile, Pen, Point, Stroke};
+
+/// Parses the body of a version 3 or 5 file.
+///
+/// These are plain lists: a count of layers, each a count of strokes, each a
+/// count of points. Version 5 adds an unknown field to every stroke.
+pub(crate) fn parse(version: u8, body: &[u8]) -> Result<LinesFile, Error> {
+    let mut reader = Reader::new(body);
+    let layer_count = reader.u32()?;
+    let mut layers = Vec::new();
+    for i in 0..layer_count {
+        let stroke_count = reader.u32()?;
+        let mut strokes = Vec::new();
+        for _ in 0..stroke_count {
+            strokes.push(stroke(version, &mut reader)?);
+        }
+        layers.push(Layer {
+            name: format!("Layer {}", i + 1),
+            visible: true,
+            strokes,
+            highlights: Vec::new(),
+        });
+    }
+    Ok(LinesFile {
+        version,
+        layers,
+        text: None,
+    })
+}
+
+fn stroke(version: u8, reader: &mut Reader) -> Result<Stroke, Error> {
+    let pen = Pen::from_id(reader.u32()?);
+    let color = Color::from_id(reader.u32()?);
+    let _padding = reader.u32()?;
+    let thickness_scale = f64::from(reader.f32()?);
+    if version >= 5 {
+        let _unknown = reader.u32()?;
+    }
+    let point_count = reader.u32()?;
+    let mut points = Vec::new();
+    for _ in 0..point_count {
+        points.push(Point {
+            x: reader.f32()?,
+            y: reader.f32()?,
+            speed: reader.f32()?,
+            direction: reader.f32()?,
+            width: reader.f32()?,
+            pressure: reader.f32()?,
+        });
+    }
+    Ok(Stroke {
+        pen,
+        color,
+        thickness_scale,
+        points,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn put_u32(data: &mut Vec<u8>, value: u32) {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn put_f32(data: &mut Vec<u8>, value: f32) {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    #[test]
+    fn parses_layers_of_strokes() {
+        let mut body = Vec::new();
+        put_u32(&mut body, 2); // layers
+        put_u32(&mut body, 1); // strokes in the first layer
+        put_u32(&mut body, 2); // ballpoint
+        put_u32(&mut body, 7); // red
+        put_u32(&mut body, 0);
+        put_f32(&mut body, 2.0);
+        put_u32(&mut body, 0); // v5 only
+        put_u32(&mut body, 1); // points
+        for value in [100.0, 200.0, 0.5, 1.0, 3.0, 0.75] {
+            put_f32(&mut body, value);
+        }
+        put_u32(&mut body, 0); // strokes in the second layer
+
+        let file = parse(5, &body).unwrap();
+        assert_eq!(file.layers.len(), 2);
+        assert_eq!(file.layers[1].name, "Layer 2");
+        let stroke = &file.layers[0].strokes[0];
+        assert_eq!(stroke.pen, Pen::Ballpoint);
+        assert_eq!(stroke.color, Color::Red);
+        assert_eq!(stroke.thickness_scale, 2.0);
+        assert_eq!(stroke.points[0].y, 200.0);
+        assert_eq!(stroke.points[0].pressure, 0.75);
+
+        // Version 3 has no unknown field, so the same bytes don't line up.
+        assert!(parse(3, &body).is_err());
+    }
+}
@@ -0,0 +1,1044 @@
+use crate::error::Error;
+use crate::lines::crdt::{self, CrdtId, SequenceItem};
+use crate::lines::reader::{Reader, TagType};
+use crate::lines::writer::Writer;
+use crate::lines::{
+    Color, Highlight, Layer, LinesFile, Paragraph, ParagraphStyle, Pen, Point, Rectangle, Stroke,
+    Text,
+};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::f32::consts::PI;
+use uuid::Uuid;
+
+const MIGRATION_INFO_BLOCK: u8 = 0x00;
+const SCENE_TREE_BLOCK: u8 = 0x01;
+const TREE_NODE_BLOCK: u8 = 0x02;
+const GLYPH_ITEM_BLOCK: u8 = 0x03;
+const GROUP_ITEM_BLOCK: u8 = 0x04;
+const LINE_ITEM_BLOCK: u8 = 0x05;
+const ROOT_TEXT_BLOCK: u8 = 0x07;
+const TOMBSTONE_ITEM_BLOCK: u8 = 0x08;
+const AUTHOR_IDS_BLOCK: u8 = 0x09;
+const PAGE_INFO_BLOCK: u8 = 0x0a;
+
+/// The size of a point in line blocks of version 1, six floats.
+const POINT_V1_SIZE: usize = 24;
+/// The size of a point in line blocks of version 2 and later.
+const POINT_V2_SIZE: usize = 14;
+
+/// The first counter given to the items `write` creates. Lower ones are
+/// taken by the root group and first layer every page has.
+const FIRST_COUNTER: u64 = 16;
+/// The kind of format that sets the style of a paragraph of typed text.
+const PARAGRAPH_STYLE_FORMAT: u8 = 17;
+
+/// The header of each block.
+struct BlockInfo {
+    current_version: u8,
+    block_type: u8,
+}
+
+/// An item of the scene tree, found in the children of a group.
+enum SceneItem {
+    Group(CrdtId),
+    Stroke(Stroke),
+    Highlight(Highlight),
+}
+
+/// A node of the scene tree, which layers are made of.
+struct Node {
+    label: String,
+    visible: bool,
+}
+
+/// A character of typed text, or a marker that changes its formatting.
+enum TextValue {
+    Char(char),
+    Format,
+}
+
+/// Everything read from the blocks of a file, before it is put together.
+#[derive(Default)]
+struct Scene {
+    nodes: HashMap<CrdtId, Node>,
+    /// The items in each group, by the id of the group.
+    children: HashMap<CrdtId, Vec<SequenceItem<SceneItem>>>,
+    text: Option<Text>,
+}
+
+/// Parses the body of a version 6 file.
+///
+/// The body is a list of blocks, each with a header giving its length and
+/// type. Blocks of unknown types are skipped.
+pub(crate) fn parse(body: &[u8]) -> Result<LinesFile, Error> {
+    let mut reader = Reader::new(body);
+    let mut scene = Scene::default();
+    while !reader.is_empty() {
+        let length = reader.u32()? as usize;
+        let _unknown = reader.u8()?;
+        let _min_version = reader.u8()?;
+        let info = BlockInfo {
+            current_version: reader.u8()?,
+            block_type: reader.u8()?,
+        };
+        let mut block = Reader::new(reader.bytes(length)?);
+        scene.read_block(&info, &mut block)?;
+    }
+    scene.finish()
+}
+
+impl Scene {
+    fn read_block(&mut self, info: &BlockInfo, block: &mut Reader) -> Result<(), Error> {
+        match info.block_type {
+            TREE_NODE_BLOCK => {
+                let id = block.id(1)?;
+                let label = block.lww(2, |r| r.string(2))?;
+                let visible = block.lww(3, |r| r.bool(2))?;
+                self.nodes.insert(id, Node { label, visible });
+            }
+            GROUP_ITEM_BLOCK | LINE_ITEM_BLOCK | GLYPH_ITEM_BLOCK | TOMBSTONE_ITEM_BLOCK => {
+                let (parent, item) = scene_item(info, block)?;
+                self.children.entry(parent).or_default().push(item);
+            }
+            ROOT_TEXT_BLOCK => self.text = Some(root_text(block)?),
+            block_type => log::trace!("Skipping block of type {:#x}", block_type),
+        }
+        Ok(())
+    }
+
+    /// Puts the layers together from the scene tree.
+    ///
+    /// Every group directly below the root is a layer, and everything in the
+    /// groups below a layer is drawn as part of it. Groups that are not linked
+    /// from the root, such as those of deleted layers, are left out.
+    fn finish(mut self) -> Result<LinesFile, Error> {
+        let mut layers = Vec::new();
+        let mut seen = HashSet::from([CrdtId::ROOT]);
+        let mut top_level = Layer::default();
+        for item in self.take_children(CrdtId::ROOT)? {
+            match item {
+                SceneItem::Group(id) => {
+                    let layer = self.layer(id, &mut seen)?;
+                    layers.push(layer);
+                }
+                item => add_item(&mut top_level, item),
+            }
+        }
+
+        if !top_level.strokes.is_empty() || !top_level.highlights.is_empty() {
+            top_level.name = "Layer 1".to_string();
+            top_level.visible = true;
+            layers.insert(0, top_level);
+        }
+        Ok(LinesFile {
+            version: 6,
+            layers,
+            text: self.text,
+        })
+    }
+
+    fn layer(&mut self, id: CrdtId, seen: &mut HashSet<CrdtId>) -> Result<Layer, Error> {
+        let (name, visible) = match self.nodes.get(&id) {
+            Some(node) => (node.label.clone(), node.visible),
+            None => (format!("Layer {}", id), true),
+        };
+        let mut layer = Layer {
+            name,
+            visible,
+            ..Layer::default()
+        };
+        self.collect(id, &mut layer, seen)?;
+        Ok(layer)
+    }
+
+    /// Adds everything in the group `id` and the groups below it to `layer`.
+    ///
+    /// The groups are walked with a stack of their own rather than by
+    /// recursion, so however deeply a file nests them it cannot overflow ours.
+    fn collect(
+        &mut self,
+        id: CrdtId,
+        layer: &mut Layer,
+        seen: &mut HashSet<CrdtId>,
+    ) -> Result<(), Error> {
+        if !seen.insert(id) {
+            return Ok(());
+        }
+        let mut groups = vec![self.take_children(id)?.into_iter()];
+        while let Some(items) = groups.last_mut() {
+            match items.next() {
+                Some(SceneItem::Group(child)) => {
+                    if seen.insert(child) {
+                        groups.push(self.take_children(child)?.into_iter());
+                    }
+                }
+                Some(item) => add_item(layer, item),
+                None => {
+                    groups.pop();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes the items of a group from the scene, in order and without deleted ones.
+    fn take_children(&mut self, id: CrdtId) -> Result<Vec<SceneItem>, Error> {
+        let items = self.children.remove(&id).unwrap_or_default();
+        Ok(crdt::order(items)?
+            .into_iter()
+            .filter(|item| item.deleted_length == 0)
+            .filter_map(|item| item.value)
+            .collect())
+    }
+}
+
+fn add_item(layer: &mut Layer, item: SceneItem) {
+    match item {
+        SceneItem::Stroke(stroke) => layer.strokes.push(stroke),
+        SceneItem::Highlight(highlight) => layer.highlights.push(highlight),
+        SceneItem::Group(_) => {}
+    }
+}
+
+/// Reads an item of the scene tree, returning the id of the group it is in.
+fn scene_item(
+    info: &BlockInfo,
+    block: &mut Reader,
+) -> Result<(CrdtId, SequenceItem<SceneItem>), Error> {
+    let parent = block.id(1)?;
+    let id = block.id(2)?;
+    let left = block.id(3)?;
+    let right = block.id(4)?;
+    let deleted_length = block.int(5)?;
+    let value = if block.has_tag(6, TagType::Length4) {
+        let mut value = block.subblock(6)?;
+        let _item_type = value.u8()?;
+        match info.block_type {
+            GROUP_ITEM_BLOCK => Some(SceneItem::Group(value.id(2)?)),
+            LINE_ITEM_BLOCK => Some(SceneItem::Stroke(line(info, &mut value)?)),
+            GLYPH_ITEM_BLOCK => Some(SceneItem::Highlight(glyph(&mut value)?)),
+            _ => None,
+        }
+    } else {
+        None
+    };
+    let item = SequenceItem {
+        id,
+        left,
+        right,
+        deleted_length,
+        value,
+    };
+    Ok((parent, item))
+}
+
+fn line(info: &BlockInfo, value: &mut Reader) -> Result<Stroke, Error> {
+    let pen = Pen::from_id(value.int(1)?);
+    let color = Color::from_id(value.int(2)?);
+    let thickness_scale = value.double(3)?;
+    let _starting_length = value.float(4)?;
+
+    let mut data = value.subblock(5)?;
+    let point_size = if info.current_version >= 2 {
+        POINT_V2_SIZE
+    } else {
+        POINT_V1_SIZE
+    };
+    let mut points = Vec::with_capacity(data.remaining() / point_size);
+    while data.remaining() >= point_size {
+        let x = data.f32()?;
+        let y = data.f32()?;
+        let point = if info.current_version >= 2 {
+            Point {
+                x,
+                y,
+                speed: f32::from(data.u16()?) / 4.0,
+                width: f32::from(data.u16()?) / 4.0,
+                direction: f32::from(data.u8()?) / 255.0 * 2.0 * PI,
+                pressure: f32::from(data.u8()?) / 255.0,
+            }
+        } else {
+            Point {
+                x,
+                y,
+                speed: data.f32()?,
+                direction: data.f32()?,
+                width: data.f32()?,
+                pressure: data.f32()?,
+            }
+        };
+        points.push(point);
+    }
+    Ok(Stroke {
+        pen,
+        color,
+        thickness_scale,
+        points,
+    })
+}
+
+fn glyph(value: &mut Reader) -> Result<Highlight, Error> {
+    let start = if value.has_tag(2, TagType::Byte4) {
+        Some(value.int(2)?)
+    } else {
+        None
+    };
+    let length = value.int(3)?;
+    let color = Color::from_id(value.int(4)?);
+    let text = value.string(5)?;
+    let mut data = value.subblock(6)?;
+    let count = data.varuint()?;
+    let mut rectangles = Vec::new();
+    for _ in 0..count {
+        rectangles.push(Rectangle {
+            x: data.f64()?,
+            y: data.f64()?,
+            width: data.f64()?,
+            height: data.f64()?,
+        });
+    }
+    Ok(Highlight {
+        text,
+        color,
+        start,
+        length,
+        rectangles,
+    })
+}
+
+/// Reads the text typed on the page.
+///
+/// The text is a sequence of strings that several devices can edit at once,
+/// followed by the style of each paragraph, keyed by the id of the newline
+/// that starts it.
+fn root_text(block: &mut Reader) -> Result<Text, Error> {
+    let _id = block.id(1)?;
+    let mut contents = block.subblock(2)?;
+
+    let mut items = contents.subblock(1)?.subblock(1)?;
+    let count = items.varuint()?;
+    let mut chars = Vec::new();
+    let mut deleted = Vec::new();
+    for _ in 0..count {
+        let mut item = items.subblock(0)?;
+        let id = item.id(2)?;
+        let left = item.id(3)?;
+        let right = item.id(4)?;
+        let deleted_length = item.int(5)?;
+        if !item.has_tag(6, TagType::Length4) {
+            deleted.push(DeletedSpan {
+                id,
+                left,
+                right,
+                length: u64::from(deleted_length),
+            });
+            continue;
+        }
+        let mut value = item.subblock(6)?;
+        let text = value.string_contents()?;
+        if value.has_tag(2, TagType::Byte4) {
+            let _format = value.int(2)?;
+            expand(
+                &mut chars,
+                id,
+                left,
+                right,
+                0,
+                vec![Some(TextValue::Format)],
+            );
+        } else {
+            let values = text.chars().map(|c| Some(TextValue::Char(c))).collect();
+            expand(&mut chars, id, left, right, deleted_length, values);
+        }
+    }
+
+    let anchors: BTreeSet<CrdtId> = chars
+        .iter()
+        .map(|item| (item.left, item.right))
+        .chain(deleted.iter().map(|span| (span.left, span.right)))
+        .flat_map(|(left, right)| [left, right])
+        .collect();
+    for span in deleted {
+        add_deleted(&mut chars, span, &anchors);
+    }
+
+    let mut formats = contents.subblock(2)?.subblock(1)?;
+    let count = formats.varuint()?;
+    let mut styles: HashMap<CrdtId, (CrdtId, ParagraphStyle)> = HashMap::new();
+    for _ in 0..count {
+        let char_id = formats.crdt_id()?;
+        let timestamp = formats.id(1)?;
+        let mut format = formats.subblock(2)?;
+        let _kind = format.u8()?;
+        let style = ParagraphStyle::from_id(format.u8()?);
+        // The style written last wins.
+        match styles.get(&char_id) {
+            Some(&(written, _)) if written > timestamp => {}
+            _ => {
+                styles.insert(char_id, (timestamp, style));
+            }
+        }
+    }
+
+    let mut position = block.subblock(3)?;
+    let x = position.f64()?;
+    let y = position.f64()?;
+    let width = block.float(4)?;
+
+    let style_of = |id: CrdtId| {
+        styles
+            .get(&id)
+            .map_or(ParagraphStyle::Plain, |&(_, style)| style)
+    };
+    let mut paragraphs = vec![Paragraph {
+        style: style_of(CrdtId::END),
+        text: String::new(),
+    }];
+    for item in crdt::order(chars)? {
+        if item.deleted_length != 0 {
+            continue;
+        }
+        match item.value {
+            Some(TextValue::Char('\n')) => paragraphs.push(Paragraph {
+                style: style_of(item.id),
+                text: String::new(),
+            }),
+            Some(TextValue::Char(c)) => paragraphs
+                .last_mut()
+                .expect("there is always a paragraph")
+                .text
+                .push(c),
+            Some(TextValue::Format) | None => {}
+        }
+    }
+    Ok(Text {
+        x,
+        y,
+        width,
+        paragraphs,
+    })
+}
+
+/// Text that was deleted, which keeps an id for every character it had.
+struct DeletedSpan {
+    id: CrdtId,
+    left: CrdtId,
+    right: CrdtId,
+    length: u64,
+}
+
+/// Adds deleted text to a sequence as a few items rather than one per character.
+///
+/// Deleted characters only matter as the neighbours of other items, so
+/// the span gets an item for every character in `anchors`, the ids other
+/// items point at, and one for each run of characters between them. The
+/// length of a span is not backed by any data, so expanding it in full
+/// would let a small file ask for billions of items.
+fn add_deleted(
+    chars: &mut Vec<SequenceItem<TextValue>>,
+    span: DeletedSpan,
+    anchors: &BTreeSet<CrdtId>,
+) {
+    let end = CrdtId::new(span.id.author, span.id.counter.saturating_add(span.length));
+    let mut starts = vec![0];
+    for anchor in anchors.range(span.id..end) {
+        let offset = anchor.counter - span.id.counter;
+        starts.extend([offset, offset + 1]);
+    }
+    starts.retain(|&start| start < span.length);
+    starts.sort_unstable();
+    starts.dedup();
+
+    let ids: Vec<CrdtId> = starts.iter().map(|&start| span.id.offset(start)).collect();
+    for (i, &id) in ids.iter().enumerate() {
+        chars.push(SequenceItem {
+            id,
+            left: i.checked_sub(1).map_or(span.left, |left| ids[left]),
+            right: ids.get(i + 1).copied().unwrap_or(span.right),
+            deleted_length: 1,
+            value: None,
+        });
+    }
+}
+
+/// Splits a text item into one item per character, each with its own id.
+fn expand(
+    chars: &mut Vec<SequenceItem<TextValue>>,
+    id: CrdtId,
+    left: CrdtId,
+    right: CrdtId,
+    deleted_length: u32,
+    values: Vec<Option<TextValue>>,
+) {
+    let count = values.len() as u64;
+    for (i, value) in values.into_iter().enumerate() {
+        let i = i as u64;
+        chars.push(SequenceItem {
+            id: id.offset(i),
+            left: if i == 0 { left } else { id.offset(i - 1) },
+            right: if i + 1 == count {
+                right
+            } else {
+                id.offset(i + 1)
+            },
+            deleted_length,
+            value,
+        });
+    }
+}
+
+/// Hands out ids for the items of a file being written, all by the same author.
+struct Ids {
+    next: u64,
+}
+
+impl Ids {
+    fn next(&mut self) -> CrdtId {
+        self.take(1)
+    }
+
+    /// Reserves `n` ids in a row, returning the first.
+    fn take(&mut self, n: u64) -> CrdtId {
+        let id = CrdtId::new(1, self.next);
+        self.next += n.max(1);
+        id
+    }
+}
+
+/// The ids a layer is written with: the node holding its name, the
+/// timestamp of the name, and the item linking it from the root group.
+struct LayerIds {
+    node: CrdtId,
+    label: CrdtId,
+    item: CrdtId,
+}
+
+/// Writes the body of a version 6 file.
+///
+/// The blocks are laid out the way the tablet lays out a page it created,
+/// with every item written by a single author and in the order given.
+pub(crate) fn write(file: &LinesFile) -> Vec<u8> {
+    let mut ids = Ids {
+        next: FIRST_COUNTER,
+    };
+    let text = file.text.as_ref().map(|text| {
+        let contents = text.plain_text();
+        let first = ids.take(contents.chars().count() as u64);
+        (text, contents, first)
+    });
+    let layers: Vec<LayerIds> = (0..file.layers.len())
+        .map(|i| match i {
+            0 => LayerIds {
+                node: CrdtId::new(0, 11),
+                label: CrdtId::new(0, 12),
+                item: CrdtId::new(0, 13),
+            },
+            _ => LayerIds {
+                node: ids.next(),
+                label: ids.next(),
+                item: ids.next(),
+            },
+        })
+        .collect();
+
+    let mut w = Writer::default();
+    w.block(AUTHOR_IDS_BLOCK, 1, |w| {
+        w.varuint(1).subblock(0, |w| {
+            w.varuint(16).bytes(&Uuid::new_v4().to_bytes_le()).u16(1);
+        });
+    });
+    w.block(MIGRATION_INFO_BLOCK, 1, |w| {
+        w.id(1, CrdtId::new(1, 1)).bool(2, true);
+    });
+    w.block(PAGE_INFO_BLOCK, 1, |w| {
+        let (chars, lines) = text.as_ref().map_or((0, 0), |(text, contents, _)| {
+            (contents.chars().count(), text.paragraphs.len())
+        });
+        w.int(1, 1)
+            .int(2, 0)
+            .int(3, chars as u32)
+            .int(4, lines as u32);
+    });
+    for layer in &layers {
+        w.block(SCENE_TREE_BLOCK, 1, |w| {
+            w.id(1, layer.node).id(2, CrdtId::END).bool(3, true);
+            w.subblock(4, |w| {
+                w.id(1, CrdtId::ROOT);
+            });
+        });
+    }
+    if let Some((text, contents, first)) = text {
+        w.block(ROOT_TEXT_BLOCK, 1, |w| {
+            write_text(w, text, &contents, first, &mut ids)
+        });
+    }
+
+    w.block(TREE_NODE_BLOCK, 1, |w| {
+        write_node(w, CrdtId::ROOT, CrdtId::END, "", true)
+    });
+    for (layer, layer_ids) in file.layers.iter().zip(&layers) {
+        w.block(TREE_NODE_BLOCK, 1, |w| {
+            write_node(
+                w,
+                layer_ids.node,
+                layer_ids.label,
+                &layer.name,
+                layer.visible,
+            )
+        });
+    }
+    let items: Vec<CrdtId> = layers.iter().map(|layer| layer.item).collect();
+    for (i, layer) in layers.iter().enumerate() {
+        w.block(GROUP_ITEM_BLOCK, 1, |w| {
+            write_sequence_item(w, CrdtId::ROOT, &items, i);
+            w.subblock(6, |w| {
+                w.u8(2).id(2, layer.node);
+            });
+        });
+    }
+
+    for (layer, layer_ids) in file.layers.iter().zip(&layers) {
+        let count = layer.strokes.len() + layer.highlights.len();
+        let items: Vec<CrdtId> = (0..count).map(|_| ids.next()).collect();
+        for (i, stroke) in layer.strokes.iter().enumerate() {
+            w.block(LINE_ITEM_BLOCK, 2, |w| {
+                write_sequence_item(w, layer_ids.node, &items, i);
+                w.subblock(6, |w| write_line(w, stroke));
+            });
+        }
+        for (i, highlight) in layer.highlights.iter().enumerate() {
+            w.block(GLYPH_ITEM_BLOCK, 1, |w| {
+                write_sequence_item(w, layer_ids.node, &items, layer.strokes.len() + i);
+                w.subblock(6, |w| write_glyph(w, highlight));
+            });
+        }
+    }
+    w.into_bytes()
+}
+
+fn write_node(w: &mut Writer, id: CrdtId, label_timestamp: CrdtId, label: &str, visible: bool) {
+    w.id(1, id);
+    w.lww(2, label_timestamp, |w| {
+        w.string(2, label);
+    });
+    w.lww(3, CrdtId::END, |w| {
+        w.bool(2, visible);
+    });
+}
+
+/// Writes the fields every scene item starts with, linking item `i` of
+/// `items` between the ones before and after it.
+fn write_sequence_item(w: &mut Writer, parent: CrdtId, items: &[CrdtId], i: usize) {
+    let left = i.checked_sub(1).map_or(CrdtId::END, |left| items[left]);
+    let right = items.get(i + 1).copied().unwrap_or(CrdtId::END);
+    w.id(1, parent)
+        .id(2, items[i])
+        .id(3, left)
+        .id(4, right)
+        .int(5, 0);
+}
+
+fn write_line(w: &mut Writer, stroke: &Stroke) {
+    w.u8(3)
+        .int(1, stroke.pen.id())
+        .int(2, stroke.color.id())
+        .double(3, stroke.thickness_scale)
+        .float(4, 0.0);
+    w.subblock(5, |w| {
+        for point in &stroke.points {
+            let direction = point.direction.rem_euclid(2.0 * PI) / (2.0 * PI);
+            // Casts saturate, so values out of range are clamped.
+            w.f32(point.x)
+                .f32(point.y)
+                .u16((point.speed * 4.0).round() as u16)
+                .u16((point.width * 4.0).round() as u16)
+                .u8((direction * 255.0).round() as u8)
+                .u8((point.pressure * 255.0).round() as u8);
+        }
+    });
+}
+
+fn write_glyph(w: &mut Writer, highlight: &Highlight) {
+    w.u8(1);
+    if let Some(start) = highlight.start {
+        w.int(2, start);
+    }
+    w.int(3, highlight.length)
+        .int(4, highlight.color.id())
+        .string(5, &highlight.text);
+    w.subblock(6, |w| {
+        w.varuint(highlight.rectangles.len() as u64);
+        for rectangle in &highlight.rectangles {
+            w.f64(rectangle.x)
+                .f64(rectangle.y)
+                .f64(rectangle.width)
+                .f64(rectangle.height);
+        }
+    });
+}
+
+/// Writes typed text as a single text item, with the style of each
+/// paragraph keyed by the newline before it, or by `CrdtId::END` for the
+/// first paragraph.
+fn write_text(w: &mut Writer, text: &Text, contents: &str, first: CrdtId, ids: &mut Ids) {
+    w.id(1, CrdtId::END);
+    w.subblock(2, |w| {
+        w.subblock(1, |w| {
+            w.subblock(1, |w| {
+                if contents.is_empty() {
+                    w.varuint(0);
+                    return;
+                }
+                w.varuint(1).subblock(0, |w| {
+                    w.id(2, first)
+                        .id(3, CrdtId::END)
+                        .id(4, CrdtId::END)
+                        .int(5, 0)
+                        .string(6, contents);
+                });
+            });
+        });
+        w.subblock(2, |w| {
+            w.subblock(1, |w| {
+                w.varuint(text.paragraphs.len() as u64);
+                let mut newline = None;
+                for paragraph in &text.paragraphs {
+                    w.crdt_id(newline.map_or(CrdtId::END, |n| first.offset(n)))
+                        .id(1, ids.next());
+                    w.subblock(2, |w| {
+                        w.u8(PARAGRAPH_STYLE_FORMAT).u8(paragraph.style.id());
+                    });
+                    let start = newline.map_or(0, |n| n + 1);
+                    newline = Some(start + paragraph.text.chars().count() as u64);
+                }
+            });
+        });
+    });
+    w.subblock(3, |w| {
+        w.f64(text.x).f64(text.y);
+    });
+    w.float(4, text.width);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lines::writer::Writer;
+
+    /// Writes the fields every scene item starts with.
+    fn item(w: &mut Writer, parent: CrdtId, id: u64, left: u64, right: u64) -> &mut Writer {
+        let side = |n| {
+            if n == 0 {
+                CrdtId::END
+            } else {
+                CrdtId::new(1, n)
+            }
+        };
+        w.id(1, parent)
+            .id(2, CrdtId::new(1, id))
+            .id(3, side(left))
+            .id(4, side(right))
+    }
+
+    const LAYER: CrdtId = CrdtId::new(0, 11);
+
+    fn line_block(w: &mut Writer, id: u64, left: u64, right: u64, pen: u32, x: f32) {
+        w.block(LINE_ITEM_BLOCK, 2, |w| {
+            item(w, LAYER, id, left, right).int(5, 0).subblock(6, |w| {
+                w.u8(3);
+                w.int(1, pen).int(2, 6).double(3, 2.0).float(4, 0.0);
+                w.subblock(5, |w| {
+                    w.f32(x);
+                    w.f32(-5.0);
+                    w.u16(8); // speed
+                    w.u16(12); // width
+                    w.bytes(&[0, 255]); // direction, pressure
+                });
+                w.id(6, CrdtId::new(1, 99));
+            });
+        });
+    }
+
+    fn layered_scene() -> Vec<u8> {
+        let mut w = Writer::default();
+        w.block(0x09, 1, |w| {
+            w.bytes(b"unknown block");
+        });
+        w.block(TREE_NODE_BLOCK, 1, |w| {
+            w.id(1, LAYER);
+            w.subblock(2, |w| {
+                w.id(1, CrdtId::new(0, 12)).string(2, "Sketch");
+            });
+            w.subblock(3, |w| {
+                w.id(1, CrdtId::new(0, 0)).bool(2, false);
+            });
+        });
+        w.block(GROUP_ITEM_BLOCK, 1, |w| {
+            item(w, CrdtId::ROOT, 1, 0, 0).int(5, 0).subblock(6, |w| {
+                w.u8(2);
+                w.id(2, LAYER);
+            });
+        });
+        // Stroke 20 was drawn first, then 21 was moved in front of it and 22 deleted.
+        line_block(&mut w, 20, 21, 0, 15, 1.0);
+        line_block(&mut w, 21, 0, 20, 17, 2.0);
+        w.block(LINE_ITEM_BLOCK, 2, |w| {
+            item(w, LAYER, 22, 20, 0).int(5, 1);
+        });
+        w.block(GLYPH_ITEM_BLOCK, 1, |w| {
+            item(w, LAYER, 30, 0, 0).int(5, 0).subblock(6, |w| {
+                w.u8(1);
+                w.int(2, 10).int(3, 4).int(4, 3).string(5, "Dune");
+                w.subblock(6, |w| {
+                    w.varuint(1);
+                    for value in [10.0f64, 20.0, 30.0, 40.0] {
+                        w.f64(value);
+                    }
+                });
+            });
+        });
+        w.into_bytes()
+    }
+
+    #[test]
+    fn parses_layers_in_sequence_order() {
+        let file = parse(&layered_scene()).unwrap();
+        assert_eq!(file.version, 6);
+        assert_eq!(file.layers.len(), 1);
+        let layer = &file.layers[0];
+        assert_eq!(layer.name, "Sketch");
+        assert!(!layer.visible);
+
+        let pens: Vec<Pen> = layer.strokes.iter().map(|s| s.pen).collect();
+        assert_eq!(pens, [Pen::Fineliner, Pen::Ballpoint]);
+        let stroke = &layer.strokes[1];
+        assert_eq!(stroke.color, Color::Blue);
+        assert_eq!(stroke.thickness_scale, 2.0);
+        let point = stroke.points[0];
+        assert_eq!((point.x, point.y), (1.0, -5.0));
+        assert_eq!((point.speed, point.width, point.pressure), (2.0, 3.0, 1.0));
+
+        let highlight = &layer.highlights[0];
+        assert_eq!(highlight.text, "Dune");
+        assert_eq!(highlight.color, Color::Yellow);
+        assert_eq!((highlight.start, highlight.length), (Some(10), 4));
+        assert_eq!(highlight.rectangles[0].height, 40.0);
+    }
+
+    #[test]
+    fn parses_deeply_nested_groups() {
+        const DEPTH: u64 = 100_000;
+        let group = |n| {
+            if n == DEPTH {
+                LAYER
+            } else {
+                CrdtId::new(0, 100 + n)
+            }
+        };
+        let mut w = Writer::default();
+        for n in 0..DEPTH {
+            let parent = if n == 0 { CrdtId::ROOT } else { group(n) };
+            w.block(GROUP_ITEM_BLOCK, 1, |w| {
+                item(w, parent, 1, 0, 0).int(5, 0).subblock(6, |w| {
+                    w.u8(2);
+                    w.id(2, group(n + 1));
+                });
+            });
+        }
+        line_block(&mut w, 20, 0, 0, 15, 1.0);
+
+        let file = parse(&w.into_bytes()).unwrap();
+        assert_eq!(file.layers.len(), 1);
+        assert_eq!(file.layers[0].strokes.len(), 1);
+    }
+
+    #[test]
+    fn parses_typed_text() {
+        let mut w = Writer::default();
+        w.block(ROOT_TEXT_BLOCK, 1, |w| {
+            w.id(1, CrdtId::END);
+            w.subblock(2, |w| {
+                w.subblock(1, |w| {
+                    w.subblock(1, |w| {
+                        w.varuint(3);
+                        // "World" was typed after "Hi\n", and a "!" typed in between was deleted.
+                        w.subblock(0, |w| {
+                            w.id(2, CrdtId::new(1, 20))
+                                .id(3, CrdtId::new(1, 12))
+                                .id(4, CrdtId::END)
+                                .int(5, 0)
+                                .string(6, "World");
+                        });
+                        w.subblock(0, |w| {
+                            w.id(2, CrdtId::new(1, 10))
+                                .id(3, CrdtId::END)
+                                .id(4, CrdtId::END)
+                                .int(5, 0)
+                                .string(6, "Hi\n");
+                        });
+                        w.subblock(0, |w| {
+                            w.id(2, CrdtId::new(1, 15))
+                                .id(3, CrdtId::new(1, 11))
+                                .id(4, CrdtId::new(1, 12))
+                                .int(5, 1);
+                        });
+                    });
+                });
+                w.subblock(2, |w| {
+                    w.subblock(1, |w| {
+                        w.varuint(1);
+                        w.crdt_id(CrdtId::END).id(1, CrdtId::new(1, 1));
+                        w.subblock(2, |w| {
+                            w.bytes(&[17, 2]);
+                        });
+                    });
+                });
+            });
+            w.subblock(3, |w| {
+                w.f64(-400.0);
+                w.f64(120.0);
+            });
+            w.float(4, 800.0);
+        });
+
+        let text = parse(&w.into_bytes()).unwrap().text.unwrap();
+        assert_eq!((text.x, text.y, text.width), (-400.0, 120.0, 800.0));
+        assert_eq!(text.plain_text(), "Hi\nWorld");
+        assert_eq!(text.paragraphs[0].style, ParagraphStyle::Heading);
+        assert_eq!(text.paragraphs[1].style, ParagraphStyle::Plain);
+    }
+
+    #[test]
+    fn writes_what_it_parses() {
+        let point = |x| Point {
+            x,
+            y: 100.0,
+            speed: 1.5,
+            direction: PI,
+            width: 2.25,
+            pressure: 0.6,
+        };
+        let stroke = |pen, x| Stroke {
+            pen,
+            color: Color::Red,
+            thickness_scale: 2.0,
+            points: vec![point(x), point(x + 10.0)],
+        };
+        let file = LinesFile {
+            version: 6,
+            layers: vec![
+                Layer {
+                    name: "Layer 1".to_string(),
+                    visible: true,
+                    strokes: vec![stroke(Pen::Ballpoint, 0.0), stroke(Pen::Pencil, 50.0)],
+                    highlights: Vec::new(),
+                },
+                Layer {
+                    name: "Notes".to_string(),
+                    visible: false,
+                    strokes: vec![stroke(Pen::Marker, -20.0)],
+                    highlights: vec![Highlight {
+                        text: "Dune".to_string(),
+                        color: Color::YellowHighlight,
+                        start: Some(3),
+                        length: 4,
+                        rectangles: vec![Rectangle {
+                            x: 1.0,
+                            y: 2.0,
+                            width: 3.0,
+                            height: 4.0,
+                        }],
+                    }],
+                },
+            ],
+            text: Some(Text {
+                x: -468.0,
+                y: 234.0,
+                width: 936.0,
+                paragraphs: vec![
+                    Paragraph {
+                        style: ParagraphStyle::Heading,
+                        text: "Groceries".to_string(),
+                    },
+                    Paragraph {
+                        style: ParagraphStyle::Bullet,
+                        text: "Crème fraîche".to_string(),
+                    },
+                    Paragraph {
+                        style: ParagraphStyle::Checkbox,
+                        text: String::new(),
+                    },
+                ],
+            }),
+        };
+        let parsed = parse(&write(&file)).unwrap();
+        assert_eq!(parsed.layers.len(), 2);
+        assert_eq!(parsed.text, file.text);
+        assert_eq!(parsed.layers[1].name, "Notes");
+        assert!(!parsed.layers[1].visible);
+        assert_eq!(parsed.layers[1].highlights, file.layers[1].highlights);
+        let pens: Vec<Pen> = parsed.strokes().map(|s| s.pen).collect();
+        assert_eq!(pens, [Pen::Ballpoint, Pen::Pencil, Pen::Marker]);
+
+        // Points lose some precision in version 2 line blocks.
+        let point = parsed.layers[0].strokes[1].points[1];
+        assert_eq!((point.x, point.y), (60.0, 100.0));
+        assert_eq!((point.speed, point.width), (1.5, 2.25));
+        assert!((point.direction - PI).abs() < 0.02);
+        assert!((point.pressure - 0.6).abs() < 0.01);
+    }
+
+    #[test]
+    fn keeps_long_deleted_text_as_a_few_items() {
+        let mut w = Writer::default();
+        w.block(ROOT_TEXT_BLOCK, 1, |w| {
+            w.id(1, CrdtId::END);
+            w.subblock(2, |w| {
+                w.subblock(1, |w| {
+                    w.subblock(1, |w| {
+                        w.varuint(3);
+                        // Billions of characters were deleted, and "b" was typed in the middle of them.
+                        w.subblock(0, |w| {
+                            w.id(2, CrdtId::new(1, 10))
+                                .id(3, CrdtId::END)
+                                .id(4, CrdtId::END)
+                                .int(5, u32::MAX);
+                        });
+                        w.subblock(0, |w| {
+                            w.id(2, CrdtId::new(2, 1))
+                                .id(3, CrdtId::END)
+                                .id(4, CrdtId::new(1, 10))
+                                .int(5, 0)
+                                .string(6, "a");
+                        });
+                        w.subblock(0, |w| {
+                            w.id(2, CrdtId::new(2, 2))
+                                .id(3, CrdtId::new(1, 1000))
+                                .id(4, CrdtId::new(1, 1001))
+                                .int(5, 0)
+                                .string(6, "b");
+                        });
+                    });
+                });
+                w.subblock(2, |w| {
+                    w.subblock(1, |w| {
+                        w.varuint(0);
+                    });
+                });
+            });
+            w.subblock(3, |w| {
+                w.f64(0.0).f64(0.0);
+            });
+            w.float(4, 100.0);
+        });
+
+        let text = parse(&w.into_bytes()).unwrap().text.unwrap();
+        assert_eq!(text.plain_text(), "ab");
+    }
+
+    #[test]
+    fn rejects_truncated_blocks() {
+        let scene = layered_scene();
+        assert!(parse(&scene[..scene.len() - 3]).is_err());
+    }
+}
@@ -0,0 +1,152 @@
+use crate::lines::crdt::CrdtId;
+use crate::lines::reader::TagType;
+
+/// Builds the little-endian data of a v6 file, the counterpart of `Reader`.
+#[derive(Default)]
+pub(crate) struct Writer {
+    data: Vec<u8>,
+}
+
+impl Writer {
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+
+    pub fn bytes(&mut self, bytes: &[u8]) -> &mut Writer {
+        self.data.extend_from_slice(bytes);
+        self
+    }
+
+    pub fn u8(&mut self, value: u8) -> &mut Writer {
+        self.data.push(value);
+        self
+    }
+
+    pub fn u16(&mut self, value: u16) -> &mut Writer {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    pub fn u32(&mut self, value: u32) -> &mut Writer {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    pub fn f32(&mut self, value: f32) -> &mut Writer {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    pub fn f64(&mut self, value: f64) -> &mut Writer {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    /// Writes an unsigned LEB128 integer.
+    pub fn varuint(&mut self, mut value: u64) -> &mut Writer {
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                return self.u8(byte);
+            }
+            self.u8(byte | 0x80);
+        }
+    }
+
+    pub fn crdt_id(&mut self, id: CrdtId) -> &mut Writer {
+        self.u8(id.author).varuint(id.counter)
+    }
+
+    fn tag(&mut self, index: u64, tag_type: TagType) -> &mut Writer {
+        self.varuint(index << 4 | tag_type as u64)
+    }
+
+    /// Writes a subblock holding whatever `contents` writes, preceded by its length.
+    pub fn subblock(&mut self, index: u64, contents: impl FnOnce(&mut Writer)) -> &mut Writer {
+        let mut inner = Writer::default();
+        contents(&mut inner);
+        self.tag(index, TagType::Length4)
+            .u32(inner.data.len() as u32)
+            .bytes(&inner.data)
+    }
+
+    pub fn id(&mut self, index: u64, id: CrdtId) -> &mut Writer {
+        self.tag(index, TagType::Id).crdt_id(id)
+    }
+
+    pub fn bool(&mut self, index: u64, value: bool) -> &mut Writer {
+        self.tag(index, TagType::Byte1).u8(u8::from(value))
+    }
+
+    pub fn int(&mut self, index: u64, value: u32) -> &mut Writer {
+        self.tag(index, TagType::Byte4).u32(value)
+    }
+
+    pub fn float(&mut self, index: u64, value: f32) -> &mut Writer {
+        self.tag(index, TagType::Byte4).f32(value)
+    }
+
+    pub fn double(&mut self, index: u64, value: f64) -> &mut Writer {
+        self.tag(index, TagType::Byte8).f64(value)
+    }
+
+    /// Writes the contents of a string subblock.
+    pub fn string_contents(&mut self, value: &str) -> &mut Writer {
+        self.varuint(value.len() as u64)
+            .u8(u8::from(value.is_ascii()))
+            .bytes(value.as_bytes())
+    }
+
+    pub fn string(&mut self, index: u64, value: &str) -> &mut Writer {
+        self.subblock(index, |w| {
+            w.string_contents(value);
+        })
+    }
+
+    /// Writes a last-writer-wins value: the time it was written at, followed by the value itself.
+    pub fn lww(
+        &mut self,
+        index: u64,
+        timestamp: CrdtId,
+        value: impl FnOnce(&mut Writer),
+    ) -> &mut Writer {
+        self.subblock(index, |w| {
+            w.id(1, timestamp);
+            value(w);
+        })
+    }
+
+    /// Writes a block of the given type, preceded by its header.
+    pub fn block(&mut self, block_type: u8, version: u8, contents: impl FnOnce(&mut Writer)) {
+        let mut inner = Writer::default();
+        contents(&mut inner);
+        self.u32(inner.data.len() as u32)
+            .bytes(&[0, 1, version, block_type])
+            .bytes(&inner.data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lines::reader::Reader;
+
+    #[test]
+    fn writes_what_the_reader_reads() {
+        let mut w = Writer::default();
+        w.id(1, CrdtId::new(1, 150))
+            .int(2, 42)
+            .string(3, "abc")
+            .lww(4, CrdtId::new(0, 12), |w| {
+                w.bool(2, true);
+            })
+            .varuint(300);
+        let data = w.into_bytes();
+        assert_eq!(&data[..4], [0x1f, 0x01, 0x96, 0x01]);
+
+        let mut reader = Reader::new(&data);
+        assert_eq!(reader.id(1).unwrap(), CrdtId::new(1, 150));
+        assert_eq!(reader.int(2).unwrap(), 42);
+        assert_eq!(reader.string(3).unwrap(), "abc");
+        assert!(reader.lww(4, |r| r.bool(2)).unwrap());
+        assert_eq!(reader.varuint().unwrap(), 300);
+        assert!(reader.is_empty());
+    }
+}
@@ -0,0 +1,382 @@
+//! Notebooks built from code, with their pages written as version 6 `.rm` files.
+//!
+//! ```no_run
+//! # async fn example(client: &rmapi::Client) -> Result<(), rmapi::Error> {
+//! use rmapi::notebook::{Notebook, NotebookPage};
+//! use rmapi::lines::{Paragraph, ParagraphStyle, Text};
+//!
+//! let heading = Text {
+//!     x: -468.0,
+//!     y: 234.0,
+//!     width: 936.0,
+//!     paragraphs: vec![Paragraph {
+//!         style: ParagraphStyle::Heading,
+//!         text: "Monday".to_string(),
+//!     }],
+//! };
+//! let notebook = Notebook::new("Week 42")
+//!     .with_page(NotebookPage::new().with_text(heading))
+//!     .with_pages(4, "P Lines small");
+//! client.create_notebook(&notebook).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::archive::Archive;
+use crate::error::Error;
+use crate::lines::{Layer, LinesFile, Stroke, Text};
+use crate::objects::{CPage, CPages, Content, Document, Orientation, Parent, Versioned};
+use serde_json::Map;
+use uuid::Uuid;
+
+/// The template of pages that have none chosen.
+const BLANK_TEMPLATE: &str = "Blank";
+
+/// A notebook to create, with its pages.
+///
+/// The notebook and its pages are given their ids when they are built, so
+/// committing the same notebook twice replaces the files of the first copy
+/// rather than making another notebook or leaving the old pages behind.
+#[derive(Debug, Clone)]
+pub struct Notebook {
+    /// The id the notebook is created with.
+    pub id: Uuid,
+    /// The name shown on the tablet.
+    pub visible_name: String,
+    /// The collection the notebook is created in.
+    pub parent: Parent,
+    pub orientation: Orientation,
+    /// The pages of the notebook, in order.
+    pub pages: Vec<NotebookPage>,
+}
+
+/// A page of a `Notebook`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NotebookPage {
+    /// The id the page is created with, which names its `.rm` file.
+    pub id: Uuid,
+    /// The name of the template drawn behind the page, such as `P Lines small`.
+    pub template: String,
+    /// What is drawn and typed on the page. Points are measured from the
+    /// horizontal centre of the page, as in version 6 files.
+    pub lines: LinesFile,
+}
+
+impl Notebook {
+    /// Creates an empty portrait notebook in the root, with a fresh id.
+    pub fn new(visible_name: &str) -> Notebook {
+        Notebook {
+            id: Uuid::new_v4(),
+            visible_name: visible_name.to_string(),
+            parent: Parent::Root,
+            orientation: Orientation::Portrait,
+            pages: Vec::new(),
+        }
+    }
+
+    /// Creates the notebook in the root, the trash or the collection with the given id.
+    pub fn with_parent(mut self, parent: Parent) -> Notebook {
+        self.parent = parent;
+        self
+    }
+
+    /// Sets whether the notebook is shown in portrait or landscape.
+    pub fn with_orientation(mut self, orientation: Orientation) -> Notebook {
+        self.orientation = orientation;
+        self
+    }
+
+    /// Adds a page after the ones added so far.
+    pub fn with_page(mut self, page: NotebookPage) -> Notebook {
+        self.pages.push(page);
+        self
+    }
+
+    /// Adds `count` empty pages with the given template after the ones added so far.
+    pub fn with_pages(mut self, count: usize, template: &str) -> Notebook {
+        self.pages
+            .extend((0..count).map(|_| NotebookPage::new().with_template(template)));
+        self
+    }
+
+    /// Generates the files of the notebook, as the tablet writes them for a
+    /// notebook created on it.
+    ///
+    /// A notebook without pages is given a blank one, since the tablet
+    /// expects every notebook to have at least one. It takes the id of the
+    /// notebook, so it too is the same each time.
+    ///
+    /// # Returns
+    ///
+    /// The `.metadata`, `.content` and `.pagedata` files of the notebook, and
+    /// an `.rm` file for each page.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `Error::Json` if the metadata or content cannot be serialized.
+    pub fn to_archive(&self) -> Result<Archive, Error> {
+        let blank = [NotebookPage {
+            id: self.id,
+            ..NotebookPage::default()
+        }];
+        let pages = if self.pages.is_empty() {
+            &blank[..]
+        } else {
+            &self.pages[..]
+        };
+        let page_ids: Vec<String> = pages.iter().map(|page| page.id.to_string()).collect();
+
+        let mut document = Document::new(&self.visible_name, self.parent);
+        document.id = self.id;
+        let c_pages = pages
+            .iter()
+            .zip(&page_ids)
+            .enumerate()
+            .map(|(i, (page, page_id))| CPage {
+                id: page_id.clone(),
+                idx: Versioned {
+                    timestamp: format!("1:{}", 2 * i + 2),
+                    value: page_index(i, pages.len()),
+                },
+                redir: None,
+                template: Some(Versioned {
+                    timestamp: format!("1:{}", 2 * i + 3),
+                    value: page.template.clone(),
+                }),
+                deleted: None,
+                extra: Map::new(),
+            })
+            .collect();
+        let content = Content {
+            file_type: Some("notebook".to_string()),
+            format_version: Some(2),
+            c_pages: Some(CPages {
+                pages: c_pages,
+                last_opened: Some(Versioned {
+                    timestamp: "1:1".to_string(),
+                    value: page_ids[0].clone(),
+                }),
+                original: Some(Versioned {
+                    timestamp: "0:0".to_string(),
+                    value: -1,
+                }),
+                extra: Map::new(),
+            }),
+            orientation: Some(self.orientation),
+            page_count: Some(pages.len() as u32),
+            cover_page_number: Some(0),
+            ..Content::default()
+        };
+        let pagedata: String = pages
+            .iter()
+            .map(|page| format!("{}\n", page.template))
+            .collect();
+
+        let id = self.id;
+        let mut files = vec![
+            (format!("{}.metadata", id), document.to_metadata()?),
+            (format!("{}.content", id), content.to_bytes()?),
+            (format!("{}.pagedata", id), pagedata.into_bytes()),
+        ];
+        for (page, page_id) in pages.iter().zip(&page_ids) {
+            files.push((format!("{}/{}.rm", id, page_id), page.lines.to_bytes()));
+        }
+        log::debug!("Generated {} pages of notebook {}", pages.len(), id);
+        Ok(Archive { id, files })
+    }
+}
+
+impl NotebookPage {
+    /// Creates a blank page with a single empty layer and a fresh id.
+    pub fn new() -> NotebookPage {
+        NotebookPage::default()
+    }
+
+    /// Sets the template drawn behind the page, by the name the tablet knows it by.
+    pub fn with_template(mut self, template: &str) -> NotebookPage {
+        self.template = template.to_string();
+        self
+    }
+
+    /// Draws a stroke on the top layer of the page, above the strokes drawn so far.
+    pub fn with_stroke(mut self, stroke: Stroke) -> NotebookPage {
+        if self.lines.layers.is_empty() {
+            self.lines.layers.push(layer(1));
+        }
+        if let Some(layer) = self.lines.layers.last_mut() {
+            layer.strokes.push(stroke);
+        }
+        self
+    }
+
+    /// Adds a layer above the ones of the page, which later strokes are drawn on.
+    pub fn with_layer(mut self) -> NotebookPage {
+        let number = self.lines.layers.len() + 1;
+        self.lines.layers.push(layer(number));
+        self
+    }
+
+    /// Sets the text typed on the page, replacing any set before.
+    pub fn with_text(mut self, text: Text) -> NotebookPage {
+        self.lines.text = Some(text);
+        self
+    }
+}
+
+impl Default for NotebookPage {
+    fn default() -> NotebookPage {
+        NotebookPage {
+            id: Uuid::new_v4(),
+            template: BLANK_TEMPLATE.to_string(),
+            lines: LinesFile {
+                version: 6,
+                layers: vec![layer(1)],
+                text: None,
+            },
+        }
+    }
+}
+
+/// An empty layer named the way the tablet names the layers it adds.
+fn layer(number: usize) -> Layer {
+    Layer {
+        name: format!("Layer {}", number),
+        visible: true,
+        ..Layer::default()
+    }
+}
+
+/// The position of page `i` of `count`, which pages are sorted by.
+///
+/// The tablet numbers the pages of a new notebook `ba`, `bb`, `bc` and so
+/// on; longer notebooks use more letters, all of the same length so they
+/// still sort in order.
+fn page_index(i: usize, count: usize) -> String {
+    let mut width = 1;
+    let mut capacity = 26;
+    while capacity < count {
+        width += 1;
+        capacity *= 26;
+    }
+    let mut letters = vec!['a'; width];
+    let mut n = i;
+    for letter in letters.iter_mut().rev() {
+        *letter = char::from(b'a' + (n % 26) as u8);
+        n /= 26;
+    }
+    std::iter::once('b').chain(letters).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lines::{Color, Paragraph, ParagraphStyle, Pen, Point};
+
+    fn stroke() -> Stroke {
+        let point = |x| Point {
+            x,
+            y: 300.0,
+            speed: 0.0,
+            direction: 0.0,
+            width: 2.0,
+            pressure: 0.5,
+        };
+        Stroke {
+            pen: Pen::Fineliner,
+            color: Color::Black,
+            thickness_scale: 2.0,
+            points: vec![point(-200.0), point(200.0)],
+        }
+    }
+
+    #[test]
+    fn writes_every_file_of_the_notebook() {
+        let parent = Parent::Collection(Uuid::new_v4());
+        let text = Text {
+            x: -468.0,
+            y: 234.0,
+            width: 936.0,
+            paragraphs: vec![Paragraph {
+                style: ParagraphStyle::Heading,
+                text: "Monday".to_string(),
+            }],
+        };
+        let notebook = Notebook::new("Week 42")
+            .with_parent(parent)
+            .with_page(NotebookPage::new().with_stroke(stroke()).with_text(text))
+            .with_pages(2, "P Lines small");
+        let archive = notebook.to_archive().unwrap();
+        let id = notebook.id;
+        assert_eq!(archive.id, id);
+        assert_eq!(archive.files.len(), 6);
+
+        let metadata = archive.file(&format!("{}.metadata", id)).unwrap();
+        let document = Document::from_metadata(id, "", metadata).unwrap();
+        assert_eq!(document.visible_name, "Week 42");
+        assert_eq!(document.parent, parent.to_string());
+
+        let content =
+            Content::from_bytes(archive.file(&format!("{}.content", id)).unwrap()).unwrap();
+        assert!(content.is_notebook());
+        assert_eq!(content.page_count, Some(3));
+        let pages = content.pages();
+        let templates: Vec<_> = pages.iter().map(|p| p.template.as_deref()).collect();
+        assert_eq!(
+            templates,
+            [Some("Blank"), Some("P Lines small"), Some("P Lines small")]
+        );
+        assert_eq!(
+            archive.file(&format!("{}.pagedata", id)).unwrap(),
+            b"Blank\nP Lines small\nP Lines small\n"
+        );
+
+        let first = archive.file(&format!("{}/{}.rm", id, pages[0].id)).unwrap();
+        let lines = LinesFile::parse(first).unwrap();
+        assert_eq!(lines.version, 6);
+        assert_eq!(lines.strokes().count(), 1);
+        assert_eq!(lines.text.unwrap().plain_text(), "Monday");
+    }
+
+    #[test]
+    fn gives_empty_notebooks_a_blank_page() {
+        let archive = Notebook::new("Empty").to_archive().unwrap();
+        let content =
+            Content::from_bytes(archive.file(&format!("{}.content", archive.id)).unwrap()).unwrap();
+        assert_eq!(content.pages().len(), 1);
+        assert_eq!(archive.files.len(), 4);
+    }
+
+    #[test]
+    fn keeps_the_page_ids_between_archives() {
+        let notebook = Notebook::new("Planner").with_pages(2, "P Lines small");
+        assert_ne!(notebook.pages[0].id, notebook.pages[1].id);
+        let names = |archive: Archive| -> Vec<String> {
+            archive.files.into_iter().map(|(name, _)| name).collect()
+        };
+        assert_eq!(
+            names(notebook.to_archive().unwrap()),
+            names(notebook.to_archive().unwrap())
+        );
+    }
+
+    #[test]
+    fn draws_strokes_on_the_top_layer() {
+        let page = NotebookPage::new()
+            .with_stroke(stroke())
+            .with_layer()
+            .with_stroke(stroke());
+        let layers = &page.lines.layers;
+        assert_eq!(layers.len(), 2);
+        assert_eq!(layers[1].name, "Layer 2");
+        assert_eq!((layers[0].strokes.len(), layers[1].strokes.len()), (1, 1));
+    }
+
+    #[test]
+    fn indexes_pages_in_order() {
+        assert_eq!(page_index(0, 3), "ba");
+        assert_eq!(page_index(2, 3), "bc");
+        let indexes: Vec<String> = (0..100).map(|i| page_index(i, 100)).collect();
+        assert_eq!(indexes[27], "bbb");
+        assert!(indexes.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+}
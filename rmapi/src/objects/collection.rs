@@ -1,30 +1,65 @@
-use serde::{Serialize, Deserialize};
-use crate::rmapi::object::RemarkableObject;
-
-#[derive(Debug)]
-enum CollectionType {
+use crate::objects::metadata::{ItemType, Metadata};
+use crate::objects::remarkable_object::ObjectKind;
+use serde::{Deserialize, Serialize};
 
+/// The `type` tag of a collection's `.metadata` file.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CollectionType {
+    CollectionType,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct Collection {
-    id: Uuid,
-    version: u64,
-    message: String,
-    blob_url_get: String,
-    blob_url_get_expires: DateTime<Utc>,
-    blob_url_put: String,
-    blob_url_put_expires: DateTime<Utc>,
-    last_modified: DateTime<Utc>,
-    display_name: String,
-    current_page: u64,
-    type: CollectionType,
-    parent: String,
-    objects: Vec<RemarkableObject>,
+impl ItemType for CollectionType {
+    const TAG: Self = CollectionType::CollectionType;
+    const KIND: ObjectKind = ObjectKind::Collection;
 }
 
-impl RemarkableObject for Collection {
-    fn register_client(code: String) -> Result<String, Error> {
-        unimplemented!()
+/// A collection (folder) as described by its `.metadata` file.
+pub type Collection = Metadata<CollectionType>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::{Parent, RemarkableObject};
+    use uuid::Uuid;
+
+    #[test]
+    fn parses_collection_metadata() {
+        let metadata = r#"{
+            "deleted": false,
+            "lastModified": "1698765432100",
+            "metadatamodified": false,
+            "modified": false,
+            "parent": "",
+            "pinned": false,
+            "synced": true,
+            "type": "CollectionType",
+            "version": 1,
+            "visibleName": "Books"
+        }"#;
+        let id = Uuid::parse_str("5a0ff2c6-3a3e-4b64-9d1a-d9e3a1f4c0a1").unwrap();
+        let collection = Collection::from_metadata(id, "abc123", metadata.as_bytes()).unwrap();
+
+        assert_eq!(collection.id, id);
+        assert_eq!(collection.visible_name, "Books");
+        assert_eq!(collection.parent(), Parent::Root);
+        assert_eq!(collection.kind(), ObjectKind::Collection);
+        assert_eq!(collection.item_type, CollectionType::CollectionType);
+        assert_eq!(collection.last_modified.timestamp_millis(), 1698765432100);
+        assert_eq!(collection.version, 1);
+    }
+
+    #[test]
+    fn new_collections_round_trip_through_metadata() {
+        let parent = Parent::Collection(Uuid::new_v4());
+        let collection = Collection::new("Papers", parent);
+        let metadata = collection.to_metadata().unwrap();
+        let parsed = Collection::from_metadata(collection.id, "", &metadata).unwrap();
+
+        assert_eq!(parsed.visible_name, "Papers");
+        assert_eq!(parsed.parent(), parent);
+        assert_eq!(
+            parsed.last_modified.timestamp_millis(),
+            collection.last_modified.timestamp_millis()
+        );
     }
 }
@@ -0,0 +1,270 @@
+use crate::error::Error;
+use crate::file_type::FileType;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// The `fileType` of notebooks, which have no source file.
+const NOTEBOOK_FILE_TYPE: &str = "notebook";
+
+/// How a document is laid out on the tablet.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Orientation {
+    Portrait,
+    Landscape,
+}
+
+/// The settings and page list of a document, as stored in its `.content` file.
+///
+/// Every field is optional, since which ones are present depends on the kind
+/// of document and the software that wrote it. Fields that are not modelled
+/// here are kept in `extra`, so a `.content` file can be edited and written
+/// back without losing anything the tablet relies on.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Content {
+    /// The kind of document: `pdf`, `epub`, `notebook` or empty.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_type: Option<String>,
+    /// The version of the `.content` format, `2` for documents that use `c_pages`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format_version: Option<u32>,
+    /// The page ids in order, as written by software before the `cPages` format.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pages: Option<Vec<String>>,
+    /// The pages as written by newer software, which tracks every change so
+    /// devices can merge their edits.
+    #[serde(rename = "cPages", default, skip_serializing_if = "Option::is_none")]
+    pub c_pages: Option<CPages>,
+    /// For each page in `pages`, the page of the source PDF it shows, or `-1`
+    /// for pages inserted on the tablet.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub redirection_page_map: Option<Vec<i32>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub orientation: Option<Orientation>,
+    /// The margins EPUBs and PDFs are shown with, in pixels.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub margins: Option<u32>,
+    /// The font EPUBs are shown with, empty for the default one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub font_name: Option<String>,
+    /// The line height of EPUBs in percent, or `-1` for the default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line_height: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page_count: Option<u32>,
+    /// The page shown as the document's cover: `0` for the first page, `-1`
+    /// for the last opened one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cover_page_number: Option<i32>,
+    /// Any other fields of the `.content` file, kept so it can be written back unchanged.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// The page list of the `cPages` format.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CPages {
+    #[serde(default)]
+    pub pages: Vec<CPage>,
+    /// The id of the page the document was last opened at.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_opened: Option<Versioned<String>>,
+    /// The page count of the source PDF, or `-1` for notebooks.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original: Option<Versioned<i32>>,
+    /// Any other fields, kept so they can be written back unchanged.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// A page in the `cPages` format.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CPage {
+    /// The id of the page, which its `<document id>/<page id>.rm` file is named after.
+    pub id: String,
+    /// The position of the page. Pages are ordered by comparing these strings.
+    pub idx: Versioned<String>,
+    /// The page of the source PDF this page shows. Inserted pages have none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub redir: Option<Versioned<i32>>,
+    /// The name of the template drawn behind the page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<Versioned<String>>,
+    /// Set to a non-zero value once the page is deleted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted: Option<Versioned<i32>>,
+    /// Any other fields, kept so they can be written back unchanged.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// A value of the `cPages` format together with the logical time it was last changed at.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Versioned<T> {
+    /// When the value was changed, as `<author>:<counter>`.
+    pub timestamp: String,
+    pub value: T,
+}
+
+/// A page of a document, in either `.content` format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Page {
+    pub id: String,
+    /// The page of the source PDF this page shows, or `None` for pages inserted on the tablet.
+    pub redirect: Option<u32>,
+    /// The name of the template drawn behind the page, if known.
+    pub template: Option<String>,
+}
+
+impl Content {
+    /// Parses the contents of a `.content` file.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `Error::Json` if the file is not a valid `.content` file.
+    pub fn from_bytes(content: &[u8]) -> Result<Content, Error> {
+        Ok(serde_json::from_slice(content)?)
+    }
+
+    /// Serializes the content back into a `.content` file.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        Ok(serde_json::to_vec(self)?)
+    }
+
+    /// The type of the source file of the document, `None` for notebooks.
+    pub fn source_file_type(&self) -> Option<FileType> {
+        match self.file_type.as_deref() {
+            Some("pdf") => Some(FileType::Pdf),
+            Some("epub") => Some(FileType::Epub),
+            _ => None,
+        }
+    }
+
+    /// Whether the document is a notebook rather than a PDF or EPUB.
+    pub fn is_notebook(&self) -> bool {
+        matches!(
+            self.file_type.as_deref(),
+            Some(NOTEBOOK_FILE_TYPE) | Some("")
+        )
+    }
+
+    /// The pages of the document in order, leaving out deleted ones.
+    ///
+    /// The `cPages` list is used if present, and the `pages` list together with
+    /// the `redirectionPageMap` otherwise.
+    pub fn pages(&self) -> Vec<Page> {
+        if let Some(ref c_pages) = self.c_pages {
+            let mut pages: Vec<&CPage> = c_pages
+                .pages
+                .iter()
+                .filter(|page| page.deleted.as_ref().is_none_or(|d| d.value == 0))
+                .collect();
+            pages.sort_by(|a, b| a.idx.value.cmp(&b.idx.value).then_with(|| a.id.cmp(&b.id)));
+            return pages
+                .into_iter()
+                .map(|page| Page {
+                    id: page.id.clone(),
+                    redirect: page
+                        .redir
+                        .as_ref()
+                        .and_then(|r| u32::try_from(r.value).ok()),
+                    template: page.template.as_ref().map(|t| t.value.clone()),
+                })
+                .collect();
+        }
+
+        let redirects = self.redirection_page_map.as_deref().unwrap_or_default();
+        let is_pdf = self.source_file_type() == Some(FileType::Pdf);
+        self.pages
+            .iter()
+            .flatten()
+            .enumerate()
+            .map(|(i, id)| Page {
+                id: id.clone(),
+                redirect: match redirects.get(i) {
+                    Some(&redirect) => u32::try_from(redirect).ok(),
+                    // Without a map, every page of a PDF shows the page at the same position.
+                    None if is_pdf => Some(i as u32),
+                    None => None,
+                },
+                template: None,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const V1_CONTENT: &str = r#"{
+        "coverPageNumber": 0,
+        "dummyDocument": false,
+        "extraMetadata": {"LastTool": "Ballpoint"},
+        "fileType": "pdf",
+        "fontName": "",
+        "lineHeight": -1,
+        "margins": 180,
+        "orientation": "portrait",
+        "pageCount": 3,
+        "pages": ["p1", "inserted", "p2"],
+        "redirectionPageMap": [0, -1, 1],
+        "textScale": 1.5
+    }"#;
+
+    const V2_CONTENT: &str = r#"{
+        "cPages": {
+            "lastOpened": {"timestamp": "1:1", "value": "b"},
+            "original": {"timestamp": "0:0", "value": -1},
+            "pages": [
+                {"id": "b", "idx": {"timestamp": "1:3", "value": "bb"}, "template": {"timestamp": "1:1", "value": "P Lines small"}},
+                {"id": "gone", "idx": {"timestamp": "1:4", "value": "bc"}, "deleted": {"timestamp": "1:5", "value": 1}},
+                {"id": "a", "idx": {"timestamp": "1:2", "value": "ba"}, "scrollTime": {"timestamp": "1:6", "value": "0"}}
+            ],
+            "uuids": [{"first": "4b3c7e2a", "second": 1}]
+        },
+        "fileType": "notebook",
+        "formatVersion": 2,
+        "orientation": "landscape",
+        "pageCount": 2,
+        "zoomMode": "bestFit"
+    }"#;
+
+    #[test]
+    fn reads_the_old_page_list() {
+        let content = Content::from_bytes(V1_CONTENT.as_bytes()).unwrap();
+        assert_eq!(content.source_file_type(), Some(FileType::Pdf));
+        assert_eq!(content.orientation, Some(Orientation::Portrait));
+        assert_eq!(content.margins, Some(180));
+        assert_eq!(content.line_height, Some(-1));
+        let pages = content.pages();
+        let redirects: Vec<Option<u32>> = pages.iter().map(|p| p.redirect).collect();
+        assert_eq!(pages[1].id, "inserted");
+        assert_eq!(redirects, [Some(0), None, Some(1)]);
+    }
+
+    #[test]
+    fn reads_the_c_pages_list() {
+        let content = Content::from_bytes(V2_CONTENT.as_bytes()).unwrap();
+        assert!(content.is_notebook());
+        assert_eq!(content.orientation, Some(Orientation::Landscape));
+        let pages = content.pages();
+        let ids: Vec<&str> = pages.iter().map(|p| p.id.as_str()).collect();
+        assert_eq!(ids, ["a", "b"]);
+        assert_eq!(pages[1].template.as_deref(), Some("P Lines small"));
+        assert_eq!(pages[0].redirect, None);
+    }
+
+    #[test]
+    fn round_trips_unknown_fields() {
+        for sample in [V1_CONTENT, V2_CONTENT, "{}"] {
+            let content = Content::from_bytes(sample.as_bytes()).unwrap();
+            let written: Value = serde_json::from_slice(&content.to_bytes().unwrap()).unwrap();
+            let original: Value = serde_json::from_str(sample).unwrap();
+            assert_eq!(written, original);
+        }
+    }
+}
@@ -1,67 +1,79 @@
-use serde::{Serialize, Deserialize};
-use crate::rmapi::object::RemarkableObject;
-
-#[derive(Debug)]
-enum DocumentType {
+use crate::objects::metadata::{ItemType, Metadata};
+use crate::objects::remarkable_object::ObjectKind;
+use serde::{Deserialize, Serialize};
 
+/// The `type` tag of a document's `.metadata` file.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocumentType {
+    DocumentType,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Document {
-    id: Uuid,
-    version: u64,
-    message: String,
-    success: bool,
-    blob_url_get: String,
-    blob_url_put: String,
-    blob_url_put_expires: DateTime<Utc>,
-    last_modified: DateTime<Utc>,
-    display_name: String,
-    current_page: u64,
-    bookmarked: bool,
-    type: DocumentType,
-    parent: String,
+impl ItemType for DocumentType {
+    const TAG: Self = DocumentType::DocumentType;
+    const KIND: ObjectKind = ObjectKind::Document;
 }
 
-impl Document {
-    pub async fn new(token: String, id: Uuid) -> Result<Document, Error> {
-        let client = reqwest::Client::new();
-        let response = client
-            .get(formatcp!("{STORAGE_API_URL}/{id}"))
-            .header("Authorization", formatcp!("Bearer {token}"))
-            .send()
-            .await?;
+/// A document (notebook, PDF or EPUB) as described by its `.metadata` file.
+pub type Document = Metadata<DocumentType>;
 
-        let document: Document = response.json().await?;
-        return Ok(document);
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::{Parent, RemarkableObject};
+    use uuid::Uuid;
 
-    pub async fn save(&self) -> Result<(), Error> {
-        let client = reqwest::Client::new();
-        let response = client
-            .put(formatcp!("{STORAGE_API_URL}/{self.id}"))
-            .header("Authorization", formatcp!("Bearer {self.token}"))
-            .json(&self)
-            .send()
-            .await?;
+    const SAMPLE_METADATA: &str = r#"{
+        "createdTime": "1712345678000",
+        "deleted": false,
+        "lastModified": "1712349999123",
+        "lastOpened": "1712349999000",
+        "lastOpenedPage": 3,
+        "metadatamodified": false,
+        "modified": false,
+        "parent": "5a0ff2c6-3a3e-4b64-9d1a-d9e3a1f4c0a1",
+        "pinned": true,
+        "synced": true,
+        "type": "DocumentType",
+        "version": 4,
+        "visibleName": "Dune"
+    }"#;
 
-        return Ok(());
+    #[test]
+    fn parses_document_metadata() {
+        let id = Uuid::parse_str("0b8e5c7e-6e0b-4c1c-9b7c-8a4f2d1e3c5b").unwrap();
+        let document = Document::from_metadata(id, "abc123", SAMPLE_METADATA.as_bytes()).unwrap();
+
+        assert_eq!(document.id, id);
+        assert_eq!(document.hash(), "abc123");
+        assert_eq!(document.kind(), ObjectKind::Document);
+        assert_eq!(document.visible_name, "Dune");
+        assert_eq!(
+            document.parent(),
+            Parent::Collection(Uuid::parse_str("5a0ff2c6-3a3e-4b64-9d1a-d9e3a1f4c0a1").unwrap())
+        );
+        assert_eq!(document.item_type, DocumentType::DocumentType);
+        assert_eq!(document.last_modified.timestamp_millis(), 1712349999123);
+        assert!(document.pinned);
+        assert!(!document.deleted);
+        assert_eq!(document.version, 4);
+        assert!(document.synced);
+        assert_eq!(document.extra["lastOpenedPage"], 3);
     }
 
-    pub async fn delete(&self) -> Result<(), Error> {
-        let client = reqwest::Client::new();
-        let response = client
-            .delete(formatcp!("{STORAGE_API_URL}/{self.id}"))
-            .header("Authorization", formatcp!("Bearer {self.token}"))
-            .send()
-            .await?;
+    #[test]
+    fn new_documents_round_trip_through_metadata() {
+        let document = Document::new("Planner", Parent::Root);
+        let metadata = document.to_metadata().unwrap();
+        let parsed = Document::from_metadata(document.id, "", &metadata).unwrap();
 
-        return Ok(());
+        assert_eq!(parsed.visible_name, "Planner");
+        assert_eq!(parsed.parent(), Parent::Root);
+        assert_eq!(parsed.item_type, DocumentType::DocumentType);
     }
-}
 
-impl RemarkableObject for Document {
-    fn register_client(code: String) -> Result<String, Error> {
-        unimplemented!()
+    #[test]
+    fn rejects_collection_metadata() {
+        let metadata = SAMPLE_METADATA.replace("DocumentType", "CollectionType");
+        assert!(Document::from_metadata(Uuid::nil(), "abc123", metadata.as_bytes()).is_err());
     }
 }
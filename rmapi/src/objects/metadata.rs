@@ -0,0 +1,131 @@
+use crate::error::Error;
+use crate::objects::remarkable_object::{ObjectKind, Parent, RemarkableObject};
+use crate::objects::timestamp;
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fmt;
+use uuid::Uuid;
+
+/// The `type` tag of a `.metadata` file, which tells documents and collections apart.
+pub trait ItemType:
+    fmt::Debug + Clone + Copy + PartialEq + Serialize + DeserializeOwned + Send + Sync
+{
+    /// The tag written into the metadata of new items.
+    const TAG: Self;
+    /// The kind of item the tag belongs to.
+    const KIND: ObjectKind;
+}
+
+/// An item as described by its `.metadata` file, shared by `Document` and `Collection`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase", bound = "T: ItemType")]
+pub struct Metadata<T> {
+    /// The id of the item. This is not part of the `.metadata` file,
+    /// it comes from the item's entry in the root index.
+    #[serde(skip)]
+    pub id: Uuid,
+    /// The hash of the item's index blob. Like the id, this is not part of the `.metadata` file.
+    #[serde(skip)]
+    pub hash: String,
+    /// The name shown on the tablet.
+    pub visible_name: String,
+    /// The id of the parent collection, `""` for the root or `"trash"`.
+    #[serde(default)]
+    pub parent: String,
+    /// Whether the item is a document or a collection.
+    #[serde(rename = "type")]
+    pub item_type: T,
+    /// When the item was last modified.
+    #[serde(with = "timestamp")]
+    pub last_modified: DateTime<Utc>,
+    /// Whether the item is marked as a favorite.
+    #[serde(default)]
+    pub pinned: bool,
+    /// Whether the item has been deleted.
+    #[serde(default)]
+    pub deleted: bool,
+    #[serde(default)]
+    pub version: u64,
+    #[serde(default)]
+    pub synced: bool,
+    /// Any other fields of the `.metadata` file, kept so it can be written back unchanged.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl<T: ItemType> Metadata<T> {
+    /// Creates the metadata of a new item with a fresh id.
+    ///
+    /// # Arguments
+    ///
+    /// * `visible_name` - The name shown on the tablet.
+    /// * `parent` - Where the item lives.
+    pub fn new(visible_name: &str, parent: Parent) -> Metadata<T> {
+        Metadata {
+            id: Uuid::new_v4(),
+            hash: String::new(),
+            visible_name: visible_name.to_string(),
+            parent: parent.to_string(),
+            item_type: T::TAG,
+            last_modified: Utc::now(),
+            pinned: false,
+            deleted: false,
+            version: 0,
+            synced: false,
+            extra: Map::new(),
+        }
+    }
+
+    /// Serializes the item into the contents of its `.metadata` file.
+    pub fn to_metadata(&self) -> Result<Vec<u8>, Error> {
+        Ok(serde_json::to_vec(self)?)
+    }
+
+    /// Builds the item from the contents of its `.metadata` file.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The id of the item.
+    /// * `hash` - The hash of the item's index blob.
+    /// * `metadata` - The raw contents of the `.metadata` file.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing:
+    /// - `Ok(Metadata)`: The parsed item.
+    /// - `Err(Error)`: An error if the metadata is not valid or has another `type`.
+    pub fn from_metadata(id: Uuid, hash: &str, metadata: &[u8]) -> Result<Metadata<T>, Error> {
+        let mut item: Metadata<T> = serde_json::from_slice(metadata)?;
+        item.id = id;
+        item.hash = hash.to_string();
+        Ok(item)
+    }
+}
+
+impl<T: ItemType> RemarkableObject for Metadata<T> {
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn name(&self) -> &str {
+        &self.visible_name
+    }
+
+    fn parent(&self) -> Parent {
+        Parent::parse(&self.parent)
+    }
+
+    fn kind(&self) -> ObjectKind {
+        T::KIND
+    }
+
+    fn last_modified(&self) -> DateTime<Utc> {
+        self.last_modified
+    }
+
+    fn hash(&self) -> &str {
+        &self.hash
+    }
+}
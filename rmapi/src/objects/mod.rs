@@ -1,7 +1,12 @@
 mod collection;
+mod content;
 mod document;
+mod metadata;
 mod remarkable_object;
+mod timestamp;
 
-pub use collection::Collection;
-pub use document::Document;
-pub use remarkable_object::RemarkableObject;
+pub use collection::{Collection, CollectionType};
+pub use content::{CPage, CPages, Content, Orientation, Page, Versioned};
+pub use document::{Document, DocumentType};
+pub use metadata::{ItemType, Metadata};
+pub use remarkable_object::{from_metadata, ObjectKind, Parent, RemarkableObject, TRASH_PARENT};
@@ -1,3 +1,113 @@
-trait RemarkableObject {
-    fn register_client(code: String) -> Result<String, Error>;
+use crate::error::Error;
+use crate::objects::{Collection, Document};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::fmt;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// The `parent` value used for items that have been moved to the trash.
+pub const TRASH_PARENT: &str = "trash";
+
+/// The kind of an item stored in the cloud.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ObjectKind {
+    Document,
+    Collection,
+}
+
+/// Where an item lives, as recorded in the `parent` field of its `.metadata` file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Parent {
+    /// The item is at the top level of the library.
+    Root,
+    /// The item has been moved to the trash.
+    Trash,
+    /// The item is inside the collection with the given id.
+    Collection(Uuid),
+}
+
+impl Parent {
+    /// Parses the `parent` field of a `.metadata` file.
+    ///
+    /// Unknown values are treated as the root so the item stays reachable.
+    pub fn parse(parent: &str) -> Parent {
+        match parent {
+            "" => Parent::Root,
+            TRASH_PARENT => Parent::Trash,
+            id => match Uuid::parse_str(id) {
+                Ok(id) => Parent::Collection(id),
+                Err(_) => {
+                    log::warn!("Unknown parent {:?}, treating it as the root", id);
+                    Parent::Root
+                }
+            },
+        }
+    }
+}
+
+impl fmt::Display for Parent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Parent::Root => Ok(()),
+            Parent::Trash => f.write_str(TRASH_PARENT),
+            Parent::Collection(ref id) => id.fmt(f),
+        }
+    }
+}
+
+/// The interface shared by every item stored in the cloud.
+pub trait RemarkableObject: fmt::Debug + Send + Sync {
+    /// The id of the item.
+    fn id(&self) -> Uuid;
+
+    /// The name shown on the tablet.
+    fn name(&self) -> &str;
+
+    /// Where the item lives.
+    fn parent(&self) -> Parent;
+
+    /// Whether the item is a document or a collection.
+    fn kind(&self) -> ObjectKind;
+
+    /// When the item was last modified.
+    fn last_modified(&self) -> DateTime<Utc>;
+
+    /// The hash of the item's index blob.
+    fn hash(&self) -> &str;
+}
+
+#[derive(Deserialize)]
+struct TypeTag {
+    #[serde(rename = "type")]
+    object_type: String,
+}
+
+/// Builds the item described by a `.metadata` file, whatever its type.
+///
+/// # Arguments
+///
+/// * `id` - The id of the item.
+/// * `hash` - The hash of the item's index blob.
+/// * `metadata` - The raw contents of the `.metadata` file.
+///
+/// # Returns
+///
+/// A `Result` containing:
+/// - `Ok(Arc<dyn RemarkableObject>)`: The parsed document or collection.
+/// - `Err(Error)`: An error if the metadata cannot be parsed or has an unknown type.
+pub fn from_metadata(
+    id: Uuid,
+    hash: &str,
+    metadata: &[u8],
+) -> Result<Arc<dyn RemarkableObject>, Error> {
+    let tag: TypeTag = serde_json::from_slice(metadata)?;
+    match tag.object_type.as_str() {
+        "DocumentType" => Ok(Arc::new(Document::from_metadata(id, hash, metadata)?)),
+        "CollectionType" => Ok(Arc::new(Collection::from_metadata(id, hash, metadata)?)),
+        t => Err(Error::InvalidMetadata(format!(
+            "unknown item type {:?} for {}",
+            t, id
+        ))),
+    }
 }
@@ -0,0 +1,59 @@
+//! Serde helpers for the millisecond timestamps used in `.metadata` files.
+//!
+//! The cloud writes these as strings (`"1700000000000"`), while some older
+//! clients wrote plain numbers, so both are accepted when deserializing.
+
+use chrono::{DateTime, Utc};
+use serde::de::{self, Visitor};
+use serde::{Deserializer, Serializer};
+use std::fmt;
+
+pub fn serialize<S>(timestamp: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&timestamp.timestamp_millis().to_string())
+}
+
+pub fn deserialize<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_any(MillisVisitor)
+}
+
+struct MillisVisitor;
+
+impl MillisVisitor {
+    fn from_millis<E: de::Error>(millis: i64) -> Result<DateTime<Utc>, E> {
+        DateTime::from_timestamp_millis(millis)
+            .ok_or_else(|| E::custom(format!("timestamp out of range: {}", millis)))
+    }
+}
+
+impl Visitor<'_> for MillisVisitor {
+    type Value = DateTime<Utc>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a millisecond timestamp as a string or an integer")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        if v.is_empty() {
+            return Ok(DateTime::UNIX_EPOCH);
+        }
+        let millis = v
+            .parse::<i64>()
+            .map_err(|e| E::custom(format!("invalid timestamp {:?}: {}", v, e)))?;
+        Self::from_millis(millis)
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+        Self::from_millis(v)
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+        let millis = i64::try_from(v).map_err(|_| E::custom("timestamp out of range"))?;
+        Self::from_millis(millis)
+    }
+}
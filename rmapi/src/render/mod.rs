@@ -0,0 +1,273 @@
+//! Rendering of parsed `.rm` pages to SVG and PNG, and of annotated documents to PDF.
+//!
+//! Pages are first turned into a list of simple shapes, with the look of each
+//! pen approximated by varying the width and opacity of the stroke along its
+//! points. The shapes are then written out as SVG or rasterized into a PNG.
+
+mod pdf;
+mod pen;
+mod png;
+mod svg;
+mod template;
+
+pub use self::pdf::to_pdf;
+pub use self::png::to_png;
+pub use self::svg::to_svg;
+pub use self::template::Template;
+
+use crate::lines::{Color, LinesFile, ParagraphStyle, PAGE_HEIGHT, PAGE_WIDTH};
+use std::collections::HashMap;
+
+/// An opaque colour.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Rgb {
+    pub const BLACK: Rgb = Rgb::new(0, 0, 0);
+    pub const WHITE: Rgb = Rgb::new(255, 255, 255);
+
+    pub const fn new(r: u8, g: u8, b: u8) -> Rgb {
+        Rgb { r, g, b }
+    }
+}
+
+/// The colours pens and highlights are drawn with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Palette {
+    colors: HashMap<Color, Rgb>,
+}
+
+impl Default for Palette {
+    /// The colours the tablet shows.
+    fn default() -> Palette {
+        let colors = [
+            (Color::Black, Rgb::BLACK),
+            (Color::Gray, Rgb::new(125, 125, 125)),
+            (Color::White, Rgb::WHITE),
+            (Color::Yellow, Rgb::new(255, 237, 117)),
+            (Color::Green, Rgb::new(172, 255, 133)),
+            (Color::Pink, Rgb::new(255, 143, 190)),
+            (Color::Blue, Rgb::new(78, 105, 201)),
+            (Color::Red, Rgb::new(179, 62, 57)),
+            (Color::GrayOverlap, Rgb::new(125, 125, 125)),
+            (Color::Highlight, Rgb::new(255, 237, 117)),
+            (Color::GreenHighlight, Rgb::new(172, 255, 133)),
+            (Color::Cyan, Rgb::new(136, 224, 255)),
+            (Color::Magenta, Rgb::new(255, 102, 255)),
+            (Color::YellowHighlight, Rgb::new(255, 237, 117)),
+        ];
+        Palette {
+            colors: colors.into_iter().collect(),
+        }
+    }
+}
+
+impl Palette {
+    /// Draws `color` as `rgb` instead.
+    pub fn with_color(mut self, color: Color, rgb: Rgb) -> Palette {
+        self.colors.insert(color, rgb);
+        self
+    }
+
+    /// The colour `color` is drawn with. Unknown colours are drawn black.
+    pub fn rgb(&self, color: Color) -> Rgb {
+        self.colors.get(&color).copied().unwrap_or(Rgb::BLACK)
+    }
+}
+
+/// How a page is rendered.
+///
+/// ```
+/// use rmapi::render::{RenderOptions, Template};
+///
+/// let options = RenderOptions::new()
+///     .with_scale(0.5)
+///     .with_template(Template::from_name("P Lines small"));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderOptions {
+    /// How many pixels of output a unit of the page takes. At `1.0` a page is 1404x1872.
+    pub scale: f32,
+    pub palette: Palette,
+    /// The template drawn behind the strokes when `background` is set.
+    pub template: Template,
+    /// Whether to draw a white page with the template behind the strokes.
+    /// Without it, only the annotations are drawn, on a transparent page.
+    pub background: bool,
+}
+
+impl Default for RenderOptions {
+    fn default() -> RenderOptions {
+        RenderOptions {
+            scale: 1.0,
+            palette: Palette::default(),
+            template: Template::Blank,
+            background: true,
+        }
+    }
+}
+
+impl RenderOptions {
+    /// Renders at full size, on a blank white page.
+    pub fn new() -> RenderOptions {
+        RenderOptions::default()
+    }
+
+    pub fn with_scale(mut self, scale: f32) -> RenderOptions {
+        self.scale = scale;
+        self
+    }
+
+    pub fn with_palette(mut self, palette: Palette) -> RenderOptions {
+        self.palette = palette;
+        self
+    }
+
+    pub fn with_template(mut self, template: Template) -> RenderOptions {
+        self.template = template;
+        self
+    }
+
+    pub fn with_background(mut self, background: bool) -> RenderOptions {
+        self.background = background;
+        self
+    }
+}
+
+/// How the ends of a line are drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Cap {
+    Round,
+    Square,
+}
+
+/// Something to draw, in page units with the origin at the top left of the page.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Shape {
+    Line {
+        points: Vec<(f32, f32)>,
+        width: f32,
+        color: Rgb,
+        opacity: f32,
+        cap: Cap,
+    },
+    Fill {
+        points: Vec<(f32, f32)>,
+        color: Rgb,
+        opacity: f32,
+    },
+    Dot {
+        x: f32,
+        y: f32,
+        radius: f32,
+        color: Rgb,
+    },
+    Text {
+        x: f32,
+        y: f32,
+        size: f32,
+        bold: bool,
+        text: String,
+    },
+}
+
+/// A page turned into shapes, ready to be written out.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Drawing {
+    pub width: f32,
+    pub height: f32,
+    pub background: bool,
+    pub shapes: Vec<Shape>,
+}
+
+/// The opacity highlighted text is covered with.
+const HIGHLIGHT_OPACITY: f32 = 0.4;
+/// The size of typed text, and how far apart its lines are.
+const TEXT_SIZE: f32 = 32.0;
+const TEXT_LINE_HEIGHT: f32 = 1.5 * TEXT_SIZE;
+/// How far a page scrolled past its end may grow, so a stray point cannot
+/// make it too large to rasterize.
+pub(crate) const MAX_PAGE_HEIGHT: f32 = 10.0 * PAGE_HEIGHT;
+
+impl Drawing {
+    /// Turns the visible layers of a page into shapes.
+    ///
+    /// Version 6 files measure `x` from the centre of the page, which is moved
+    /// to the left edge here. Pages that were scrolled past their end grow to
+    /// fit everything drawn on them, up to `MAX_PAGE_HEIGHT`; points with
+    /// coordinates that are not finite are left out of the height.
+    pub fn new(file: &LinesFile, options: &RenderOptions) -> Drawing {
+        let x_offset = if file.version >= 6 {
+            PAGE_WIDTH / 2.0
+        } else {
+            0.0
+        };
+        let bottom = file
+            .strokes()
+            .flat_map(|stroke| stroke.points.iter())
+            .map(|point| point.y + point.width)
+            .filter(|bottom| bottom.is_finite())
+            .fold(PAGE_HEIGHT, f32::max)
+            .min(MAX_PAGE_HEIGHT);
+        let height = bottom.ceil();
+
+        let mut shapes = Vec::new();
+        if options.background {
+            options.template.draw(PAGE_WIDTH, height, &mut shapes);
+        }
+        for layer in file.layers.iter().filter(|layer| layer.visible) {
+            for highlight in &layer.highlights {
+                let color = options.palette.rgb(highlight.color);
+                for rect in &highlight.rectangles {
+                    let (x, y) = (rect.x as f32 + x_offset, rect.y as f32);
+                    let (w, h) = (rect.width as f32, rect.height as f32);
+                    shapes.push(Shape::Fill {
+                        points: vec![(x, y), (x + w, y), (x + w, y + h), (x, y + h)],
+                        color,
+                        opacity: HIGHLIGHT_OPACITY,
+                    });
+                }
+            }
+            for stroke in &layer.strokes {
+                pen::draw(
+                    stroke,
+                    x_offset,
+                    &options.palette,
+                    options.background,
+                    &mut shapes,
+                );
+            }
+        }
+        if let Some(ref text) = file.text {
+            let mut y = text.y as f32 + TEXT_SIZE;
+            for paragraph in &text.paragraphs {
+                let (size, bold) = match paragraph.style {
+                    ParagraphStyle::Heading => (1.5 * TEXT_SIZE, true),
+                    ParagraphStyle::Bold => (TEXT_SIZE, true),
+                    _ => (TEXT_SIZE, false),
+                };
+                if !paragraph.text.is_empty() {
+                    shapes.push(Shape::Text {
+                        x: text.x as f32 + x_offset,
+                        y,
+                        size,
+                        bold,
+                        text: paragraph.text.clone(),
+                    });
+                }
+                y += TEXT_LINE_HEIGHT.max(1.5 * size);
+            }
+        }
+
+        Drawing {
+            width: PAGE_WIDTH,
+            height,
+            background: options.background,
+            shapes,
+        }
+    }
+}
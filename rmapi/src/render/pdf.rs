@@ -0,0 +1,877 @@
+use crate::archive::Archive;
+use crate::error::Error;
+use crate::file_type::FileType;
+use crate::lines::{LinesFile, PAGE_HEIGHT, PAGE_WIDTH};
+use crate::objects::{Content, Orientation, Page};
+use crate::render::{Cap, Drawing, RenderOptions, Shape, Template};
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, Stream};
+use std::collections::BTreeSet;
+use std::io::Write;
+
+/// The size of a pixel of the tablet's 226 DPI screen, in PDF points.
+const POINTS_PER_PIXEL: f32 = 72.0 / 226.0;
+/// Page attributes a page can inherit from the nodes of the page tree above it.
+const INHERITED: [&[u8]; 4] = [b"MediaBox", b"CropBox", b"Resources", b"Rotate"];
+/// How far up the page tree inherited attributes are looked for.
+const MAX_TREE_DEPTH: usize = 32;
+/// The names the standard fonts typed text is set in are added to pages as.
+const REGULAR_FONT: &str = "RmF1";
+const BOLD_FONT: &str = "RmF2";
+
+/// Renders a PDF document or a notebook as a PDF.
+///
+/// Pages are written in the order of the document's `.content` file, and
+/// strokes are drawn as vector graphics.
+///
+/// For PDF documents, each page is drawn over the page of the source PDF it
+/// shows, scaled the way the tablet fits the page to its screen in the
+/// document's orientation. Pages inserted on the tablet become new pages
+/// the size of the page before them, and pages of the source PDF that were
+/// deleted on the tablet are left out.
+///
+/// Notebooks get a page the size of the tablet's screen for each of their
+/// pages, taller where they were scrolled past the end.
+///
+/// Pages that are not part of a PDF have their template drawn behind them
+/// when `options.background` is set, or a blank page where the template
+/// cannot be told, see `Template::from_name`.
+///
+/// # Arguments
+///
+/// * `archive` - Every file of the document, as returned by `Client::export_archive`.
+/// * `options` - The palette to draw with, and whether to draw templates; the scale is not used.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// - The document has no `.content` file (`Error::InvalidArchive`).
+/// - The document is a PDF whose PDF is missing (`Error::NoSourceFile`).
+/// - The document is an EPUB, which cannot be laid out (`Error::Render`).
+/// - The PDF cannot be read or written (`Error::InvalidPdf`).
+/// - A page cannot be parsed (`Error::InvalidLinesFile`).
+pub fn to_pdf(archive: &Archive, options: &RenderOptions) -> Result<Vec<u8>, Error> {
+    let id = archive.id;
+    let content = archive
+        .file(&format!("{}.content", id))
+        .ok_or_else(|| Error::InvalidArchive(format!("no {}.content file", id)))
+        .and_then(Content::from_bytes)?;
+    match content.source_file_type() {
+        Some(FileType::Pdf) => {
+            let pdf = archive
+                .file(&format!("{}.pdf", id))
+                .ok_or_else(|| Error::NoSourceFile(id.to_string()))?;
+            annotate(pdf, archive, &content, options)
+        }
+        Some(file_type) => Err(Error::Render(format!(
+            "{} documents cannot be rendered as PDF",
+            file_type
+        ))),
+        None => notebook(archive, &content, options),
+    }
+}
+
+fn annotate(
+    pdf: &[u8],
+    archive: &Archive,
+    content: &Content,
+    options: &RenderOptions,
+) -> Result<Vec<u8>, Error> {
+    let mut doc = Document::load_mem(pdf)?;
+    let originals = doc.get_pages();
+    let pages_id = doc.catalog()?.get(b"Pages")?.as_reference()?;
+    let viewport = match content.orientation {
+        Some(Orientation::Landscape) => (PAGE_HEIGHT, PAGE_WIDTH),
+        _ => (PAGE_WIDTH, PAGE_HEIGHT),
+    };
+
+    let pages = pages(content, originals.len() as u32, true);
+    let templates = templates(archive, &pages);
+    let mut kids = Vec::with_capacity(pages.len());
+    let mut last_box = None;
+    for (page, template) in pages.iter().zip(templates) {
+        let original = page.redirect.and_then(|i| originals.get(&(i + 1)).copied());
+        if let (Some(i), None) = (page.redirect, original) {
+            log::warn!(
+                "Page {} shows page {} of a PDF with {} pages, drawing it on a blank page",
+                page.id,
+                i + 1,
+                originals.len()
+            );
+        }
+
+        let (page_id, page_box, rotation, page_options) = match original {
+            Some(page_id) => {
+                inherit_attributes(&mut doc, page_id, pages_id)?;
+                let page_box = page_box(&doc, page_id)?;
+                let page_options = options.clone().with_background(false);
+                (page_id, page_box, rotation(&doc, page_id), page_options)
+            }
+            None => {
+                let page_box = last_box.unwrap_or([
+                    0.0,
+                    0.0,
+                    viewport.0 * POINTS_PER_PIXEL,
+                    viewport.1 * POINTS_PER_PIXEL,
+                ]);
+                let page_id = add_page(&mut doc, pages_id, page_box);
+                (
+                    page_id,
+                    page_box,
+                    0,
+                    options.clone().with_template(template),
+                )
+            }
+        };
+        last_box = Some(displayed_box(page_box, rotation));
+
+        let lines = page_lines(archive, page)?;
+        let drawing = Drawing::new(&lines, &page_options);
+        if !drawing.shapes.is_empty() {
+            let transform = page_transform(page_box, rotation, viewport, lines.version);
+            draw_on_page(&mut doc, page_id, transform, &drawing.shapes)?;
+        }
+        kids.push(Object::Reference(page_id));
+    }
+
+    let count = kids.len() as i64;
+    let root = doc.get_dictionary_mut(pages_id)?;
+    root.set("Kids", kids);
+    root.set("Count", count);
+    log::debug!("Rendered {} pages onto the PDF of {}", count, archive.id);
+    save(doc)
+}
+
+fn notebook(
+    archive: &Archive,
+    content: &Content,
+    options: &RenderOptions,
+) -> Result<Vec<u8>, Error> {
+    let mut doc = Document::with_version("1.7");
+    let pages_id = doc.new_object_id();
+    let pages = pages(content, content.page_count.unwrap_or(0), false);
+    let templates = templates(archive, &pages);
+    let mut kids = Vec::with_capacity(pages.len());
+    for (page, template) in pages.iter().zip(templates) {
+        let lines = page_lines(archive, page)?;
+        let drawing = Drawing::new(&lines, &options.clone().with_template(template));
+        let width = drawing.width * POINTS_PER_PIXEL;
+        let height = drawing.height * POINTS_PER_PIXEL;
+        let page_id = add_page(&mut doc, pages_id, [0.0, 0.0, width, height]);
+        if !drawing.shapes.is_empty() {
+            let transform = [POINTS_PER_PIXEL, 0.0, 0.0, -POINTS_PER_PIXEL, 0.0, height];
+            draw_on_page(&mut doc, page_id, transform, &drawing.shapes)?;
+        }
+        kids.push(Object::Reference(page_id));
+    }
+
+    let count = kids.len() as i64;
+    doc.objects.insert(
+        pages_id,
+        Object::Dictionary(dictionary! { "Type" => "Pages", "Kids" => kids, "Count" => count }),
+    );
+    let catalog = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+    doc.trailer.set("Root", catalog);
+    log::debug!("Rendered {} pages of notebook {}", count, archive.id);
+    save(doc)
+}
+
+/// The pages of a document in order.
+///
+/// Documents from old firmware only record their page count, and name their
+/// pages by index; for PDFs those show the page at the same position.
+fn pages(content: &Content, count: u32, is_pdf: bool) -> Vec<Page> {
+    let pages = content.pages();
+    if !pages.is_empty() {
+        return pages;
+    }
+    (0..count)
+        .map(|i| Page {
+            id: i.to_string(),
+            redirect: is_pdf.then_some(i),
+            template: None,
+        })
+        .collect()
+}
+
+/// The templates of the pages of a document, in order.
+///
+/// Newer documents record the template of each page in their `.content`
+/// file, older ones in their `.pagedata` file, a line per page.
+fn templates(archive: &Archive, pages: &[Page]) -> Vec<Template> {
+    let pagedata = archive
+        .file(&format!("{}.pagedata", archive.id))
+        .map(String::from_utf8_lossy)
+        .unwrap_or_default();
+    let mut names = pagedata.lines();
+    pages
+        .iter()
+        .map(|page| {
+            let name = names.next();
+            page.template
+                .as_deref()
+                .or(name)
+                .map_or(Template::Blank, Template::from_name)
+        })
+        .collect()
+}
+
+/// Parses the `.rm` file of a page, or gives an empty page where nothing was drawn.
+fn page_lines(archive: &Archive, page: &Page) -> Result<LinesFile, Error> {
+    match archive.file(&format!("{}/{}.rm", archive.id, page.id)) {
+        Some(data) => LinesFile::parse(data),
+        None => Ok(LinesFile {
+            version: 6,
+            ..LinesFile::default()
+        }),
+    }
+}
+
+/// Adds an empty page of the given size, as `[left, bottom, right, top]`.
+fn add_page(doc: &mut Document, pages_id: ObjectId, page_box: [f32; 4]) -> ObjectId {
+    doc.add_object(dictionary! {
+        "Type" => "Page",
+        "Parent" => pages_id,
+        "MediaBox" => page_box.iter().map(|&v| Object::Real(v)).collect::<Vec<_>>(),
+        "Resources" => Dictionary::new(),
+    })
+}
+
+fn save(mut doc: Document) -> Result<Vec<u8>, Error> {
+    doc.prune_objects();
+    doc.compress();
+    let mut output = Vec::new();
+    doc.save_to(&mut output)?;
+    Ok(output)
+}
+
+/// Copies the attributes a page inherits onto the page itself, and hangs it
+/// directly off the root of the page tree, so pages can be reordered freely.
+fn inherit_attributes(doc: &mut Document, page_id: ObjectId, root: ObjectId) -> Result<(), Error> {
+    let page = doc.get_dictionary(page_id)?;
+    let mut inherited = Vec::new();
+    for key in INHERITED {
+        if page.has(key) {
+            continue;
+        }
+        let mut parent = page.get(b"Parent").and_then(Object::as_reference).ok();
+        for _ in 0..MAX_TREE_DEPTH {
+            let Some(node) = parent.and_then(|id| doc.get_dictionary(id).ok()) else {
+                break;
+            };
+            if let Ok(value) = node.get(key) {
+                inherited.push((key, value.clone()));
+                break;
+            }
+            parent = node.get(b"Parent").and_then(Object::as_reference).ok();
+        }
+    }
+
+    let page = doc.get_dictionary_mut(page_id)?;
+    for (key, value) in inherited {
+        page.set(key, value);
+    }
+    page.set("Parent", root);
+    Ok(())
+}
+
+/// The visible area of a page, as `[left, bottom, right, top]`.
+fn page_box(doc: &Document, page_id: ObjectId) -> Result<[f32; 4], Error> {
+    let page = doc.get_dictionary(page_id)?;
+    let bounds = page
+        .get_deref(b"CropBox", doc)
+        .or_else(|_| page.get_deref(b"MediaBox", doc))?
+        .as_array()?;
+    let mut values = [0.0; 4];
+    for (value, object) in values.iter_mut().zip(bounds) {
+        *value = doc.dereference(object)?.1.as_float()?;
+    }
+    let [x0, y0, x1, y1] = values;
+    Ok([x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1)])
+}
+
+/// How far a page is turned clockwise when it is shown, in degrees: 0, 90, 180 or 270.
+fn rotation(doc: &Document, page_id: ObjectId) -> i64 {
+    let rotate = doc
+        .get_dictionary(page_id)
+        .and_then(|page| page.get_deref(b"Rotate", doc))
+        .and_then(Object::as_i64)
+        .unwrap_or(0);
+    match rotate.rem_euclid(360) {
+        rotation @ (0 | 90 | 180 | 270) => rotation,
+        rotation => {
+            log::warn!("Ignoring page rotation of {} degrees", rotation);
+            0
+        }
+    }
+}
+
+/// The box of a page as it is shown once turned by `rotation`.
+fn displayed_box(page_box: [f32; 4], rotation: i64) -> [f32; 4] {
+    let [x0, y0, x1, y1] = page_box;
+    match rotation {
+        90 | 270 => [x0, y0, x0 + (y1 - y0), y0 + (x1 - x0)],
+        _ => page_box,
+    }
+}
+
+/// The transformation from the coordinates of a drawing to those of a PDF page.
+///
+/// The tablet shows the whole page, turned by its `/Rotate`, as large as
+/// fits its screen, against the top of the screen and centred across it.
+/// Version 6 files measure `x` from the middle of the page, which `Drawing`
+/// moves to `PAGE_WIDTH / 2`.
+fn page_transform(
+    page_box: [f32; 4],
+    rotation: i64,
+    viewport: (f32, f32),
+    version: u8,
+) -> [f32; 6] {
+    let [x0, y0, x1, y1] = page_box;
+    let [_, _, shown_x1, shown_y1] = displayed_box([0.0, 0.0, x1 - x0, y1 - y0], rotation);
+    let (width, height) = (shown_x1, shown_y1);
+    let pixels_per_point = (viewport.0 / width).min(viewport.1 / height);
+    let centre = if version >= 6 {
+        PAGE_WIDTH / 2.0
+    } else {
+        viewport.0 / 2.0
+    };
+    let scale = 1.0 / pixels_per_point;
+    // From the drawing to the page as shown, with its bottom left corner at the origin...
+    let shown = [
+        scale,
+        0.0,
+        0.0,
+        -scale,
+        width / 2.0 - centre * scale,
+        height,
+    ];
+    // ...and from the page as shown to the page's own coordinates.
+    let unturn = match rotation {
+        90 => [0.0, 1.0, -1.0, 0.0, x1, y0],
+        180 => [-1.0, 0.0, 0.0, -1.0, x1, y1],
+        270 => [0.0, -1.0, 1.0, 0.0, x0, y1],
+        _ => [1.0, 0.0, 0.0, 1.0, x0, y0],
+    };
+    multiply(shown, unturn)
+}
+
+/// The transformation that applies `first` and then `second`, as PDF matrices.
+fn multiply(first: [f32; 6], second: [f32; 6]) -> [f32; 6] {
+    let [a, b, c, d, e, f] = first;
+    let [a2, b2, c2, d2, e2, f2] = second;
+    [
+        a * a2 + b * c2,
+        a * b2 + b * d2,
+        c * a2 + d * c2,
+        c * b2 + d * d2,
+        e * a2 + f * c2 + e2,
+        e * b2 + f * d2 + f2,
+    ]
+}
+
+/// The resources the shapes drawn on a page need.
+#[derive(Default)]
+struct Resources {
+    /// The opacities used, in percent.
+    opacities: BTreeSet<u32>,
+    fonts: bool,
+}
+
+/// Draws shapes over the existing contents of a page.
+///
+/// The page's own contents are wrapped in `q`/`Q`, so whatever state they
+/// leave behind does not affect the shapes.
+fn draw_on_page(
+    doc: &mut Document,
+    page_id: ObjectId,
+    transform: [f32; 6],
+    shapes: &[Shape],
+) -> Result<(), Error> {
+    let mut resources = Resources::default();
+    let mut stream = b"\nQ\nq\n".to_vec();
+    let [a, b, c, d, e, f] = transform;
+    writeln!(stream, "{} {} {} {} {} {} cm", a, b, c, d, e, f)?;
+    for shape in shapes {
+        write_shape(&mut stream, shape, &mut resources)?;
+    }
+    stream.extend_from_slice(b"Q\n");
+
+    add_resources(doc, page_id, &resources)?;
+    let save = doc.add_object(Stream::new(Dictionary::new(), b"q\n".to_vec()));
+    let overlay = doc.add_object(Stream::new(Dictionary::new(), stream));
+    let mut contents = vec![Object::Reference(save)];
+    contents.extend(
+        doc.get_page_contents(page_id)
+            .into_iter()
+            .map(Object::Reference),
+    );
+    contents.push(Object::Reference(overlay));
+    doc.get_dictionary_mut(page_id)?.set("Contents", contents);
+    Ok(())
+}
+
+/// Adds the graphics states and fonts the shapes use to a page's resources.
+///
+/// The resources are copied onto the page, rather than changed where they
+/// are, as they may be shared with other pages.
+fn add_resources(doc: &mut Document, page_id: ObjectId, used: &Resources) -> Result<(), Error> {
+    let page = doc.get_dictionary(page_id)?;
+    let mut resources = match page.get(b"Resources") {
+        Ok(object) => doc.dereference(object)?.1.as_dict()?.clone(),
+        Err(_) => Dictionary::new(),
+    };
+    if !used.opacities.is_empty() {
+        let mut states = subdictionary(doc, &resources, b"ExtGState")?;
+        for &percent in &used.opacities {
+            let alpha = percent as f32 / 100.0;
+            states.set(
+                opacity_name(percent),
+                dictionary! { "Type" => "ExtGState", "CA" => alpha, "ca" => alpha },
+            );
+        }
+        resources.set("ExtGState", states);
+    }
+    if used.fonts {
+        let mut fonts = subdictionary(doc, &resources, b"Font")?;
+        for (name, font) in [(REGULAR_FONT, "Helvetica"), (BOLD_FONT, "Helvetica-Bold")] {
+            fonts.set(
+                name,
+                dictionary! {
+                    "Type" => "Font",
+                    "Subtype" => "Type1",
+                    "BaseFont" => font,
+                    "Encoding" => "WinAnsiEncoding",
+                },
+            );
+        }
+        resources.set("Font", fonts);
+    }
+    doc.get_dictionary_mut(page_id)?.set("Resources", resources);
+    Ok(())
+}
+
+fn subdictionary(doc: &Document, dictionary: &Dictionary, key: &[u8]) -> Result<Dictionary, Error> {
+    match dictionary.get(key) {
+        Ok(object) => Ok(doc.dereference(object)?.1.as_dict()?.clone()),
+        Err(_) => Ok(Dictionary::new()),
+    }
+}
+
+fn opacity_name(percent: u32) -> String {
+    format!("RmA{}", percent)
+}
+
+/// Writes the operators that draw a shape, each in its own graphics state.
+fn write_shape(out: &mut Vec<u8>, shape: &Shape, resources: &mut Resources) -> Result<(), Error> {
+    out.extend_from_slice(b"q\n");
+    let mut opacity = |out: &mut Vec<u8>, opacity: f32| -> Result<(), Error> {
+        let percent = (opacity.clamp(0.0, 1.0) * 100.0).round() as u32;
+        if percent < 100 {
+            resources.opacities.insert(percent);
+            writeln!(out, "/{} gs", opacity_name(percent))?;
+        }
+        Ok(())
+    };
+    match *shape {
+        Shape::Line {
+            ref points,
+            width,
+            color,
+            opacity: alpha,
+            cap,
+        } => {
+            opacity(out, alpha)?;
+            let cap = match cap {
+                Cap::Round => 1,
+                Cap::Square => 2,
+            };
+            writeln!(out, "{} w {} J 1 j", width, cap)?;
+            writeln!(out, "{} RG", rgb(color))?;
+            path(out, points)?;
+            if points.len() == 1 {
+                // A single point is drawn as a dot by the line cap.
+                writeln!(out, "{} {} l", points[0].0, points[0].1)?;
+            }
+            out.extend_from_slice(b"S\n");
+        }
+        Shape::Fill {
+            ref points,
+            color,
+            opacity: alpha,
+        } => {
+            opacity(out, alpha)?;
+            writeln!(out, "{} rg", rgb(color))?;
+            path(out, points)?;
+            out.extend_from_slice(b"h f\n");
+        }
+        Shape::Dot {
+            x,
+            y,
+            radius: r,
+            color,
+        } => {
+            // Four Bézier curves make a close enough circle.
+            let k = 0.5523 * r;
+            writeln!(out, "{} rg", rgb(color))?;
+            writeln!(out, "{} {} m", x + r, y)?;
+            writeln!(
+                out,
+                "{} {} {} {} {} {} c",
+                x + r,
+                y + k,
+                x + k,
+                y + r,
+                x,
+                y + r
+            )?;
+            writeln!(
+                out,
+                "{} {} {} {} {} {} c",
+                x - k,
+                y + r,
+                x - r,
+                y + k,
+                x - r,
+                y
+            )?;
+            writeln!(
+                out,
+                "{} {} {} {} {} {} c",
+                x - r,
+                y - k,
+                x - k,
+                y - r,
+                x,
+                y - r
+            )?;
+            writeln!(
+                out,
+                "{} {} {} {} {} {} c",
+                x + k,
+                y - r,
+                x + r,
+                y - k,
+                x + r,
+                y
+            )?;
+            out.extend_from_slice(b"f\n");
+        }
+        Shape::Text {
+            x,
+            y,
+            size,
+            bold,
+            ref text,
+        } => {
+            resources.fonts = true;
+            let font = if bold { BOLD_FONT } else { REGULAR_FONT };
+            // The text matrix flips the text back upright on the flipped page.
+            writeln!(
+                out,
+                "0 0 0 rg BT /{} {} Tf 1 0 0 -1 {} {} Tm",
+                font, size, x, y
+            )?;
+            out.push(b'(');
+            out.extend(encode_text(text));
+            out.extend_from_slice(b") Tj ET\n");
+        }
+    }
+    out.extend_from_slice(b"Q\n");
+    Ok(())
+}
+
+fn path(out: &mut Vec<u8>, points: &[(f32, f32)]) -> Result<(), Error> {
+    for (i, &(x, y)) in points.iter().enumerate() {
+        writeln!(out, "{} {} {}", x, y, if i == 0 { "m" } else { "l" })?;
+    }
+    Ok(())
+}
+
+fn rgb(color: crate::render::Rgb) -> String {
+    format!(
+        "{:.3} {:.3} {:.3}",
+        f32::from(color.r) / 255.0,
+        f32::from(color.g) / 255.0,
+        f32::from(color.b) / 255.0
+    )
+}
+
+/// Encodes text for a literal string in the standard fonts' WinAnsi encoding.
+///
+/// Characters outside Latin-1 cannot be shown without embedding a font, and are replaced by `?`.
+fn encode_text(text: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(text.len());
+    for c in text.chars() {
+        let byte = u8::try_from(u32::from(c)).unwrap_or(b'?');
+        if matches!(byte, b'(' | b')' | b'\\') {
+            bytes.push(b'\\');
+        }
+        bytes.push(byte);
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    /// A PDF with an A4 and a letter page, their sizes inherited from the page tree.
+    fn source_pdf() -> Vec<u8> {
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let mut kids = Vec::new();
+        for media_box in [[0, 0, 595, 842], [0, 0, 612, 792]] {
+            let content =
+                doc.add_object(Stream::new(Dictionary::new(), b"1 0 0 1 50 50 cm".to_vec()));
+            let node = doc.add_object(dictionary! {
+                "Type" => "Pages",
+                "Parent" => pages_id,
+                "MediaBox" => media_box.iter().map(|&v| Object::Integer(v)).collect::<Vec<_>>(),
+                "Count" => 1,
+            });
+            let page = doc.add_object(dictionary! {
+                "Type" => "Page",
+                "Parent" => node,
+                "Contents" => content,
+            });
+            doc.get_dictionary_mut(node)
+                .unwrap()
+                .set("Kids", vec![Object::Reference(page)]);
+            kids.push(Object::Reference(node));
+        }
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! { "Type" => "Pages", "Kids" => kids, "Count" => 2 }),
+        );
+        let catalog = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        doc.trailer.set("Root", catalog);
+        let mut pdf = Vec::new();
+        doc.save_to(&mut pdf).unwrap();
+        pdf
+    }
+
+    /// A version 3 page with a single red ballpoint stroke.
+    fn lines() -> Vec<u8> {
+        let mut data = b"reMarkable .lines file, version=3          ".to_vec();
+        for value in [1u32, 1, 2, 7, 0] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.extend_from_slice(&2.0f32.to_le_bytes());
+        data.extend_from_slice(&2u32.to_le_bytes());
+        for point in [
+            [702.0f32, 100.0, 0.0, 0.0, 2.0, 0.5],
+            [800.0, 200.0, 0.0, 0.0, 2.0, 0.9],
+        ] {
+            for value in point {
+                data.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        data
+    }
+
+    fn archive(content: &str) -> Archive {
+        let id = Uuid::new_v4();
+        Archive {
+            id,
+            files: vec![
+                (format!("{}.content", id), content.as_bytes().to_vec()),
+                (format!("{}.pdf", id), source_pdf()),
+                (format!("{}/b.rm", id), lines()),
+            ],
+        }
+    }
+
+    fn media_box(doc: &Document, page_id: ObjectId) -> Vec<f32> {
+        page_box(doc, page_id).unwrap().to_vec()
+    }
+
+    #[test]
+    fn follows_the_redirection_map() {
+        // The pages are swapped, and a blank page is inserted between them.
+        let archive = archive(
+            r#"{"fileType": "pdf", "pages": ["b", "new", "a"], "redirectionPageMap": [1, -1, 0]}"#,
+        );
+        let doc = Document::load_mem(&to_pdf(&archive, &RenderOptions::new()).unwrap()).unwrap();
+        let pages = doc.get_pages();
+        assert_eq!(pages.len(), 3);
+        assert_eq!(media_box(&doc, pages[&1]), [0.0, 0.0, 612.0, 792.0]);
+        assert_eq!(media_box(&doc, pages[&2]), [0.0, 0.0, 612.0, 792.0]);
+        assert_eq!(media_box(&doc, pages[&3]), [0.0, 0.0, 595.0, 842.0]);
+
+        let annotated =
+            String::from_utf8_lossy(&doc.get_page_content(pages[&1]).unwrap()).into_owned();
+        assert!(annotated.starts_with("q\n1 0 0 1 50 50 cm\nQ\nq\n"));
+        assert!(annotated.contains("0.702 0.243 0.224 RG"));
+        assert!(doc
+            .get_page_content(pages[&3])
+            .unwrap()
+            .ends_with(b"50 50 cm"));
+    }
+
+    #[test]
+    fn drops_deleted_pages() {
+        let archive = archive(r#"{"fileType": "pdf", "pages": ["a"], "redirectionPageMap": [0]}"#);
+        let doc = Document::load_mem(&to_pdf(&archive, &RenderOptions::new()).unwrap()).unwrap();
+        assert_eq!(doc.get_pages().len(), 1);
+    }
+
+    #[test]
+    fn fits_the_page_to_the_screen() {
+        // An A4 page is limited by the height of the portrait screen, and the
+        // centre of the screen falls on the centre of the page.
+        let a4 = [0.0, 0.0, 595.0, 842.0];
+        let [a, _, _, d, e, f] = page_transform(a4, 0, (PAGE_WIDTH, PAGE_HEIGHT), 6);
+        assert!((a - 842.0 / PAGE_HEIGHT).abs() < 1e-6);
+        assert_eq!(d, -a);
+        assert!((e + PAGE_WIDTH / 2.0 * a - 297.5).abs() < 1e-3);
+        assert_eq!(f, 842.0);
+
+        // In landscape the same page is limited by the height of the turned screen.
+        let [a, ..] = page_transform(a4, 0, (PAGE_HEIGHT, PAGE_WIDTH), 6);
+        assert!((a - 842.0 / PAGE_WIDTH).abs() < 1e-6);
+    }
+
+    fn apply(transform: [f32; 6], (x, y): (f32, f32)) -> (f32, f32) {
+        let [a, b, c, d, e, f] = transform;
+        (a * x + c * y + e, b * x + d * y + f)
+    }
+
+    fn assert_near((x, y): (f32, f32), (expected_x, expected_y): (f32, f32)) {
+        assert!(
+            (x - expected_x).abs() < 1e-2 && (y - expected_y).abs() < 1e-2,
+            "({}, {}) is not ({}, {})",
+            x,
+            y,
+            expected_x,
+            expected_y
+        );
+    }
+
+    #[test]
+    fn fits_turned_pages_as_they_are_shown() {
+        // An A4 page turned a quarter clockwise is shown as a landscape page,
+        // limited by the width of the portrait screen.
+        let a4 = [0.0, 0.0, 595.0, 842.0];
+        let scale = 842.0 / PAGE_WIDTH;
+        let top_centre = (PAGE_WIDTH / 2.0, 0.0);
+        let below = (PAGE_WIDTH / 2.0, 100.0);
+        let screen = (PAGE_WIDTH, PAGE_HEIGHT);
+
+        // The top of the shown page is the left edge of the page itself.
+        let transform = page_transform(a4, 90, screen, 6);
+        assert_near(apply(transform, top_centre), (0.0, 421.0));
+        assert_near(apply(transform, below), (100.0 * scale, 421.0));
+
+        let transform = page_transform(a4, 270, screen, 6);
+        assert_near(apply(transform, top_centre), (595.0, 421.0));
+        assert_near(apply(transform, below), (595.0 - 100.0 * scale, 421.0));
+
+        // Upside down, the top of the shown page is the bottom of the page.
+        let transform = page_transform(a4, 180, screen, 6);
+        assert_near(apply(transform, top_centre), (297.5, 0.0));
+
+        assert_eq!(
+            page_transform(a4, 0, screen, 6),
+            page_transform(a4, 45, screen, 6)
+        );
+    }
+
+    #[test]
+    fn draws_on_turned_pages() {
+        let mut archive =
+            archive(r#"{"fileType": "pdf", "pages": ["b"], "redirectionPageMap": [0]}"#);
+        let mut doc = Document::load_mem(&source_pdf()).unwrap();
+        let first = doc.get_pages()[&1];
+        let node = doc
+            .get_dictionary(first)
+            .unwrap()
+            .get(b"Parent")
+            .unwrap()
+            .as_reference()
+            .unwrap();
+        doc.get_dictionary_mut(node).unwrap().set("Rotate", 90);
+        let mut pdf = Vec::new();
+        doc.save_to(&mut pdf).unwrap();
+        archive.files[1].1 = pdf;
+
+        let doc = Document::load_mem(&to_pdf(&archive, &RenderOptions::new()).unwrap()).unwrap();
+        let page = doc.get_pages()[&1];
+        assert_eq!(rotation(&doc, page), 90);
+        let overlay = String::from_utf8_lossy(&doc.get_page_content(page).unwrap()).into_owned();
+        // Going down the shown page goes right across the page itself.
+        let cm: Vec<f32> = overlay
+            .lines()
+            .filter(|line| line.ends_with(" cm"))
+            .nth(1)
+            .unwrap()
+            .split_whitespace()
+            .filter_map(|value| value.parse().ok())
+            .collect();
+        let scale = 842.0 / PAGE_WIDTH;
+        assert_near((cm[0], cm[1]), (0.0, scale));
+        assert_near((cm[2], cm[3]), (scale, 0.0));
+    }
+
+    #[test]
+    fn lays_out_notebooks_in_page_order() {
+        let id = Uuid::new_v4();
+        let content = r#"{"fileType": "notebook", "cPages": {"pages": [
+            {"id": "b", "idx": {"timestamp": "1:2", "value": "bb"}, "template": {"timestamp": "1:3", "value": "P Grid small"}},
+            {"id": "a", "idx": {"timestamp": "1:1", "value": "ba"}, "template": {"timestamp": "1:4", "value": "Blank"}}
+        ]}}"#;
+        let archive = Archive {
+            id,
+            files: vec![
+                (format!("{}.content", id), content.as_bytes().to_vec()),
+                (format!("{}/a.rm", id), lines()),
+            ],
+        };
+        let doc = Document::load_mem(&to_pdf(&archive, &RenderOptions::new()).unwrap()).unwrap();
+        let pages = doc.get_pages();
+        assert_eq!(pages.len(), 2);
+        let width = PAGE_WIDTH * POINTS_PER_PIXEL;
+        let height = PAGE_HEIGHT * POINTS_PER_PIXEL;
+        assert_eq!(media_box(&doc, pages[&1]), [0.0, 0.0, width, height]);
+
+        // The first page has only its strokes, the second only its grid.
+        let first = String::from_utf8_lossy(&doc.get_page_content(pages[&1]).unwrap()).into_owned();
+        assert!(first.contains("0.702 0.243 0.224 RG"));
+        assert!(!first.contains("0.784 0.784 0.784 RG"));
+        let second =
+            String::from_utf8_lossy(&doc.get_page_content(pages[&2]).unwrap()).into_owned();
+        assert!(second.contains("0.784 0.784 0.784 RG"));
+
+        let blank = to_pdf(&archive, &RenderOptions::new().with_background(false)).unwrap();
+        let doc = Document::load_mem(&blank).unwrap();
+        assert!(doc
+            .get_page_content(doc.get_pages()[&2])
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn reads_old_templates_from_pagedata() {
+        let id = Uuid::new_v4();
+        let archive = Archive {
+            id,
+            files: vec![
+                (
+                    format!("{}.content", id),
+                    br#"{"fileType": "notebook", "pages": ["a", "b"]}"#.to_vec(),
+                ),
+                (
+                    format!("{}.pagedata", id),
+                    b"P Lines medium\nP Storyboard 2\n".to_vec(),
+                ),
+            ],
+        };
+        let content =
+            Content::from_bytes(archive.file(&format!("{}.content", id)).unwrap()).unwrap();
+        let templates = templates(&archive, &content.pages());
+        assert!(matches!(
+            templates[..],
+            [Template::Lines { .. }, Template::Blank]
+        ));
+    }
+}
@@ -0,0 +1,253 @@
+use crate::lines::{Pen, Point, Stroke};
+use crate::render::{Cap, Palette, Rgb, Shape};
+
+/// The opacity of highlighter and shader strokes, which are drawn over other strokes.
+const HIGHLIGHTER_OPACITY: f32 = 0.35;
+const SHADER_OPACITY: f32 = 0.12;
+/// The width of highlighter strokes when the file does not give one.
+const HIGHLIGHTER_WIDTH: f32 = 30.0;
+
+/// How a single segment of a stroke is drawn.
+struct Segment {
+    width: f32,
+    opacity: f32,
+}
+
+/// Adds the shapes that draw `stroke`.
+///
+/// Pens whose strokes look the same all along are drawn as a single line.
+/// The others are drawn a segment at a time, each with the width and opacity
+/// the pen would give it from the pressure, speed and tilt at its end.
+///
+/// Erasers are drawn in white, which only hides what is under them when the
+/// page has a white background; without one they are left out, so they do not
+/// paint over whatever the drawing is placed on.
+pub(crate) fn draw(
+    stroke: &Stroke,
+    x_offset: f32,
+    palette: &Palette,
+    background: bool,
+    shapes: &mut Vec<Shape>,
+) {
+    if stroke.points.is_empty() {
+        return;
+    }
+    if !background && matches!(stroke.pen, Pen::Eraser | Pen::EraseArea) {
+        return;
+    }
+    let points: Vec<(f32, f32)> = stroke
+        .points
+        .iter()
+        .map(|p| (p.x + x_offset, p.y))
+        .collect();
+    let color = palette.rgb(stroke.color);
+    let base = stroke.thickness_scale as f32;
+    let first_width = stroke.points[0].width;
+
+    let line =
+        |points: Vec<(f32, f32)>, width: f32, color: Rgb, opacity: f32, cap: Cap| Shape::Line {
+            points,
+            width,
+            color,
+            opacity,
+            cap,
+        };
+    match stroke.pen {
+        Pen::Fineliner => {
+            shapes.push(line(points, base.powf(2.1) * 1.3, color, 1.0, Cap::Round));
+        }
+        Pen::Highlighter => {
+            let width = if first_width > 0.0 {
+                first_width
+            } else {
+                HIGHLIGHTER_WIDTH
+            };
+            shapes.push(line(points, width, color, HIGHLIGHTER_OPACITY, Cap::Square));
+        }
+        Pen::Shader => {
+            shapes.push(line(
+                points,
+                first_width.max(base),
+                color,
+                SHADER_OPACITY,
+                Cap::Round,
+            ));
+        }
+        Pen::Eraser => {
+            shapes.push(line(
+                points,
+                base * 2.0 * first_width.max(1.0),
+                Rgb::WHITE,
+                1.0,
+                Cap::Square,
+            ));
+        }
+        Pen::EraseArea => {
+            // The tool erases everything inside the outline it is drawn around.
+            shapes.push(Shape::Fill {
+                points,
+                color: Rgb::WHITE,
+                opacity: 1.0,
+            });
+        }
+        pen => {
+            let mut last_width = first_width;
+            for (i, point) in stroke.points.iter().enumerate().skip(1) {
+                let segment = segment(pen, base, point, last_width);
+                last_width = segment.width;
+                if segment.width <= 0.0 || segment.opacity <= 0.0 {
+                    continue;
+                }
+                shapes.push(line(
+                    vec![points[i - 1], points[i]],
+                    segment.width,
+                    color,
+                    segment.opacity,
+                    Cap::Round,
+                ));
+            }
+            if stroke.points.len() == 1 {
+                shapes.push(line(points, first_width.max(base), color, 1.0, Cap::Round));
+            }
+        }
+    }
+}
+
+/// Works out how a segment ending at `point` is drawn with `pen`.
+///
+/// These follow the community renderers' approximations of the tablet's pens.
+fn segment(pen: Pen, base: f32, point: &Point, last_width: f32) -> Segment {
+    let tilt = point.direction;
+    let speed = point.speed;
+    let pressure = point.pressure;
+    match pen {
+        Pen::Ballpoint => Segment {
+            width: 0.5 + pressure + point.width - 0.5 * (speed / 50.0),
+            opacity: (0.5 + 1.2 * pressure - 0.1 * (speed / 35.0)).clamp(0.0, 1.0),
+        },
+        Pen::Marker => Segment {
+            width: 0.9 * (point.width - 0.4 * tilt) + 0.1 * last_width,
+            opacity: 1.0,
+        },
+        Pen::Pencil => Segment {
+            width: (0.7
+                * (((0.8 * base) + (0.5 * pressure)) * point.width
+                    - 0.25 * tilt.powf(1.8)
+                    - 0.6 * (speed / 50.0)))
+                .min(base * 10.0),
+            // A lighter line where less pressure was applied stands in for the pencil's grain.
+            opacity: ((1.0 * pressure - 0.1 * (speed / 35.0)).clamp(0.0, 1.0) - 0.1).max(0.05),
+        },
+        Pen::MechanicalPencil => Segment {
+            width: base * base,
+            opacity: ((0.7 * pressure - 0.1 * (speed / 35.0)).clamp(0.0, 1.0) - 0.1).max(0.05),
+        },
+        Pen::Paintbrush => Segment {
+            width: 0.7 * ((1.0 + 1.4 * pressure) * point.width - 0.5 * tilt - speed / 50.0),
+            opacity: (0.5 + pressure).clamp(0.0, 1.0),
+        },
+        Pen::Calligraphy => Segment {
+            width: 0.9 * (point.width * (0.5 + tilt.sin().abs())) + 0.1 * last_width,
+            opacity: 1.0,
+        },
+        _ => Segment {
+            width: point.width.max(base),
+            opacity: 1.0,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lines::Color;
+
+    fn stroke(pen: Pen, pressures: &[f32]) -> Stroke {
+        Stroke {
+            pen,
+            color: Color::Black,
+            thickness_scale: 2.0,
+            points: pressures
+                .iter()
+                .enumerate()
+                .map(|(i, &pressure)| Point {
+                    x: i as f32 * 10.0,
+                    y: 100.0,
+                    speed: 0.0,
+                    direction: 0.0,
+                    width: 2.0,
+                    pressure,
+                })
+                .collect(),
+        }
+    }
+
+    fn widths(shapes: &[Shape]) -> Vec<f32> {
+        shapes
+            .iter()
+            .map(|shape| match *shape {
+                Shape::Line { width, .. } => width,
+                _ => panic!("expected a line"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn ballpoint_widens_with_pressure() {
+        let mut shapes = Vec::new();
+        draw(
+            &stroke(Pen::Ballpoint, &[0.2, 0.2, 0.9]),
+            0.0,
+            &Palette::default(),
+            true,
+            &mut shapes,
+        );
+        let widths = widths(&shapes);
+        assert_eq!(widths.len(), 2);
+        assert!(widths[1] > widths[0]);
+    }
+
+    #[test]
+    fn highlighter_is_one_translucent_line() {
+        let mut shapes = Vec::new();
+        draw(
+            &stroke(Pen::Highlighter, &[0.5; 4]),
+            702.0,
+            &Palette::default(),
+            true,
+            &mut shapes,
+        );
+        match shapes[..] {
+            [Shape::Line {
+                ref points,
+                opacity,
+                cap: Cap::Square,
+                ..
+            }] => {
+                assert_eq!(points.len(), 4);
+                assert_eq!(points[0], (702.0, 100.0));
+                assert!(opacity < 1.0);
+            }
+            _ => panic!("expected a single line, got {:?}", shapes),
+        }
+    }
+
+    #[test]
+    fn erasers_are_left_out_without_a_background() {
+        let eraser = stroke(Pen::Eraser, &[0.5; 3]);
+        let mut shapes = Vec::new();
+        draw(&eraser, 0.0, &Palette::default(), true, &mut shapes);
+        assert_eq!(shapes.len(), 1);
+
+        shapes.clear();
+        draw(&eraser, 0.0, &Palette::default(), false, &mut shapes);
+        draw(
+            &stroke(Pen::EraseArea, &[0.5; 3]),
+            0.0,
+            &Palette::default(),
+            false,
+            &mut shapes,
+        );
+        assert!(shapes.is_empty());
+    }
+}
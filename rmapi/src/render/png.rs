@@ -0,0 +1,196 @@
+use crate::error::Error;
+use crate::lines::LinesFile;
+use crate::render::{Cap, Drawing, RenderOptions, Rgb, Shape};
+use tiny_skia::{FillRule, LineCap, LineJoin, Paint, PathBuilder, Pixmap, Stroke, Transform};
+
+/// Renders a page and encodes it as a PNG.
+///
+/// Typed text is not drawn, since no fonts are bundled, and a warning is
+/// logged for pages that have some; use `to_svg` for pages with text.
+///
+/// # Errors
+///
+/// This function will return `Error::Render` if the scaled page is empty or
+/// too large to allocate, or the PNG cannot be encoded.
+pub fn to_png(file: &LinesFile, options: &RenderOptions) -> Result<Vec<u8>, Error> {
+    let drawing = Drawing::new(file, options);
+    if drawing
+        .shapes
+        .iter()
+        .any(|shape| matches!(shape, Shape::Text { .. }))
+    {
+        log::warn!("The typed text of the page is left out of the PNG");
+    }
+    let pixmap = rasterize(&drawing, options.scale)?;
+    pixmap
+        .encode_png()
+        .map_err(|e| Error::Render(format!("could not encode PNG: {}", e)))
+}
+
+fn rasterize(drawing: &Drawing, scale: f32) -> Result<Pixmap, Error> {
+    let width = (drawing.width * scale).round() as u32;
+    let height = (drawing.height * scale).round() as u32;
+    let mut pixmap = Pixmap::new(width, height).ok_or_else(|| {
+        Error::Render(format!(
+            "cannot render a page of {}x{} pixels",
+            width, height
+        ))
+    })?;
+    if drawing.background {
+        pixmap.fill(color(Rgb::WHITE, 1.0));
+    }
+
+    let transform = Transform::from_scale(scale, scale);
+    for shape in &drawing.shapes {
+        match *shape {
+            Shape::Line {
+                ref points,
+                width,
+                color: rgb,
+                opacity,
+                cap,
+            } => {
+                let Some(path) = polyline(points, false) else {
+                    continue;
+                };
+                let stroke = Stroke {
+                    width,
+                    line_cap: match cap {
+                        Cap::Round => LineCap::Round,
+                        Cap::Square => LineCap::Square,
+                    },
+                    line_join: LineJoin::Round,
+                    ..Stroke::default()
+                };
+                pixmap.stroke_path(&path, &paint(rgb, opacity), &stroke, transform, None);
+            }
+            Shape::Fill {
+                ref points,
+                color: rgb,
+                opacity,
+            } => {
+                if let Some(path) = polyline(points, true) {
+                    let paint = paint(rgb, opacity);
+                    pixmap.fill_path(&path, &paint, FillRule::Winding, transform, None);
+                }
+            }
+            Shape::Dot {
+                x,
+                y,
+                radius,
+                color: rgb,
+            } => {
+                if let Some(path) = PathBuilder::from_circle(x, y, radius) {
+                    let paint = paint(rgb, 1.0);
+                    pixmap.fill_path(&path, &paint, FillRule::Winding, transform, None);
+                }
+            }
+            Shape::Text { .. } => {}
+        }
+    }
+    Ok(pixmap)
+}
+
+fn polyline(points: &[(f32, f32)], close: bool) -> Option<tiny_skia::Path> {
+    let (&(x, y), rest) = points.split_first()?;
+    let mut builder = PathBuilder::new();
+    builder.move_to(x, y);
+    if rest.is_empty() {
+        // A single point is drawn as a dot by the line cap.
+        builder.line_to(x, y);
+    }
+    for &(x, y) in rest {
+        builder.line_to(x, y);
+    }
+    if close {
+        builder.close();
+    }
+    builder.finish()
+}
+
+fn color(rgb: Rgb, opacity: f32) -> tiny_skia::Color {
+    tiny_skia::Color::from_rgba8(rgb.r, rgb.g, rgb.b, (opacity.clamp(0.0, 1.0) * 255.0) as u8)
+}
+
+fn paint(rgb: Rgb, opacity: f32) -> Paint<'static> {
+    let mut paint = Paint::default();
+    paint.set_color(color(rgb, opacity));
+    paint.anti_alias = true;
+    paint
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lines::{Color, Layer, Pen, Point, Stroke, PAGE_HEIGHT};
+    use crate::render::MAX_PAGE_HEIGHT;
+
+    fn page(pen: Pen) -> LinesFile {
+        let point = |y| Point {
+            x: 0.0,
+            y,
+            speed: 0.0,
+            direction: 0.0,
+            width: 10.0,
+            pressure: 1.0,
+        };
+        LinesFile {
+            version: 6,
+            layers: vec![Layer {
+                name: "Layer 1".to_string(),
+                visible: true,
+                strokes: vec![Stroke {
+                    pen,
+                    color: Color::Black,
+                    thickness_scale: 3.0,
+                    points: vec![point(100.0), point(200.0)],
+                }],
+                highlights: Vec::new(),
+            }],
+            text: None,
+        }
+    }
+
+    fn pixel(pixmap: &Pixmap, x: u32, y: u32) -> (u8, u8, u8, u8) {
+        let pixel = pixmap.pixel(x, y).unwrap().demultiply();
+        (pixel.red(), pixel.green(), pixel.blue(), pixel.alpha())
+    }
+
+    #[test]
+    fn draws_strokes_at_scale() {
+        let drawing = Drawing::new(&page(Pen::Fineliner), &RenderOptions::new());
+        let pixmap = rasterize(&drawing, 0.5).unwrap();
+        assert_eq!((pixmap.width(), pixmap.height()), (702, 936));
+        // The stroke runs down the middle of the page, from y = 100 to 200.
+        assert_eq!(pixel(&pixmap, 351, 75), (0, 0, 0, 255));
+        assert_eq!(pixel(&pixmap, 351, 20), (255, 255, 255, 255));
+    }
+
+    #[test]
+    fn draws_highlighters_translucent_on_a_transparent_page() {
+        let options = RenderOptions::new().with_background(false);
+        let drawing = Drawing::new(&page(Pen::Highlighter), &options);
+        let pixmap = rasterize(&drawing, 1.0).unwrap();
+        let (_, _, _, alpha) = pixel(&pixmap, 702, 150);
+        assert!(alpha > 0 && alpha < 255);
+        assert_eq!(pixel(&pixmap, 10, 10).3, 0);
+        assert!(to_png(&page(Pen::Highlighter), &options)
+            .unwrap()
+            .starts_with(b"\x89PNG"));
+    }
+
+    #[test]
+    fn caps_the_height_of_pages_with_stray_points() {
+        let mut file = page(Pen::Fineliner);
+        let stroke = &mut file.layers[0].strokes[0];
+        stroke.points[0].y = f32::NAN;
+        stroke.points[1].y = f32::INFINITY;
+        let drawing = Drawing::new(&file, &RenderOptions::new());
+        assert_eq!(drawing.height, PAGE_HEIGHT);
+
+        file.layers[0].strokes[0].points[1].y = 1.0e30;
+        let drawing = Drawing::new(&file, &RenderOptions::new());
+        assert_eq!(drawing.height, MAX_PAGE_HEIGHT);
+        assert!(to_png(&file, &RenderOptions::new().with_scale(0.1)).is_ok());
+    }
+}
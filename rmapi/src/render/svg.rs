@@ -0,0 +1,185 @@
+use crate::lines::LinesFile;
+use crate::render::{Cap, Drawing, RenderOptions, Rgb, Shape};
+use std::fmt::Write;
+
+/// Renders a page as an SVG document.
+///
+/// The SVG keeps the page's own coordinates in its `viewBox` and is sized by
+/// `options.scale`, so it can be scaled further without losing detail.
+pub fn to_svg(file: &LinesFile, options: &RenderOptions) -> String {
+    let drawing = Drawing::new(file, options);
+    let mut svg = String::new();
+    // Writing to a String cannot fail.
+    let _ = write_svg(&mut svg, &drawing, options.scale);
+    svg
+}
+
+fn write_svg(svg: &mut String, drawing: &Drawing, scale: f32) -> std::fmt::Result {
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="0 0 {} {}">"#,
+        (drawing.width * scale).round(),
+        (drawing.height * scale).round(),
+        drawing.width,
+        drawing.height
+    )?;
+    if drawing.background {
+        writeln!(
+            svg,
+            r#"<rect width="100%" height="100%" fill="{}"/>"#,
+            hex(Rgb::WHITE)
+        )?;
+    }
+    for shape in &drawing.shapes {
+        match *shape {
+            Shape::Line {
+                ref points,
+                width,
+                color,
+                opacity,
+                cap,
+            } => {
+                let cap = match cap {
+                    Cap::Round => "round",
+                    Cap::Square => "square",
+                };
+                write!(
+                    svg,
+                    r#"<polyline points="{}" fill="none" stroke="{}" stroke-width="{:.3}" stroke-linecap="{}" stroke-linejoin="round""#,
+                    coordinates(points),
+                    hex(color),
+                    width,
+                    cap
+                )?;
+                if opacity < 1.0 {
+                    write!(svg, r#" stroke-opacity="{:.3}""#, opacity)?;
+                }
+                writeln!(svg, "/>")?;
+            }
+            Shape::Fill {
+                ref points,
+                color,
+                opacity,
+            } => {
+                write!(
+                    svg,
+                    r#"<polygon points="{}" fill="{}""#,
+                    coordinates(points),
+                    hex(color)
+                )?;
+                if opacity < 1.0 {
+                    write!(svg, r#" fill-opacity="{:.3}""#, opacity)?;
+                }
+                writeln!(svg, "/>")?;
+            }
+            Shape::Dot {
+                x,
+                y,
+                radius,
+                color,
+            } => {
+                writeln!(
+                    svg,
+                    r#"<circle cx="{:.3}" cy="{:.3}" r="{:.3}" fill="{}"/>"#,
+                    x,
+                    y,
+                    radius,
+                    hex(color)
+                )?;
+            }
+            Shape::Text {
+                x,
+                y,
+                size,
+                bold,
+                ref text,
+            } => {
+                writeln!(
+                    svg,
+                    r#"<text x="{:.3}" y="{:.3}" font-family="sans-serif" font-size="{}" font-weight="{}">{}</text>"#,
+                    x,
+                    y,
+                    size,
+                    if bold { "bold" } else { "normal" },
+                    escape(text)
+                )?;
+            }
+        }
+    }
+    writeln!(svg, "</svg>")
+}
+
+fn coordinates(points: &[(f32, f32)]) -> String {
+    points
+        .iter()
+        .map(|(x, y)| format!("{:.3},{:.3}", x, y))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn hex(color: Rgb) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.r, color.g, color.b)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lines::{Color, Layer, Paragraph, ParagraphStyle, Pen, Point, Stroke, Text};
+    use crate::render::Template;
+
+    fn page() -> LinesFile {
+        let point = |x| Point {
+            x,
+            y: 10.0,
+            speed: 0.0,
+            direction: 0.0,
+            width: 2.0,
+            pressure: 0.5,
+        };
+        LinesFile {
+            version: 6,
+            layers: vec![Layer {
+                name: "Layer 1".to_string(),
+                visible: true,
+                strokes: vec![Stroke {
+                    pen: Pen::Fineliner,
+                    color: Color::Blue,
+                    thickness_scale: 1.0,
+                    points: vec![point(0.0), point(100.0)],
+                }],
+                highlights: Vec::new(),
+            }],
+            text: Some(Text {
+                x: -500.0,
+                y: 100.0,
+                width: 1000.0,
+                paragraphs: vec![Paragraph {
+                    style: ParagraphStyle::Plain,
+                    text: "Fish & <chips>".to_string(),
+                }],
+            }),
+        }
+    }
+
+    #[test]
+    fn writes_strokes_and_text() {
+        let svg = to_svg(&page(), &RenderOptions::new().with_scale(0.5));
+        assert!(svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" width="702" height="936" viewBox="0 0 1404 1872">"#));
+        assert!(svg.contains(r##"<polyline points="702.000,10.000 802.000,10.000" fill="none" stroke="#4e69c9" stroke-width="1.300""##));
+        assert!(svg.contains("Fish &amp; &lt;chips&gt;</text>"));
+        assert!(svg.trim_end().ends_with("</svg>"));
+    }
+
+    #[test]
+    fn leaves_out_the_page_without_a_background() {
+        let options = RenderOptions::new().with_template(Template::Lines { spacing: 50.0 });
+        assert!(to_svg(&page(), &options).contains(r##"fill="#ffffff""##));
+        assert!(!to_svg(&page(), &options.with_background(false)).contains("<rect"));
+    }
+}
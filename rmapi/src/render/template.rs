@@ -0,0 +1,140 @@
+use crate::render::{Cap, Rgb, Shape};
+
+/// The colour template lines and dots are drawn with.
+const TEMPLATE_COLOR: Rgb = Rgb::new(200, 200, 200);
+const TEMPLATE_LINE_WIDTH: f32 = 2.0;
+const TEMPLATE_DOT_RADIUS: f32 = 3.0;
+/// How far below the top of the page the first line of a template is.
+const TEMPLATE_TOP_MARGIN: f32 = 120.0;
+
+const SMALL_SPACING: f32 = 52.0;
+const MEDIUM_SPACING: f32 = 70.0;
+const LARGE_SPACING: f32 = 90.0;
+
+/// The background of a page.
+///
+/// The tablet draws its templates from image files that are not synced to
+/// the cloud, so the common ones are approximated here.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Template {
+    Blank,
+    /// Ruled lines, `spacing` apart.
+    Lines {
+        spacing: f32,
+    },
+    /// Squares with sides of `spacing`.
+    Grid {
+        spacing: f32,
+    },
+    /// A grid of dots, `spacing` apart.
+    Dots {
+        spacing: f32,
+    },
+}
+
+impl Template {
+    /// Picks the template closest to one of the tablet's, by its name as
+    /// found in `.content` files, such as `P Lines small` or `LS Grid margin large`.
+    ///
+    /// Names that are not recognised give `Template::Blank`.
+    pub fn from_name(name: &str) -> Template {
+        let words: Vec<String> = name
+            .split_whitespace()
+            .map(str::to_ascii_lowercase)
+            .collect();
+        let has = |word: &str| words.iter().any(|w| w == word);
+        let spacing = if has("small") || has("s") {
+            SMALL_SPACING
+        } else if has("large") || has("l") {
+            LARGE_SPACING
+        } else {
+            MEDIUM_SPACING
+        };
+
+        if has("lines") {
+            Template::Lines { spacing }
+        } else if has("grid") || has("squared") {
+            Template::Grid { spacing }
+        } else if has("dots") || has("dotted") {
+            Template::Dots { spacing }
+        } else {
+            if !name.is_empty() && name != "Blank" {
+                log::debug!("Drawing unknown template {:?} as a blank page", name);
+            }
+            Template::Blank
+        }
+    }
+
+    /// Adds the shapes of the template on a page of the given size.
+    pub(crate) fn draw(&self, width: f32, height: f32, shapes: &mut Vec<Shape>) {
+        let line = |from: (f32, f32), to: (f32, f32)| Shape::Line {
+            points: vec![from, to],
+            width: TEMPLATE_LINE_WIDTH,
+            color: TEMPLATE_COLOR,
+            opacity: 1.0,
+            cap: Cap::Square,
+        };
+        let steps = |spacing: f32, start: f32, end: f32| {
+            (0..)
+                .map(move |i| start + i as f32 * spacing)
+                .take_while(move |&v| v < end)
+        };
+
+        match *self {
+            Template::Blank => {}
+            Template::Lines { spacing } => {
+                for y in steps(spacing, TEMPLATE_TOP_MARGIN, height) {
+                    shapes.push(line((0.0, y), (width, y)));
+                }
+            }
+            Template::Grid { spacing } => {
+                for y in steps(spacing, spacing, height) {
+                    shapes.push(line((0.0, y), (width, y)));
+                }
+                for x in steps(spacing, spacing, width) {
+                    shapes.push(line((x, 0.0), (x, height)));
+                }
+            }
+            Template::Dots { spacing } => {
+                for y in steps(spacing, spacing, height) {
+                    for x in steps(spacing, spacing, width) {
+                        shapes.push(Shape::Dot {
+                            x,
+                            y,
+                            radius: TEMPLATE_DOT_RADIUS,
+                            color: TEMPLATE_COLOR,
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognises_tablet_template_names() {
+        assert_eq!(
+            Template::from_name("P Lines small"),
+            Template::Lines {
+                spacing: SMALL_SPACING
+            }
+        );
+        assert_eq!(
+            Template::from_name("LS Grid large"),
+            Template::Grid {
+                spacing: LARGE_SPACING
+            }
+        );
+        assert_eq!(
+            Template::from_name("P Dots S"),
+            Template::Dots {
+                spacing: SMALL_SPACING
+            }
+        );
+        assert_eq!(Template::from_name("P Storyboard 2"), Template::Blank);
+    }
+}
@@ -0,0 +1,219 @@
+use crate::error::Error;
+use chrono::{DateTime, Utc};
+use reqwest::header::RETRY_AFTER;
+use reqwest::{RequestBuilder, Response, StatusCode};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+/// When and how often failed requests are retried.
+///
+/// Requests are retried after connection failures, timeouts, `429 Too Many
+/// Requests` and `5xx` responses, waiting exponentially longer between
+/// attempts. A `Retry-After` header sent by the server takes precedence over
+/// the computed delay.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The total number of attempts, including the first one. `1` disables retries.
+    pub max_attempts: u32,
+    /// The delay before the first retry, doubled for every retry after it.
+    pub base_delay: Duration,
+    /// The longest delay between two attempts, including delays asked for with `Retry-After`.
+    pub max_delay: Duration,
+    /// Whether to randomize delays so concurrent clients do not retry in lockstep.
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 4,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that never retries.
+    pub fn none() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 1,
+            ..RetryPolicy::default()
+        }
+    }
+
+    /// The delay before retry number `retry` (starting at 1), ignoring `Retry-After`.
+    fn backoff(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        let delay = self.base_delay.saturating_mul(factor).min(self.max_delay);
+        if self.jitter {
+            // Pick a delay between half and all of the computed one. The jitter
+            // only has to differ between calls and clients, not be unpredictable,
+            // and every `RandomState` is seeded differently, so hashing nothing
+            // with a new one gives such a number without depending on `rand`.
+            let random = RandomState::new().build_hasher().finish();
+            delay / 2 + delay.mul_f64((random % 1000) as f64 / 2000.0)
+        } else {
+            delay
+        }
+    }
+}
+
+/// Whether a request can be sent more than once without changing its outcome.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Idempotency {
+    /// Sending the request again is always safe, e.g. `GET`s and uploads of
+    /// content addressed blobs.
+    Idempotent,
+    /// The request may have an effect every time it reaches the server, so it
+    /// is only retried when it is known that it did not: when the connection
+    /// could not be established or the server answered `429`.
+    NotIdempotent,
+}
+
+/// Parses a `Retry-After` header, given either in seconds or as an HTTP date.
+pub(crate) fn retry_after(response: &Response) -> Option<Duration> {
+    let value = response.headers().get(RETRY_AFTER)?.to_str().ok()?;
+    parse_retry_after(value, Utc::now())
+}
+
+/// Parses the value of a `Retry-After` header into the delay from `now`.
+///
+/// Dates in the past give no delay at all.
+fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (date.with_timezone(&Utc) - now)
+            .to_std()
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+fn should_retry_status(status: StatusCode, idempotency: Idempotency) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS
+        || (status.is_server_error() && idempotency == Idempotency::Idempotent)
+}
+
+fn should_retry_error(err: &reqwest::Error, idempotency: Idempotency) -> bool {
+    match idempotency {
+        Idempotency::Idempotent => !err.is_builder() && !err.is_redirect() && !err.is_decode(),
+        Idempotency::NotIdempotent => err.is_connect(),
+    }
+}
+
+/// Sends the request built by `make_request`, retrying according to `policy`.
+///
+/// `make_request` is called again for every attempt since a request cannot be
+/// sent twice. The last response is returned as is once the attempts run out,
+/// so error statuses are left for the caller to handle.
+pub(crate) async fn send<F>(
+    policy: &RetryPolicy,
+    idempotency: Idempotency,
+    make_request: F,
+) -> Result<Response, Error>
+where
+    F: Fn() -> RequestBuilder,
+{
+    let mut attempt = 1;
+    loop {
+        let last_attempt = attempt >= policy.max_attempts;
+        let delay = match make_request().send().await {
+            Ok(response) => {
+                if last_attempt || !should_retry_status(response.status(), idempotency) {
+                    return Ok(response);
+                }
+                let backoff = policy.backoff(attempt);
+                let delay = retry_after(&response)
+                    .map(|delay| delay.min(policy.max_delay))
+                    .unwrap_or(backoff);
+                log::warn!(
+                    "Attempt {} at {} returned {}, retrying in {:?}",
+                    attempt,
+                    response.url().path(),
+                    response.status(),
+                    delay
+                );
+                delay
+            }
+            Err(err) => {
+                if last_attempt || !should_retry_error(&err, idempotency) {
+                    return Err(Error::from(err));
+                }
+                let delay = policy.backoff(attempt);
+                log::warn!(
+                    "Attempt {} failed: {}, retrying in {:?}",
+                    attempt,
+                    err,
+                    delay
+                );
+                delay
+            }
+        };
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(jitter: bool) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 10,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(3),
+            jitter,
+        }
+    }
+
+    #[test]
+    fn doubles_the_delay_up_to_the_maximum() {
+        let policy = policy(false);
+        let delays: Vec<_> = (1..=5).map(|retry| policy.backoff(retry)).collect();
+        assert_eq!(
+            delays,
+            [500, 1000, 2000, 3000, 3000].map(Duration::from_millis)
+        );
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_secs(3));
+    }
+
+    #[test]
+    fn jitter_keeps_delays_between_half_and_all_of_the_backoff() {
+        let (plain, jittered) = (policy(false), policy(true));
+        for retry in 1..=5 {
+            let delay = plain.backoff(retry);
+            for _ in 0..100 {
+                let jittered = jittered.backoff(retry);
+                assert!(jittered >= delay / 2 && jittered <= delay, "{:?}", jittered);
+            }
+        }
+    }
+
+    #[test]
+    fn parses_retry_after_in_seconds_and_as_a_date() {
+        let now = DateTime::parse_from_rfc2822("Wed, 21 Oct 2026 07:28:00 GMT")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(
+            parse_retry_after(" 120 ", now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2026 07:28:30 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2026 07:00:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+        assert_eq!(parse_retry_after("-5", now), None);
+    }
+}
@@ -0,0 +1,323 @@
+use crate::error::Error;
+
+/// Type field of an index entry that points at another index blob (a document or a collection).
+const INDEX_ENTRY_TYPE: &str = "80000000";
+/// Type field of an index entry that points at a plain file blob.
+const FILE_ENTRY_TYPE: &str = "0";
+/// Type field of the `0:<id>:<count>:<size>` summary line that schema 4 indexes carry after the version.
+const SUMMARY_TYPE: &str = "0";
+/// Id field of the summary line of the root index; document indexes give the document id instead.
+const ROOT_SUMMARY_ID: &str = ".";
+
+/// The schema version an index blob was written with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaVersion {
+    V3,
+    V4,
+}
+
+/// What an index entry points at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryType {
+    /// The entry points at another index blob (the root index lists documents this way).
+    Index,
+    /// The entry points at a file blob (`.metadata`, `.content`, `.pdf`, page `.rm` files, ...).
+    File,
+}
+
+/// A single line of an index blob.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexEntry {
+    /// The SHA-256 hash of the blob this entry points at, hex encoded.
+    pub hash: String,
+    /// Whether the entry points at another index or at a file.
+    pub entry_type: EntryType,
+    /// The document id for root entries, or the file name for document entries.
+    pub id: String,
+    /// The number of files in the blob this entry points at (0 for files).
+    pub subfiles: u32,
+    /// The size of the blob in bytes.
+    pub size: u64,
+}
+
+/// A parsed index blob, either the root index or the index of a single document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Index {
+    /// The schema the index was written with.
+    pub schema: SchemaVersion,
+    /// The id of the document the index lists the files of, or `None` for the
+    /// root index. Only schema 4 indexes record it, in their summary line.
+    pub document_id: Option<String>,
+    /// The entries listed in the index.
+    pub entries: Vec<IndexEntry>,
+}
+
+impl Index {
+    /// Parses an index blob as returned by the sync files endpoint.
+    ///
+    /// Both schema 3 and schema 4 are accepted. Schema 4 blobs have an extra
+    /// `0:<id>:<count>:<size>` summary line after the version, where the id is
+    /// `.` for the root index and the document id for document indexes. The
+    /// id is kept in `document_id`; the counts are recomputed when writing.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The raw contents of the index blob.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing:
+    /// - `Ok(Index)`: The parsed index.
+    /// - `Err(Error)`: An error if the blob is not a valid index.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `Error::InvalidIndex` if:
+    /// - The blob is not valid UTF-8.
+    /// - The schema version is missing or unsupported.
+    /// - A schema 4 blob has no valid summary line.
+    /// - Any entry line does not have the expected five fields.
+    pub fn parse(data: &[u8]) -> Result<Index, Error> {
+        let text = std::str::from_utf8(data)
+            .map_err(|e| Error::InvalidIndex(format!("index is not valid UTF-8: {}", e)))?;
+        let mut lines = text.lines();
+
+        let schema = match lines.next().map(str::trim) {
+            Some("3") => SchemaVersion::V3,
+            Some("4") => SchemaVersion::V4,
+            Some(v) => {
+                return Err(Error::InvalidIndex(format!(
+                    "unsupported index schema version: {:?}",
+                    v
+                )))
+            }
+            None => return Err(Error::InvalidIndex("index is empty".to_string())),
+        };
+
+        let document_id = if schema == SchemaVersion::V4 {
+            match lines.next().and_then(summary_id) {
+                Some(ROOT_SUMMARY_ID) => None,
+                Some(id) => Some(id.to_string()),
+                None => {
+                    return Err(Error::InvalidIndex(
+                        "missing schema 4 summary line".to_string(),
+                    ))
+                }
+            }
+        } else {
+            None
+        };
+
+        let entries = lines
+            .filter(|l| !l.trim().is_empty())
+            .map(IndexEntry::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Index {
+            schema,
+            document_id,
+            entries,
+        })
+    }
+
+    /// Serializes the index in the format `parse` reads, with entries sorted by id.
+    ///
+    /// Schema 4 indexes get a `0:<id>:<count>:<size>` summary line holding the
+    /// document id, or `.` for the root index, the number of entries and the
+    /// sum of their sizes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut entries: Vec<&IndexEntry> = self.entries.iter().collect();
+        entries.sort_by(|a, b| a.id.cmp(&b.id));
+
+        let mut text = match self.schema {
+            SchemaVersion::V3 => "3\n".to_string(),
+            SchemaVersion::V4 => format!(
+                "4\n{}:{}:{}:{}\n",
+                SUMMARY_TYPE,
+                self.document_id.as_deref().unwrap_or(ROOT_SUMMARY_ID),
+                entries.len(),
+                entries.iter().map(|e| e.size).sum::<u64>()
+            ),
+        };
+        for entry in entries {
+            text.push_str(&entry.to_line());
+            text.push('\n');
+        }
+        text.into_bytes()
+    }
+}
+
+/// The id of a `0:<id>:<count>:<size>` schema 4 summary line, or `None` if
+/// `line` is not one.
+fn summary_id(line: &str) -> Option<&str> {
+    let fields: Vec<&str> = line.trim().split(':').collect();
+    match fields[..] {
+        [SUMMARY_TYPE, id, count, size]
+            if !id.is_empty() && count.parse::<u32>().is_ok() && size.parse::<u64>().is_ok() =>
+        {
+            Some(id)
+        }
+        _ => None,
+    }
+}
+
+impl IndexEntry {
+    /// Formats the entry as a `hash:type:id:subfiles:size` index line.
+    fn to_line(&self) -> String {
+        let entry_type = match self.entry_type {
+            EntryType::Index => INDEX_ENTRY_TYPE,
+            EntryType::File => FILE_ENTRY_TYPE,
+        };
+        format!(
+            "{}:{}:{}:{}:{}",
+            self.hash, entry_type, self.id, self.subfiles, self.size
+        )
+    }
+
+    /// Parses a single `hash:type:id:subfiles:size` index line.
+    fn parse(line: &str) -> Result<IndexEntry, Error> {
+        let fields: Vec<&str> = line.trim().split(':').collect();
+        if fields.len() != 5 {
+            return Err(Error::InvalidIndex(format!(
+                "expected 5 fields in index line, found {}: {:?}",
+                fields.len(),
+                line
+            )));
+        }
+
+        let entry_type = match fields[1] {
+            INDEX_ENTRY_TYPE => EntryType::Index,
+            FILE_ENTRY_TYPE => EntryType::File,
+            t => {
+                return Err(Error::InvalidIndex(format!(
+                    "unknown index entry type {:?} in line {:?}",
+                    t, line
+                )))
+            }
+        };
+        let subfiles = fields[3].parse::<u32>().map_err(|e| {
+            Error::InvalidIndex(format!("invalid subfile count in {:?}: {}", line, e))
+        })?;
+        let size = fields[4]
+            .parse::<u64>()
+            .map_err(|e| Error::InvalidIndex(format!("invalid size in {:?}: {}", line, e)))?;
+
+        Ok(IndexEntry {
+            hash: fields[0].to_string(),
+            entry_type,
+            id: fields[2].to_string(),
+            subfiles,
+            size,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASH: &str = "5bd56ea9db1b1cd8ee2aa0ecbec1e3ac1ac94dbf0b5e4ac9d5e1ecb0d3dd46ab";
+
+    #[test]
+    fn parses_schema_3() {
+        let data = format!(
+            "3\n{}:80000000:10000000-0000-0000-0000-000000000002:4:1024\n{}:0:notes.rm:0:12\n",
+            HASH, HASH
+        );
+        let index = Index::parse(data.as_bytes()).unwrap();
+        assert_eq!(index.schema, SchemaVersion::V3);
+        assert_eq!(
+            index.entries,
+            [
+                IndexEntry {
+                    hash: HASH.to_string(),
+                    entry_type: EntryType::Index,
+                    id: "10000000-0000-0000-0000-000000000002".to_string(),
+                    subfiles: 4,
+                    size: 1024,
+                },
+                IndexEntry {
+                    hash: HASH.to_string(),
+                    entry_type: EntryType::File,
+                    id: "notes.rm".to_string(),
+                    subfiles: 0,
+                    size: 12,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_schema_4_root_and_document_indexes() {
+        let root = format!(
+            "4\n0:.:1:1024\n{}:80000000:10000000-0000-0000-0000-000000000002:4:1024\n",
+            HASH
+        );
+        let index = Index::parse(root.as_bytes()).unwrap();
+        assert_eq!(index.schema, SchemaVersion::V4);
+        assert_eq!(index.document_id, None);
+        assert_eq!(index.entries.len(), 1);
+
+        let document = format!(
+            "4\n0:10000000-0000-0000-0000-000000000002:1:12\n{}:0:notes.rm:0:12\n",
+            HASH
+        );
+        let index = Index::parse(document.as_bytes()).unwrap();
+        assert_eq!(
+            index.document_id.as_deref(),
+            Some("10000000-0000-0000-0000-000000000002")
+        );
+        assert_eq!(index.entries[0].id, "notes.rm");
+    }
+
+    #[test]
+    fn writes_the_document_id_in_the_summary() {
+        let index = Index {
+            schema: SchemaVersion::V4,
+            document_id: Some("10000000-0000-0000-0000-000000000002".to_string()),
+            entries: vec![IndexEntry {
+                hash: HASH.to_string(),
+                entry_type: EntryType::File,
+                id: "notes.rm".to_string(),
+                subfiles: 0,
+                size: 12,
+            }],
+        };
+        let data = index.to_bytes();
+        assert_eq!(
+            std::str::from_utf8(&data).unwrap(),
+            format!(
+                "4\n0:10000000-0000-0000-0000-000000000002:1:12\n{}:0:notes.rm:0:12\n",
+                HASH
+            )
+        );
+        assert_eq!(Index::parse(&data).unwrap(), index);
+
+        let root = Index {
+            document_id: None,
+            ..index
+        };
+        assert!(root.to_bytes().starts_with(b"4\n0:.:1:12\n"));
+        assert_eq!(Index::parse(&root.to_bytes()).unwrap(), root);
+    }
+
+    #[test]
+    fn rejects_malformed_indexes() {
+        let invalid = [
+            "".to_string(),
+            "5\n".to_string(),
+            format!("4\n{}:0:notes.rm:0:12\n", HASH),
+            "4\n0:.:one:12\n".to_string(),
+            format!("3\n{}:0:notes.rm:0\n", HASH),
+            format!("3\n{}:7:notes.rm:0:12\n", HASH),
+            format!("3\n{}:0:notes.rm:0:-12\n", HASH),
+        ];
+        for data in &invalid {
+            assert!(
+                matches!(Index::parse(data.as_bytes()), Err(Error::InvalidIndex(_))),
+                "{:?} was accepted",
+                data
+            );
+        }
+    }
+}
@@ -0,0 +1,5 @@
+mod index;
+mod tree;
+
+pub use index::{EntryType, Index, IndexEntry, SchemaVersion};
+pub use tree::{Tree, TreeItem};
@@ -0,0 +1,267 @@
+use crate::objects::{ObjectKind, Parent, RemarkableObject, TRASH_PARENT};
+use crate::sync::index::{IndexEntry, SchemaVersion};
+use std::sync::Arc;
+
+/// A snapshot of every item stored in the cloud, as reachable from a single root hash.
+#[derive(Debug, Clone)]
+pub struct Tree {
+    /// The hash of the root index blob this tree was built from.
+    pub hash: String,
+    /// The generation of the root, used for optimistic concurrency when updating it.
+    pub generation: u64,
+    /// The schema the root index was written with.
+    pub schema: SchemaVersion,
+    /// Every item (document or collection) listed in the root index.
+    pub items: Vec<TreeItem>,
+}
+
+/// A single document or collection in the tree together with its file listing.
+#[derive(Debug, Clone)]
+pub struct TreeItem {
+    /// The id of the item.
+    pub id: String,
+    /// The hash of the item's index blob.
+    pub hash: String,
+    /// The files listed in the item's index blob.
+    pub files: Vec<IndexEntry>,
+    /// The document or collection described by the item's `.metadata` file.
+    pub object: Arc<dyn RemarkableObject>,
+}
+
+impl Tree {
+    /// Looks up an item by its id.
+    pub fn get(&self, id: &str) -> Option<&TreeItem> {
+        self.items.iter().find(|item| item.id == id)
+    }
+
+    /// Iterates over every document and collection in the tree.
+    pub fn objects(&self) -> impl Iterator<Item = &dyn RemarkableObject> {
+        self.items.iter().map(|item| item.object.as_ref())
+    }
+
+    /// Lists the items directly inside `parent`, sorted by name.
+    pub fn children(&self, parent: Parent) -> Vec<&TreeItem> {
+        let mut children: Vec<&TreeItem> = self
+            .items
+            .iter()
+            .filter(|item| item.object.parent() == parent)
+            .collect();
+        children.sort_by(|a, b| {
+            a.object
+                .name()
+                .cmp(b.object.name())
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        children
+    }
+}
+
+/// Splits a path into the container it starts from and its non-empty components.
+///
+/// A leading `trash` component selects the trash instead of the root.
+fn split_path(path: &str) -> (Parent, Vec<&str>) {
+    let mut components: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+    if components.first() == Some(&TRASH_PARENT) {
+        components.remove(0);
+        (Parent::Trash, components)
+    } else {
+        (Parent::Root, components)
+    }
+}
+
+impl Tree {
+    /// Resolves a slash-separated path, as shown on the tablet, to an item.
+    ///
+    /// Paths start at the root of the library, so `/Books/Fiction/Dune` and
+    /// `Books/Fiction/Dune` are the same. Paths starting with `/trash` are
+    /// resolved inside the trash instead, which means a top-level collection
+    /// named `trash` can only be reached by id.
+    ///
+    /// Every component but the last must name a collection. When several
+    /// siblings share a name, the one with the lowest id is picked so the same
+    /// tree always resolves a path to the same item.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the item.
+    ///
+    /// # Returns
+    ///
+    /// The item at `path`, or `None` if there is no such item or the path
+    /// names the root or the trash itself.
+    pub fn resolve(&self, path: &str) -> Option<&TreeItem> {
+        let (start, mut components) = split_path(path);
+        let name = components.pop()?;
+        let parent = self.walk(start, &components)?;
+        self.child_by_name(parent, name, false)
+    }
+
+    /// Resolves a slash-separated path to a container that items can live in.
+    ///
+    /// This accepts everything `resolve` does when the path names a collection,
+    /// plus `/` for the root and `/trash` for the trash.
+    pub fn resolve_parent(&self, path: &str) -> Option<Parent> {
+        let (start, components) = split_path(path);
+        self.walk(start, &components)
+    }
+
+    /// Builds the full path of the item with the given id, as `resolve` reads it.
+    ///
+    /// Items in the trash get a `/trash` prefix. Items whose parent is missing
+    /// from the tree are treated as if they were at the root.
+    ///
+    /// The path is built from names alone, so when the item or one of its
+    /// collections shares its name with a sibling, `resolve` may lead from the
+    /// path to that sibling instead. Keep the id to find the item again.
+    ///
+    /// # Returns
+    ///
+    /// The path of the item, or `None` if there is no item with that id or its
+    /// parents form a cycle.
+    pub fn path_of(&self, id: &str) -> Option<String> {
+        let mut item = self.get(id)?;
+        let mut names = vec![item.object.name()];
+        let prefix = loop {
+            match item.object.parent() {
+                Parent::Root => break "",
+                Parent::Trash => break "/trash",
+                Parent::Collection(parent_id) => {
+                    if names.len() > self.items.len() {
+                        log::warn!("Parents of {} form a cycle", id);
+                        return None;
+                    }
+                    match self.get(&parent_id.to_string()) {
+                        Some(parent) => {
+                            names.push(parent.object.name());
+                            item = parent;
+                        }
+                        None => {
+                            log::warn!("Parent {} of {} is missing", parent_id, item.id);
+                            break "";
+                        }
+                    }
+                }
+            }
+        };
+        names.reverse();
+        Some(format!("{}/{}", prefix, names.join("/")))
+    }
+
+    /// Follows `components` from `start`, each of which must name a collection.
+    fn walk(&self, start: Parent, components: &[&str]) -> Option<Parent> {
+        components.iter().try_fold(start, |parent, name| {
+            self.child_by_name(parent, name, true)
+                .map(|item| Parent::Collection(item.object.id()))
+        })
+    }
+
+    /// Finds the child of `parent` named `name`, picking the lowest id among duplicates.
+    fn child_by_name(
+        &self,
+        parent: Parent,
+        name: &str,
+        collections_only: bool,
+    ) -> Option<&TreeItem> {
+        let candidates: Vec<&TreeItem> = self
+            .items
+            .iter()
+            .filter(|item| item.object.parent() == parent && item.object.name() == name)
+            .filter(|item| !collections_only || item.object.kind() == ObjectKind::Collection)
+            .collect();
+        if candidates.len() > 1 {
+            log::warn!(
+                "{} items named {:?} in {:?}, using the one with the lowest id",
+                candidates.len(),
+                name,
+                parent
+            );
+        }
+        candidates.into_iter().min_by(|a, b| a.id.cmp(&b.id))
+    }
+}
+
+impl TreeItem {
+    /// Looks up a file of this item by its name, e.g. `<id>.metadata`.
+    pub fn file(&self, name: &str) -> Option<&IndexEntry> {
+        self.files.iter().find(|file| file.id == name)
+    }
+
+    /// Looks up a file of this item by its extension, e.g. `metadata` for `<id>.metadata`.
+    pub fn file_with_extension(&self, extension: &str) -> Option<&IndexEntry> {
+        self.file(&format!("{}.{}", self.id, extension))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects;
+    use uuid::Uuid;
+
+    const BOOKS: &str = "10000000-0000-0000-0000-000000000001";
+    const FICTION: &str = "10000000-0000-0000-0000-000000000002";
+    const DUNE: &str = "10000000-0000-0000-0000-000000000003";
+    const DUNE_COPY: &str = "10000000-0000-0000-0000-000000000004";
+    const OLD_NOTES: &str = "10000000-0000-0000-0000-000000000005";
+
+    fn item(id: &str, name: &str, parent: &str, object_type: &str) -> TreeItem {
+        let metadata = format!(
+            r#"{{"visibleName": "{}", "parent": "{}", "type": "{}", "lastModified": "0"}}"#,
+            name, parent, object_type
+        );
+        let object =
+            objects::from_metadata(Uuid::parse_str(id).unwrap(), "", metadata.as_bytes()).unwrap();
+        TreeItem {
+            id: id.to_string(),
+            hash: String::new(),
+            files: Vec::new(),
+            object,
+        }
+    }
+
+    fn tree() -> Tree {
+        Tree {
+            hash: String::new(),
+            generation: 1,
+            schema: SchemaVersion::V3,
+            items: vec![
+                item(DUNE_COPY, "Dune", FICTION, "DocumentType"),
+                item(BOOKS, "Books", "", "CollectionType"),
+                item(FICTION, "Fiction", BOOKS, "CollectionType"),
+                item(DUNE, "Dune", FICTION, "DocumentType"),
+                item(OLD_NOTES, "Notes", "trash", "DocumentType"),
+            ],
+        }
+    }
+
+    #[test]
+    fn resolves_paths() {
+        let tree = tree();
+        assert_eq!(tree.resolve("/Books/Fiction").unwrap().id, FICTION);
+        assert_eq!(tree.resolve("Books/Fiction/").unwrap().id, FICTION);
+        assert_eq!(tree.resolve("/trash/Notes").unwrap().id, OLD_NOTES);
+        assert!(tree.resolve("/Notes").is_none());
+        assert!(tree.resolve("/").is_none());
+        assert_eq!(tree.resolve_parent("/"), Some(Parent::Root));
+        assert_eq!(tree.resolve_parent("/trash"), Some(Parent::Trash));
+        assert_eq!(
+            tree.resolve_parent("/Books"),
+            Some(Parent::Collection(Uuid::parse_str(BOOKS).unwrap()))
+        );
+        assert_eq!(tree.resolve_parent("/Books/Fiction/Dune"), None);
+    }
+
+    #[test]
+    fn resolves_duplicates_to_lowest_id() {
+        assert_eq!(tree().resolve("/Books/Fiction/Dune").unwrap().id, DUNE);
+    }
+
+    #[test]
+    fn builds_paths() {
+        let tree = tree();
+        assert_eq!(tree.path_of(DUNE_COPY).unwrap(), "/Books/Fiction/Dune");
+        assert_eq!(tree.path_of(BOOKS).unwrap(), "/Books");
+        assert_eq!(tree.path_of(OLD_NOTES).unwrap(), "/trash/Notes");
+        assert!(tree.path_of("missing").is_none());
+    }
+}
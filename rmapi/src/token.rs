@@ -0,0 +1,212 @@
+use crate::error::Error;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::fmt;
+use std::time::Duration;
+
+/// Scopes that mark an account as using the sync 1.5 protocol.
+const SYNC_15_SCOPES: [&str; 3] = ["sync:tortoise", "sync:fox", "sync:hare"];
+
+/// The sync protocol an account is on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncVersion {
+    /// The original document storage API.
+    V10,
+    /// The hash tree based protocol used by `sync/v3` and `sync/v4`.
+    V15,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[allow(non_snake_case)]
+struct Auth0Profile {
+    #[serde(default)]
+    UserID: String,
+}
+
+/// The claims of a user token.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Claims {
+    /// When the token expires, in seconds since the epoch.
+    pub exp: i64,
+    /// When the token was issued, in seconds since the epoch.
+    pub iat: i64,
+    /// The space separated scopes granted to the token.
+    #[serde(default)]
+    pub scopes: String,
+    /// The description the device registered with, e.g. `desktop-windows`.
+    #[serde(rename = "device-desc", default)]
+    pub device_desc: String,
+    /// The id the device registered with.
+    #[serde(rename = "device-id", default)]
+    pub device_id: String,
+    #[serde(rename = "auth0-profile", default)]
+    auth0_profile: Option<Auth0Profile>,
+}
+
+/// A user token together with its decoded claims.
+///
+/// The token's signature is not verified, the claims are only used to decide
+/// when to refresh the token and which protocol to speak.
+#[derive(Clone)]
+pub struct UserToken {
+    raw: String,
+    claims: Claims,
+}
+
+impl UserToken {
+    /// Decodes the claims of a user token.
+    ///
+    /// # Arguments
+    ///
+    /// * `raw` - The user token as returned by the token endpoint.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing:
+    /// - `Ok(UserToken)`: The token and its claims.
+    /// - `Err(Error)`: An error if the token is not a JWT with a readable payload.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `Error::InvalidToken` if the token does not have
+    /// three parts, or its payload is not base64 encoded JSON with the expected claims.
+    pub fn parse(raw: &str) -> Result<UserToken, Error> {
+        let raw = raw.trim();
+        let parts: Vec<&str> = raw.split('.').collect();
+        if parts.len() != 3 {
+            return Err(Error::InvalidToken(format!(
+                "expected 3 parts in token, found {}",
+                parts.len()
+            )));
+        }
+        let payload = URL_SAFE_NO_PAD
+            .decode(parts[1].trim_end_matches('='))
+            .map_err(|e| Error::InvalidToken(format!("payload is not base64: {}", e)))?;
+        let claims = serde_json::from_slice::<Claims>(&payload)
+            .map_err(|e| Error::InvalidToken(format!("invalid claims: {}", e)))?;
+        Ok(UserToken {
+            raw: raw.to_string(),
+            claims,
+        })
+    }
+
+    /// The token as sent in the `Authorization` header.
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+
+    /// All of the decoded claims.
+    pub fn claims(&self) -> &Claims {
+        &self.claims
+    }
+
+    /// When the token expires.
+    pub fn expires_at(&self) -> DateTime<Utc> {
+        DateTime::from_timestamp(self.claims.exp, 0).unwrap_or(DateTime::UNIX_EPOCH)
+    }
+
+    /// When the token was issued.
+    pub fn issued_at(&self) -> DateTime<Utc> {
+        DateTime::from_timestamp(self.claims.iat, 0).unwrap_or(DateTime::UNIX_EPOCH)
+    }
+
+    /// Whether the token expires within `margin` from now.
+    pub fn expires_within(&self, margin: Duration) -> bool {
+        let margin = chrono::Duration::from_std(margin).unwrap_or(chrono::Duration::MAX);
+        self.expires_at() - margin <= Utc::now()
+    }
+
+    /// The space separated scopes granted to the token.
+    pub fn scopes(&self) -> &str {
+        &self.claims.scopes
+    }
+
+    /// The id of the user the token belongs to, if the token carries one.
+    pub fn user_id(&self) -> Option<&str> {
+        self.claims
+            .auth0_profile
+            .as_ref()
+            .map(|profile| profile.UserID.as_str())
+            .filter(|id| !id.is_empty())
+    }
+
+    /// The description of the device the token was issued to.
+    pub fn device_description(&self) -> &str {
+        &self.claims.device_desc
+    }
+
+    /// The sync protocol the account is on, as indicated by the token's scopes.
+    pub fn sync_version(&self) -> SyncVersion {
+        let on_sync_15 = self
+            .claims
+            .scopes
+            .split_whitespace()
+            .any(|scope| SYNC_15_SCOPES.contains(&scope));
+        if on_sync_15 {
+            SyncVersion::V15
+        } else {
+            SyncVersion::V10
+        }
+    }
+}
+
+impl fmt::Debug for UserToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("UserToken")
+            .field("claims", &self.claims)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(payload: &str) -> String {
+        format!(
+            "{}.{}.signature",
+            URL_SAFE_NO_PAD.encode(r#"{"alg":"HS256","typ":"JWT"}"#),
+            URL_SAFE_NO_PAD.encode(payload)
+        )
+    }
+
+    #[test]
+    fn decodes_claims() {
+        let raw = token(
+            r#"{
+                "auth0-profile": {"UserID": "auth0|1234", "Name": "someone"},
+                "device-desc": "desktop-linux",
+                "device-id": "e9c3c1c4-56a1-4d1c-9d3b-6c6f1e4b2a90",
+                "exp": 1893456000,
+                "iat": 1893369600,
+                "scopes": "intgr screenshare hwcmail:-1 mail:-1 sync:fox",
+                "tectonic": "eu"
+            }"#,
+        );
+        let token = UserToken::parse(&raw).unwrap();
+
+        assert_eq!(token.as_str(), raw);
+        assert_eq!(token.expires_at().timestamp(), 1893456000);
+        assert_eq!(token.issued_at().timestamp(), 1893369600);
+        assert_eq!(token.user_id(), Some("auth0|1234"));
+        assert_eq!(token.device_description(), "desktop-linux");
+        assert_eq!(token.sync_version(), SyncVersion::V15);
+    }
+
+    #[test]
+    fn detects_legacy_sync() {
+        let token =
+            UserToken::parse(&token(r#"{"exp": 0, "iat": 0, "scopes": "sync:default"}"#)).unwrap();
+        assert_eq!(token.sync_version(), SyncVersion::V10);
+        assert!(token.expires_within(Duration::from_secs(0)));
+        assert_eq!(token.user_id(), None);
+    }
+
+    #[test]
+    fn rejects_malformed_tokens() {
+        assert!(UserToken::parse("not-a-jwt").is_err());
+        assert!(UserToken::parse("a.!!!.c").is_err());
+    }
+}
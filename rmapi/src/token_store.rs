@@ -0,0 +1,178 @@
+use crate::error::Error;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// The tokens a client needs to talk to the reMarkable Cloud.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Tokens {
+    /// The long lived device token obtained by registering with a one-time code.
+    pub device_token: String,
+    /// The short lived user token exchanged from the device token and sent with API requests.
+    #[serde(default)]
+    pub user_token: Option<String>,
+}
+
+impl Tokens {
+    /// Creates a set of tokens holding only a device token.
+    pub fn from_device_token(device_token: &str) -> Tokens {
+        Tokens {
+            device_token: device_token.trim().to_string(),
+            user_token: None,
+        }
+    }
+}
+
+/// Somewhere to keep tokens between runs so a client does not have to register again.
+///
+/// `Client` loads the tokens once when it is created and saves them every time
+/// the user token is refreshed.
+pub trait TokenStore: Send + Sync {
+    /// Loads the stored tokens, or `None` if nothing has been stored yet.
+    fn load(&self) -> Result<Option<Tokens>, Error>;
+
+    /// Replaces the stored tokens.
+    fn save(&self, tokens: &Tokens) -> Result<(), Error>;
+}
+
+/// A `TokenStore` backed by a file, such as `~/.cache/rmapi/auth_token`.
+///
+/// Tokens are written as JSON. A file holding just a bare device token, as
+/// written by older versions of `rmclient`, is also accepted.
+#[derive(Debug, Clone)]
+pub struct FileTokenStore {
+    path: PathBuf,
+}
+
+impl FileTokenStore {
+    /// Creates a store that keeps its tokens in the file at `path`.
+    pub fn new(path: impl Into<PathBuf>) -> FileTokenStore {
+        FileTokenStore { path: path.into() }
+    }
+
+    /// The file the tokens are kept in.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl TokenStore for FileTokenStore {
+    fn load(&self) -> Result<Option<Tokens>, Error> {
+        let contents = match fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(Error::from(e)),
+        };
+        if contents.trim().is_empty() {
+            return Ok(None);
+        }
+        match serde_json::from_str::<Tokens>(&contents) {
+            Ok(tokens) => Ok(Some(tokens)),
+            Err(_) => {
+                log::debug!("Reading {:?} as a bare device token", self.path);
+                Ok(Some(Tokens::from_device_token(&contents)))
+            }
+        }
+    }
+
+    /// Saves the tokens by writing a temporary file next to the store and
+    /// renaming it over the old one, so a crash never leaves a partial file behind.
+    fn save(&self, tokens: &Tokens) -> Result<(), Error> {
+        if let Some(parent) = self.path.parent() {
+            log::debug!("Making token store dir {:?}", parent);
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut tmp_name = self.path.file_name().unwrap_or_default().to_os_string();
+        tmp_name.push(".tmp");
+        let tmp_path = self.path.with_file_name(tmp_name);
+
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(&tmp_path)?;
+        file.write_all(&serde_json::to_vec(tokens)?)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, &self.path)?;
+
+        log::debug!("Saved tokens to {:?}", self.path);
+        Ok(())
+    }
+}
+
+/// A `TokenStore` that only keeps tokens for the lifetime of the process.
+#[derive(Debug, Default)]
+pub struct MemoryTokenStore {
+    tokens: Mutex<Option<Tokens>>,
+}
+
+impl MemoryTokenStore {
+    /// Creates a store that starts out holding `tokens`.
+    pub fn new(tokens: Tokens) -> MemoryTokenStore {
+        MemoryTokenStore {
+            tokens: Mutex::new(Some(tokens)),
+        }
+    }
+}
+
+impl TokenStore for MemoryTokenStore {
+    fn load(&self) -> Result<Option<Tokens>, Error> {
+        Ok(self
+            .tokens
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone())
+    }
+
+    fn save(&self, tokens: &Tokens) -> Result<(), Error> {
+        *self.tokens.lock().unwrap_or_else(|e| e.into_inner()) = Some(tokens.clone());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn temp_path() -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("rmapi-test-{}", Uuid::new_v4()))
+            .join("auth_token")
+    }
+
+    #[test]
+    fn file_store_round_trips_tokens() {
+        let store = FileTokenStore::new(temp_path());
+        assert_eq!(store.load().unwrap(), None);
+
+        let tokens = Tokens {
+            device_token: "device".to_string(),
+            user_token: Some("user".to_string()),
+        };
+        store.save(&tokens).unwrap();
+        assert_eq!(store.load().unwrap(), Some(tokens));
+
+        fs::remove_dir_all(store.path().parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn file_store_reads_bare_device_tokens() {
+        let store = FileTokenStore::new(temp_path());
+        fs::create_dir_all(store.path().parent().unwrap()).unwrap();
+        fs::write(store.path(), "eyJhbGciOi.device.token\n").unwrap();
+
+        assert_eq!(
+            store.load().unwrap(),
+            Some(Tokens::from_device_token("eyJhbGciOi.device.token"))
+        );
+
+        fs::remove_dir_all(store.path().parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,392 @@
+use crate::client::Client;
+use crate::endpoints::RootInfo;
+use crate::error::Error;
+use crate::hash;
+use crate::notebook::Notebook;
+use crate::objects::{Collection, Parent};
+use crate::sync::{EntryType, Index, IndexEntry, SchemaVersion};
+use chrono::Utc;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// The name the root index is uploaded under.
+const ROOT_INDEX_FILENAME: &str = "root.docSchema";
+
+/// A change staged in a `Transaction`.
+///
+/// Changes describe what to do rather than the resulting blobs, so they can be
+/// replayed on top of a newer root when another device updated it first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// Adds or replaces files of an item, creating the item if it does not exist yet.
+    ///
+    /// Each file is given by its name (such as `<id>.metadata`) and its contents.
+    PutFiles {
+        id: Uuid,
+        files: Vec<(String, Vec<u8>)>,
+    },
+    /// Moves an item into a collection, the root or the trash.
+    Move { id: Uuid, parent: Parent },
+    /// Changes the name an item is shown with.
+    Rename { id: Uuid, name: String },
+    /// Removes an item and all of its files from the tree for good.
+    Delete { id: Uuid },
+}
+
+/// A set of changes to the tree that are applied together by `Client::commit`.
+///
+/// ```no_run
+/// # async fn example(client: &rmapi::Client) -> Result<(), rmapi::Error> {
+/// use rmapi::objects::Parent;
+/// use rmapi::Transaction;
+///
+/// let mut transaction = Transaction::new();
+/// let papers = transaction.create_collection("Papers", Parent::Root)?;
+/// let tree = client.get_tree().await?;
+/// if let Some(item) = tree.resolve("/Dune") {
+///     transaction.move_to(item.object.id(), Parent::Collection(papers));
+/// }
+/// client.commit(&transaction).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Transaction {
+    changes: Vec<Change>,
+}
+
+impl Transaction {
+    /// Creates an empty transaction.
+    pub fn new() -> Transaction {
+        Transaction::default()
+    }
+
+    /// The changes staged so far, in the order they are applied.
+    pub fn changes(&self) -> &[Change] {
+        &self.changes
+    }
+
+    /// Whether no changes have been staged.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Stages an arbitrary change.
+    pub fn push(&mut self, change: Change) {
+        self.changes.push(change);
+    }
+
+    /// Adds or replaces files of the item with the given id, creating it if needed.
+    pub fn put_files(&mut self, id: Uuid, files: Vec<(String, Vec<u8>)>) {
+        self.push(Change::PutFiles { id, files });
+    }
+
+    /// Creates a collection named `name` inside `parent`.
+    ///
+    /// # Returns
+    ///
+    /// The id of the new collection, which can be used by later changes in the
+    /// same transaction.
+    pub fn create_collection(&mut self, name: &str, parent: Parent) -> Result<Uuid, Error> {
+        let collection = Collection::new(name, parent);
+        let id = collection.id;
+        self.put_files(
+            id,
+            vec![
+                (format!("{}.metadata", id), collection.to_metadata()?),
+                (format!("{}.content", id), b"{}".to_vec()),
+            ],
+        );
+        Ok(id)
+    }
+
+    /// Creates a notebook with the pages built in `notebook`.
+    ///
+    /// See `Notebook::to_archive` for the files that are written.
+    ///
+    /// # Returns
+    ///
+    /// The id of the new notebook.
+    pub fn create_notebook(&mut self, notebook: &Notebook) -> Result<Uuid, Error> {
+        let archive = notebook.to_archive()?;
+        self.put_files(archive.id, archive.files);
+        Ok(archive.id)
+    }
+
+    /// Moves the item with the given id into `parent`.
+    ///
+    /// Committing fails with `Error::InvalidMove` if `parent` is the item
+    /// itself or a collection inside it, as staged by earlier changes.
+    pub fn move_to(&mut self, id: Uuid, parent: Parent) {
+        self.push(Change::Move { id, parent });
+    }
+
+    /// Renames the item with the given id.
+    pub fn rename(&mut self, id: Uuid, name: &str) {
+        self.push(Change::Rename {
+            id,
+            name: name.to_string(),
+        });
+    }
+
+    /// Moves the item with the given id to the trash, like deleting it on the tablet does.
+    pub fn trash(&mut self, id: Uuid) {
+        self.move_to(id, Parent::Trash);
+    }
+
+    /// Removes the item with the given id from the tree for good.
+    pub fn delete(&mut self, id: Uuid) {
+        self.push(Change::Delete { id });
+    }
+}
+
+/// A blob that has to be uploaded before the root can be updated.
+pub(crate) struct Upload {
+    pub hash: String,
+    pub filename: String,
+    pub blob: Vec<u8>,
+}
+
+/// The result of applying a transaction to a root: the new root hash and every blob it needs.
+pub(crate) struct Staged {
+    pub hash: String,
+    pub uploads: Vec<Upload>,
+}
+
+/// Applies `changes` on top of `root`, building every new index blob along the way.
+///
+/// Unchanged documents keep their existing index blobs, so only the files,
+/// document indexes and root index touched by the changes are uploaded.
+pub(crate) async fn stage(
+    client: &Client,
+    root: &RootInfo,
+    changes: &[Change],
+) -> Result<Staged, Error> {
+    let root_index = if root.hash.is_empty() {
+        let schema = match root.schema_version {
+            Some(3) => SchemaVersion::V3,
+            _ => SchemaVersion::V4,
+        };
+        Index {
+            schema,
+            document_id: None,
+            entries: Vec::new(),
+        }
+    } else {
+        client.get_index(&root.hash).await?
+    };
+    let mut stager = Stager {
+        client,
+        root: root_index,
+        documents: HashMap::new(),
+        blobs: HashMap::new(),
+        uploads: Vec::new(),
+    };
+    for change in changes {
+        stager.apply(change).await?;
+    }
+    stager.finish()
+}
+
+struct Stager<'a> {
+    client: &'a Client,
+    root: Index,
+    /// The indexes of the documents changed so far, by id.
+    documents: HashMap<String, Index>,
+    /// The contents of the files staged so far, by hash, so later changes can read them.
+    blobs: HashMap<String, Vec<u8>>,
+    uploads: Vec<Upload>,
+}
+
+impl Stager<'_> {
+    async fn apply(&mut self, change: &Change) -> Result<(), Error> {
+        log::debug!("Staging {:?}", change);
+        match *change {
+            Change::PutFiles { ref id, ref files } => {
+                let id = id.to_string();
+                let document = self.document(&id, true).await?;
+                let mut staged = Vec::with_capacity(files.len());
+                for (name, contents) in files {
+                    let entry = hash::file_entry(name, contents);
+                    match document.entries.iter_mut().find(|e| e.id == *name) {
+                        Some(existing) => *existing = entry.clone(),
+                        None => document.entries.push(entry.clone()),
+                    }
+                    staged.push((entry, contents));
+                }
+                for (entry, contents) in staged {
+                    self.stage_blob(entry.hash, entry.id, contents.clone());
+                }
+            }
+            Change::Move { ref id, parent } => {
+                self.check_move(*id, parent).await?;
+                self.update_metadata(&id.to_string(), "parent", Value::String(parent.to_string()))
+                    .await?;
+            }
+            Change::Rename { ref id, ref name } => {
+                self.update_metadata(&id.to_string(), "visibleName", Value::String(name.clone()))
+                    .await?;
+            }
+            Change::Delete { ref id } => {
+                let id = id.to_string();
+                let before = self.root.entries.len();
+                self.root.entries.retain(|e| e.id != id);
+                let staged = self.documents.remove(&id).is_some();
+                if self.root.entries.len() == before && !staged {
+                    return Err(Error::PathNotFound(id));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The index of the document with the given id, loading it on first use.
+    ///
+    /// If the document does not exist, an empty index is started when `create`
+    /// is set and `Error::PathNotFound` is returned otherwise.
+    async fn document(&mut self, id: &str, create: bool) -> Result<&mut Index, Error> {
+        if !self.documents.contains_key(id) {
+            let index = match self.root.entries.iter().find(|e| e.id == id) {
+                Some(entry) => self.client.get_index(&entry.hash).await?,
+                None if create => Index {
+                    schema: self.root.schema,
+                    document_id: Some(id.to_string()),
+                    entries: Vec::new(),
+                },
+                None => return Err(Error::PathNotFound(id.to_string())),
+            };
+            self.documents.insert(id.to_string(), index);
+        }
+        Ok(self
+            .documents
+            .get_mut(id)
+            .expect("document was just loaded"))
+    }
+
+    /// Checks that moving the item with the given id into `parent` does not
+    /// put it inside itself, following the parents as staged so far.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `Error::InvalidMove` if `parent` is the item
+    /// itself or a collection inside it.
+    async fn check_move(&self, id: Uuid, parent: Parent) -> Result<(), Error> {
+        let invalid = || Error::InvalidMove {
+            id: id.to_string(),
+            parent: parent.to_string(),
+        };
+        let mut current = parent;
+        // Every step goes up a level, so a longer walk means the parents already form a cycle.
+        for _ in 0..=self.root.entries.len() + self.documents.len() {
+            let Parent::Collection(collection) = current else {
+                return Ok(());
+            };
+            if collection == id {
+                return Err(invalid());
+            }
+            let collection = collection.to_string();
+            let exists = self.documents.contains_key(&collection)
+                || self.root.entries.iter().any(|e| e.id == collection);
+            if !exists {
+                // Items whose parent is missing are shown in the root.
+                return Ok(());
+            }
+            let metadata = self.metadata(&collection).await?;
+            current = Parent::parse(metadata.get("parent").and_then(Value::as_str).unwrap_or(""));
+        }
+        Err(invalid())
+    }
+
+    /// Reads an item's `.metadata` file as staged so far, without staging its index.
+    async fn metadata(&self, id: &str) -> Result<Map<String, Value>, Error> {
+        let name = format!("{}.metadata", id);
+        let find = |index: &Index| {
+            index
+                .entries
+                .iter()
+                .find(|e| e.id == name)
+                .map(|e| e.hash.clone())
+        };
+        let hash = match self.documents.get(id) {
+            Some(document) => find(document),
+            None => {
+                let entry = self
+                    .root
+                    .entries
+                    .iter()
+                    .find(|e| e.id == id)
+                    .ok_or_else(|| Error::PathNotFound(id.to_string()))?;
+                find(&self.client.get_index(&entry.hash).await?)
+            }
+        }
+        .ok_or_else(|| Error::InvalidMetadata(format!("{} has no .metadata file", id)))?;
+        let contents = match self.blobs.get(&hash) {
+            Some(contents) => contents.clone(),
+            None => self.client.get_blob(&hash).await?,
+        };
+        Ok(serde_json::from_slice(&contents)?)
+    }
+
+    /// Sets a field of an item's `.metadata` file, keeping every other field as it is.
+    async fn update_metadata(&mut self, id: &str, field: &str, value: Value) -> Result<(), Error> {
+        let name = format!("{}.metadata", id);
+        self.document(id, false).await?;
+        let mut metadata = self.metadata(id).await?;
+        metadata.insert(field.to_string(), value);
+        metadata.insert(
+            "lastModified".to_string(),
+            Value::String(Utc::now().timestamp_millis().to_string()),
+        );
+        let contents = serde_json::to_vec(&metadata)?;
+
+        let entry = hash::file_entry(&name, &contents);
+        let document = self.document(id, false).await?;
+        if let Some(existing) = document.entries.iter_mut().find(|e| e.id == name) {
+            *existing = entry.clone();
+        }
+        self.stage_blob(entry.hash, entry.id, contents);
+        Ok(())
+    }
+
+    fn stage_blob(&mut self, hash: String, filename: String, blob: Vec<u8>) {
+        if self.blobs.contains_key(&hash) {
+            return;
+        }
+        self.blobs.insert(hash.clone(), blob.clone());
+        self.uploads.push(Upload {
+            hash,
+            filename,
+            blob,
+        });
+    }
+
+    /// Builds the index of every changed document and the new root index.
+    fn finish(mut self) -> Result<Staged, Error> {
+        let documents = std::mem::take(&mut self.documents);
+        for (id, document) in documents {
+            let subfiles = document.entries.len() as u32;
+            let size = document.entries.iter().map(|e| e.size).sum();
+            let (hash, blob) = hash::build_index(document.schema, Some(&id), document.entries)?;
+            let entry = IndexEntry {
+                hash: hash.clone(),
+                entry_type: EntryType::Index,
+                id: id.clone(),
+                subfiles,
+                size,
+            };
+            match self.root.entries.iter_mut().find(|e| e.id == id) {
+                Some(existing) => *existing = entry,
+                None => self.root.entries.push(entry),
+            }
+            self.stage_blob(hash, format!("{}.docSchema", id), blob);
+        }
+
+        let (hash, blob) = hash::build_index(self.root.schema, None, self.root.entries.clone())?;
+        self.stage_blob(hash.clone(), ROOT_INDEX_FILENAME.to_string(), blob);
+        Ok(Staged {
+            hash,
+            uploads: self.uploads,
+        })
+    }
+}
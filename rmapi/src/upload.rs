@@ -0,0 +1,233 @@
+use crate::error::Error;
+use crate::file_type::{self, FileType};
+use crate::objects::Parent;
+use bytes::Bytes;
+use futures_util::stream::{self, Stream, StreamExt};
+use std::fmt;
+use std::io::Cursor;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio_util::codec::{BytesCodec, FramedRead};
+
+/// How much of an in-memory document is handed to the HTTP client at a time,
+/// which is also how often progress is reported for it.
+const BYTES_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Called with the progress of an upload every time a chunk of the document is sent.
+pub type ProgressCallback = Arc<dyn Fn(UploadProgress) + Send + Sync>;
+
+/// How far along an upload is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UploadProgress {
+    /// The number of bytes sent so far.
+    pub sent: u64,
+    /// The size of the document, if known.
+    pub total: Option<u64>,
+}
+
+/// Where the contents of an uploaded document come from.
+pub enum UploadSource {
+    /// A document held in memory.
+    Bytes(Bytes),
+    /// A document read from a stream, such as a file or a network response.
+    Reader {
+        reader: Box<dyn AsyncRead + Send + Unpin>,
+        /// The size of the document if known up front, sent as its `Content-Length`.
+        length: Option<u64>,
+    },
+}
+
+impl UploadSource {
+    /// Uploads a document held in memory.
+    pub fn from_bytes(bytes: impl Into<Bytes>) -> UploadSource {
+        UploadSource::Bytes(bytes.into())
+    }
+
+    /// Uploads a document read from `reader`, whose size may be known up front.
+    pub fn from_reader(
+        reader: impl AsyncRead + Send + Unpin + 'static,
+        length: Option<u64>,
+    ) -> UploadSource {
+        UploadSource::Reader {
+            reader: Box::new(reader),
+            length,
+        }
+    }
+
+    /// The size of the document, if known.
+    pub fn len(&self) -> Option<u64> {
+        match *self {
+            UploadSource::Bytes(ref bytes) => Some(bytes.len() as u64),
+            UploadSource::Reader { length, .. } => length,
+        }
+    }
+
+    /// Whether the document is known to be empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == Some(0)
+    }
+
+    /// Works out the type of the document from its first bytes and `name`.
+    ///
+    /// Readers are read just far enough to see the magic bytes, and those bytes
+    /// are put back in front of the rest of the stream.
+    pub(crate) async fn detect(
+        self,
+        name: Option<&Path>,
+    ) -> Result<(FileType, UploadSource), Error> {
+        match self {
+            UploadSource::Bytes(bytes) => {
+                let head = &bytes[..bytes.len().min(file_type::DETECT_LEN)];
+                Ok((FileType::detect(head, name)?, UploadSource::Bytes(bytes)))
+            }
+            UploadSource::Reader { mut reader, length } => {
+                let mut head = Vec::with_capacity(file_type::DETECT_LEN);
+                (&mut reader)
+                    .take(file_type::DETECT_LEN as u64)
+                    .read_to_end(&mut head)
+                    .await?;
+                let file_type = FileType::detect(&head, name)?;
+                let reader = Cursor::new(head).chain(reader);
+                Ok((file_type, UploadSource::from_reader(reader, length)))
+            }
+        }
+    }
+
+    /// Turns the document into a stream of chunks, reporting each chunk to `progress`.
+    pub(crate) fn into_stream(
+        self,
+        progress: Option<ProgressCallback>,
+    ) -> impl Stream<Item = Result<Bytes, std::io::Error>> + Send + 'static {
+        let total = self.len();
+        let chunks = match self {
+            UploadSource::Bytes(bytes) => {
+                let chunks: Vec<Result<Bytes, std::io::Error>> = (0..bytes.len())
+                    .step_by(BYTES_CHUNK_SIZE)
+                    .map(|start| Ok(bytes.slice(start..bytes.len().min(start + BYTES_CHUNK_SIZE))))
+                    .collect();
+                stream::iter(chunks).boxed()
+            }
+            UploadSource::Reader { reader, .. } => FramedRead::new(reader, BytesCodec::new())
+                .map(|chunk| chunk.map(|chunk| chunk.freeze()))
+                .boxed(),
+        };
+        let sent = AtomicU64::new(0);
+        chunks.inspect(move |chunk| {
+            if let (Ok(chunk), Some(progress)) = (chunk, &progress) {
+                let sent =
+                    sent.fetch_add(chunk.len() as u64, Ordering::Relaxed) + chunk.len() as u64;
+                progress(UploadProgress { sent, total });
+            }
+        })
+    }
+}
+
+impl fmt::Debug for UploadSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            UploadSource::Bytes(ref bytes) => f.debug_tuple("Bytes").field(&bytes.len()).finish(),
+            UploadSource::Reader { length, .. } => {
+                f.debug_struct("Reader").field("length", &length).finish()
+            }
+        }
+    }
+}
+
+/// Where an uploaded document should land.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Destination {
+    /// The root, the trash or a collection given by its id.
+    Parent(Parent),
+    /// The collection at a slash-separated path, resolved against the current tree.
+    Path(String),
+}
+
+/// How a document uploaded through `Client::upload_file` should appear on the tablet.
+///
+/// ```no_run
+/// # async fn example(client: &rmapi::Client) -> Result<(), rmapi::Error> {
+/// use rmapi::UploadOptions;
+///
+/// let options = UploadOptions::new()
+///     .with_visible_name("Weekly report")
+///     .with_parent_path("/Reports")
+///     .with_tag("work");
+/// let id = client
+///     .upload_file(std::path::Path::new("report.pdf"), &options)
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct UploadOptions {
+    /// The name shown on the tablet. Defaults to the name of the file without its extension.
+    pub visible_name: Option<String>,
+    /// The collection to upload into. Defaults to the root.
+    pub destination: Option<Destination>,
+    /// Tags to attach to the document.
+    pub tags: Vec<String>,
+    /// The type of the document. Detected from its contents and name when not set.
+    pub file_type: Option<FileType>,
+    /// Called as the document is sent.
+    pub progress: Option<ProgressCallback>,
+}
+
+impl UploadOptions {
+    /// Creates options that upload to the root under the file's own name.
+    pub fn new() -> UploadOptions {
+        UploadOptions::default()
+    }
+
+    /// Sets the name the document is shown with.
+    pub fn with_visible_name(mut self, visible_name: &str) -> UploadOptions {
+        self.visible_name = Some(visible_name.to_string());
+        self
+    }
+
+    /// Uploads into the root, the trash or the collection with the given id.
+    pub fn with_parent(mut self, parent: Parent) -> UploadOptions {
+        self.destination = Some(Destination::Parent(parent));
+        self
+    }
+
+    /// Uploads into the collection at `path`.
+    pub fn with_parent_path(mut self, path: &str) -> UploadOptions {
+        self.destination = Some(Destination::Path(path.to_string()));
+        self
+    }
+
+    /// Adds a tag to the document.
+    pub fn with_tag(mut self, tag: &str) -> UploadOptions {
+        self.tags.push(tag.to_string());
+        self
+    }
+
+    /// Sets the type of the document instead of detecting it.
+    pub fn with_file_type(mut self, file_type: FileType) -> UploadOptions {
+        self.file_type = Some(file_type);
+        self
+    }
+
+    /// Calls `progress` every time a chunk of the document is sent.
+    pub fn with_progress(
+        mut self,
+        progress: impl Fn(UploadProgress) + Send + Sync + 'static,
+    ) -> UploadOptions {
+        self.progress = Some(Arc::new(progress));
+        self
+    }
+}
+
+impl fmt::Debug for UploadOptions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("UploadOptions")
+            .field("visible_name", &self.visible_name)
+            .field("destination", &self.destination)
+            .field("tags", &self.tags)
+            .field("file_type", &self.file_type)
+            .field("progress", &self.progress.is_some())
+            .finish()
+    }
+}
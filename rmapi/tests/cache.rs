@@ -0,0 +1,33 @@
+mod common;
+
+use rmapi::hash::sha256_hex;
+use rmapi::{BlobCache, Client, ClientConfig};
+use wiremock::MockServer;
+
+#[tokio::test]
+async fn downloads_each_blob_once() {
+    let server = MockServer::start().await;
+    common::mock_token_exchange(&server).await;
+    let hash = sha256_hex(b"contents");
+    common::mock_blob(&server, &hash, "contents").await;
+    let dir = std::env::temp_dir().join(format!("rmapi-test-{}", uuid::Uuid::new_v4()));
+    let client = Client::builder()
+        .config(ClientConfig::local(&server.uri()))
+        .cache(BlobCache::new(&dir))
+        .from_token("device-token")
+        .await
+        .unwrap();
+
+    assert_eq!(client.get_blob(&hash).await.unwrap(), b"contents");
+    assert_eq!(client.get_blob(&hash).await.unwrap(), b"contents");
+    let downloads = server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .filter(|r| r.url.path().starts_with("/sync/v3/files/"))
+        .count();
+    assert_eq!(downloads, 1);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,128 @@
+#![allow(dead_code)]
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use rmapi::hash;
+use rmapi::sync::{EntryType, IndexEntry, SchemaVersion};
+use rmapi::{Client, ClientConfig};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// Builds an unsigned user token for a sync 1.5 account that expires far in the future.
+pub fn user_token() -> String {
+    format!(
+        "{}.{}.signature",
+        URL_SAFE_NO_PAD.encode(r#"{"alg":"HS256","typ":"JWT"}"#),
+        URL_SAFE_NO_PAD.encode(
+            r#"{"exp": 4102444800, "iat": 1700000000, "scopes": "sync:fox",
+                "device-desc": "desktop-linux", "auth0-profile": {"UserID": "auth0|test"}}"#
+        )
+    )
+}
+
+/// Makes the mock server hand out user tokens.
+pub async fn mock_token_exchange(server: &MockServer) {
+    Mock::given(method("POST"))
+        .and(path("/token/json/2/user/new"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(user_token()))
+        .mount(server)
+        .await;
+}
+
+/// Creates a client that talks to the mock server.
+pub async fn client(server: &MockServer) -> Client {
+    Client::builder()
+        .config(ClientConfig::local(&server.uri()))
+        .from_token("device-token")
+        .await
+        .unwrap()
+}
+
+/// Serves `body` as the blob with the given hash.
+pub async fn mock_blob(server: &MockServer, hash: &str, body: &str) {
+    Mock::given(method("GET"))
+        .and(path(format!("/sync/v3/files/{}", hash)))
+        .respond_with(ResponseTemplate::new(200).set_body_string(body))
+        .mount(server)
+        .await;
+}
+
+/// Serves a root with the given hash and generation.
+pub async fn mock_root(server: &MockServer, hash: &str, generation: u64) {
+    Mock::given(method("GET"))
+        .and(path("/sync/v4/root"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "hash": hash,
+            "generation": generation,
+            "schemaVersion": 3,
+        })))
+        .mount(server)
+        .await;
+}
+
+/// Serves `contents` under its own hash and returns the hash.
+pub async fn mock_file(server: &MockServer, contents: &str) -> String {
+    let hash = hash::sha256_hex(contents.as_bytes());
+    mock_blob(server, &hash, contents).await;
+    hash
+}
+
+/// Serves a schema 3 index listing `entries` and returns the root index entry pointing at it.
+pub async fn mock_index(server: &MockServer, id: &str, entries: Vec<IndexEntry>) -> IndexEntry {
+    let subfiles = entries.len() as u32;
+    let size = entries.iter().map(|e| e.size).sum();
+    let (hash, blob) = hash::build_index(SchemaVersion::V3, Some(id), entries).unwrap();
+    mock_blob(server, &hash, std::str::from_utf8(&blob).unwrap()).await;
+    IndexEntry {
+        hash,
+        entry_type: EntryType::Index,
+        id: id.to_string(),
+        subfiles,
+        size,
+    }
+}
+
+/// Serves a document or collection with the given metadata and extra files,
+/// and returns the root index entry pointing at it.
+pub async fn mock_item(
+    server: &MockServer,
+    id: &str,
+    metadata: &str,
+    files: &[(&str, &str)],
+) -> IndexEntry {
+    let mut entries = vec![hash::file_entry(
+        &format!("{}.metadata", id),
+        metadata.as_bytes(),
+    )];
+    mock_file(server, metadata).await;
+    for (extension, contents) in files {
+        entries.push(hash::file_entry(
+            &format!("{}.{}", id, extension),
+            contents.as_bytes(),
+        ));
+        mock_file(server, contents).await;
+    }
+    mock_index(server, id, entries).await
+}
+
+/// Serves a small library with a `Books` collection holding a `Dune` document,
+/// and returns the hash of its root index.
+pub async fn mock_library(server: &MockServer) -> String {
+    let books = mock_item(
+        server,
+        "10000000-0000-0000-0000-000000000001",
+        r#"{"visibleName": "Books", "parent": "", "type": "CollectionType", "lastModified": "1700000000000"}"#,
+        &[("content", "{}")],
+    )
+    .await;
+    let dune = mock_item(
+        server,
+        "10000000-0000-0000-0000-000000000002",
+        r#"{"visibleName": "Dune", "parent": "10000000-0000-0000-0000-000000000001", "type": "DocumentType", "lastModified": "1700000000000"}"#,
+        &[("content", r#"{"fileType": "pdf"}"#), ("pdf", "%PDF-1.7")],
+    )
+    .await;
+    let root = mock_index(server, "root", vec![books, dune]).await;
+    mock_root(server, &root.hash, 7).await;
+    root.hash
+}
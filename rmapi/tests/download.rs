@@ -0,0 +1,96 @@
+mod common;
+
+use rmapi::objects::Parent;
+use rmapi::{Archive, Error, FileType};
+use wiremock::matchers::{method, path, path_regex};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const BOOKS: &str = "10000000-0000-0000-0000-000000000001";
+const DUNE: &str = "10000000-0000-0000-0000-000000000002";
+
+#[tokio::test]
+async fn downloads_the_source_file() {
+    let server = MockServer::start().await;
+    common::mock_token_exchange(&server).await;
+    common::mock_library(&server).await;
+    let client = common::client(&server).await;
+
+    let tree = client.get_tree().await.unwrap();
+    let (file_type, contents) = client.download(tree.get(DUNE).unwrap()).await.unwrap();
+    assert_eq!(file_type, FileType::Pdf);
+    assert_eq!(contents, b"%PDF-1.7");
+
+    assert!(matches!(
+        client.download(tree.get(BOOKS).unwrap()).await,
+        Err(Error::NoSourceFile(id)) if id == BOOKS
+    ));
+}
+
+#[tokio::test]
+async fn exports_and_imports_archives() {
+    let server = MockServer::start().await;
+    common::mock_token_exchange(&server).await;
+    common::mock_library(&server).await;
+    Mock::given(method("PUT"))
+        .and(path_regex("^/sync/v3/files/[0-9a-f]{64}$"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&server)
+        .await;
+    Mock::given(method("PUT"))
+        .and(path("/sync/v3/root"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(serde_json::json!({"hash": "newroot", "generation": 8})),
+        )
+        .mount(&server)
+        .await;
+    let client = common::client(&server).await;
+
+    let tree = client.get_tree().await.unwrap();
+    let archive = client
+        .export_archive(tree.get(DUNE).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(archive.files.len(), 3);
+    assert_eq!(
+        archive.file(&format!("{}.pdf", DUNE)),
+        Some(&b"%PDF-1.7"[..])
+    );
+
+    let archive = Archive::from_zip(&archive.to_zip().unwrap()).unwrap();
+    let id = client.import_archive(archive, Parent::Root).await.unwrap();
+    assert_ne!(id.to_string(), DUNE);
+
+    let uploads = server.received_requests().await.unwrap();
+    let metadata = uploads
+        .iter()
+        .find(|r| {
+            r.headers
+                .get("rm-filename")
+                .is_some_and(|f| *f == format!("{}.metadata", id).as_str())
+        })
+        .unwrap();
+    let metadata: serde_json::Value = serde_json::from_slice(&metadata.body).unwrap();
+    assert_eq!(metadata["visibleName"], "Dune");
+    assert_eq!(metadata["parent"], "");
+}
+
+#[tokio::test]
+async fn gets_highlights_without_the_source_file() {
+    let server = MockServer::start().await;
+    common::mock_token_exchange(&server).await;
+    common::mock_library(&server).await;
+    let client = common::client(&server).await;
+
+    let tree = client.get_tree().await.unwrap();
+    let highlights = client
+        .get_highlights(tree.get(DUNE).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(highlights.title, "Dune");
+    assert!(highlights.excerpts.is_empty());
+
+    let pdf = rmapi::hash::sha256_hex(b"%PDF-1.7");
+    let requests = server.received_requests().await.unwrap();
+    assert!(!requests.iter().any(|r| r.url.path().ends_with(&pdf)));
+}
@@ -0,0 +1,145 @@
+mod common;
+
+use rmapi::{Client, ClientConfig, Error, RetryPolicy, StatusCode};
+use std::time::Duration;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+async fn client(server: &MockServer) -> Client {
+    Client::builder()
+        .config(ClientConfig::local(&server.uri()))
+        .retry(RetryPolicy::none())
+        .from_token("device-token")
+        .await
+        .unwrap()
+}
+
+async fn mock_blob_status(server: &MockServer, status: u16, body: &str) {
+    Mock::given(method("GET"))
+        .and(path("/sync/v3/files/blobhash"))
+        .respond_with(ResponseTemplate::new(status).set_body_string(body))
+        .mount(server)
+        .await;
+}
+
+#[tokio::test]
+async fn rejected_code_is_invalid_code() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/token/json/2/device/new"))
+        .respond_with(ResponseTemplate::new(400).set_body_string("invalid code"))
+        .mount(&server)
+        .await;
+
+    let result = Client::builder()
+        .config(ClientConfig::local(&server.uri()))
+        .register("abcdefgh")
+        .await;
+    match result {
+        Err(Error::InvalidCode(res)) => {
+            assert_eq!(res.status, StatusCode::BAD_REQUEST);
+            assert_eq!(res.body, "invalid code");
+        }
+        other => panic!("expected InvalidCode, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[tokio::test]
+async fn rejected_device_token_is_token_expired() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/token/json/2/user/new"))
+        .respond_with(ResponseTemplate::new(401).set_body_string("token revoked"))
+        .mount(&server)
+        .await;
+
+    let result = Client::builder()
+        .config(ClientConfig::local(&server.uri()))
+        .from_token("device-token")
+        .await;
+    let err = result.err().unwrap();
+    assert!(err.is_unauthorized());
+    assert_eq!(err.status(), Some(StatusCode::UNAUTHORIZED));
+    assert_eq!(err.response().unwrap().body, "token revoked");
+}
+
+#[tokio::test]
+async fn statuses_map_to_variants() {
+    for (status, check) in [
+        (
+            404,
+            (|e| matches!(e, Error::NotFound(_))) as fn(&Error) -> bool,
+        ),
+        (409, |e| matches!(e, Error::GenerationConflict(_))),
+        (412, |e| matches!(e, Error::GenerationConflict(_))),
+        (413, |e| matches!(e, Error::PayloadTooLarge(_))),
+        (415, |e| matches!(e, Error::UnsupportedFileType { .. })),
+        (500, |e| matches!(e, Error::Api(_))),
+    ] {
+        let server = MockServer::start().await;
+        common::mock_token_exchange(&server).await;
+        mock_blob_status(&server, status, "details").await;
+        let client = client(&server).await;
+
+        let err = client.get_blob("blobhash").await.unwrap_err();
+        assert!(check(&err), "{} mapped to {:?}", status, err);
+        assert_eq!(err.status().unwrap().as_u16(), status);
+        assert_eq!(err.response().unwrap().body, "details");
+    }
+}
+
+#[tokio::test]
+async fn rate_limit_keeps_retry_after() {
+    let server = MockServer::start().await;
+    common::mock_token_exchange(&server).await;
+    Mock::given(method("GET"))
+        .and(path("/sync/v3/files/blobhash"))
+        .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "12"))
+        .mount(&server)
+        .await;
+    let client = client(&server).await;
+
+    match client.get_blob("blobhash").await {
+        Err(Error::RateLimited(res)) => {
+            assert_eq!(res.retry_after, Some(Duration::from_secs(12)))
+        }
+        other => panic!("expected RateLimited, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn unexpected_body_is_schema_parse() {
+    let server = MockServer::start().await;
+    common::mock_token_exchange(&server).await;
+    Mock::given(method("GET"))
+        .and(path("/sync/v4/root"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("<html>maintenance</html>"))
+        .mount(&server)
+        .await;
+    let client = client(&server).await;
+
+    match client.sync_root().await {
+        Err(Error::SchemaParse { response, .. }) => {
+            assert_eq!(response.status, StatusCode::OK);
+            assert_eq!(response.body, "<html>maintenance</html>");
+        }
+        other => panic!("expected SchemaParse, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn tampered_blob_is_hash_mismatch() {
+    let server = MockServer::start().await;
+    common::mock_token_exchange(&server).await;
+    let hash = rmapi::hash::sha256_hex(b"contents");
+    common::mock_blob(&server, &hash, "tampered").await;
+    let client = client(&server).await;
+
+    match client.get_blob(&hash).await {
+        Err(Error::HashMismatch { expected, actual }) => {
+            assert_eq!(expected, hash);
+            assert_eq!(actual, rmapi::hash::sha256_hex(b"tampered"));
+        }
+        other => panic!("expected HashMismatch, got {:?}", other),
+    }
+}
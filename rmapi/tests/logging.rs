@@ -0,0 +1,67 @@
+mod common;
+
+use log::{Level, Log, Metadata, Record};
+use rmapi::{Client, ClientConfig};
+use std::sync::Mutex;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// Keeps every message logged by the library, so tests can check what it gives away.
+struct CapturingLogger {
+    messages: Mutex<Vec<String>>,
+}
+
+impl Log for CapturingLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.target().starts_with("rmapi")
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            self.messages
+                .lock()
+                .unwrap()
+                .push(format!("{}", record.args()));
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: CapturingLogger = CapturingLogger {
+    messages: Mutex::new(Vec::new()),
+};
+
+#[tokio::test]
+async fn never_logs_codes_or_tokens() {
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(Level::Trace.to_level_filter());
+
+    let server = MockServer::start().await;
+    common::mock_token_exchange(&server).await;
+    Mock::given(method("POST"))
+        .and(path("/token/json/2/device/new"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("secret-device-token"))
+        .mount(&server)
+        .await;
+    let client = Client::builder()
+        .config(ClientConfig::local(&server.uri()))
+        .register("secret-code")
+        .await
+        .unwrap();
+    client.refresh_token().await.unwrap();
+
+    let messages = LOGGER.messages.lock().unwrap();
+    assert!(!messages.is_empty());
+    let user_token = common::user_token();
+    for message in messages.iter() {
+        for secret in ["secret-code", "secret-device-token", user_token.as_str()] {
+            assert!(
+                !message.contains(secret),
+                "{:?} was logged in {:?}",
+                secret,
+                message
+            );
+        }
+    }
+}
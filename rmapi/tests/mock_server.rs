@@ -0,0 +1,126 @@
+mod common;
+
+use rmapi::objects::ObjectKind;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+#[tokio::test]
+async fn walks_the_tree_of_a_mock_server() {
+    let server = MockServer::start().await;
+    common::mock_token_exchange(&server).await;
+    let root_hash = common::mock_library(&server).await;
+    let client = common::client(&server).await;
+
+    let tree = client.get_tree().await.unwrap();
+    assert_eq!(tree.hash, root_hash);
+    assert_eq!(tree.generation, 7);
+    assert_eq!(tree.items.len(), 2);
+
+    let dune = tree.resolve("/Books/Dune").unwrap();
+    assert_eq!(dune.object.kind(), ObjectKind::Document);
+    assert_eq!(dune.files.len(), 3);
+    assert!(dune.file_with_extension("pdf").is_some());
+}
+
+#[tokio::test]
+async fn skips_items_with_unreadable_metadata() {
+    let server = MockServer::start().await;
+    common::mock_token_exchange(&server).await;
+    let books = common::mock_item(
+        &server,
+        "10000000-0000-0000-0000-000000000001",
+        r#"{"visibleName": "Books", "parent": "", "type": "CollectionType", "lastModified": "1700000000000"}"#,
+        &[],
+    )
+    .await;
+    let template = common::mock_item(
+        &server,
+        "10000000-0000-0000-0000-000000000003",
+        r#"{"visibleName": "Grid", "parent": "", "type": "TemplateType", "lastModified": "1700000000000"}"#,
+        &[],
+    )
+    .await;
+    let broken = common::mock_item(
+        &server,
+        "10000000-0000-0000-0000-000000000004",
+        "not json",
+        &[],
+    )
+    .await;
+    let root = common::mock_index(&server, "root", vec![books, template, broken]).await;
+    common::mock_root(&server, &root.hash, 1).await;
+    let client = common::client(&server).await;
+
+    let tree = client.get_tree().await.unwrap();
+    assert_eq!(tree.items.len(), 1);
+    assert!(tree.resolve("/Books").is_some());
+}
+
+#[tokio::test]
+async fn refreshes_the_user_token_when_it_is_rejected() {
+    let server = MockServer::start().await;
+    common::mock_token_exchange(&server).await;
+    Mock::given(method("GET"))
+        .and(path("/sync/v4/root"))
+        .respond_with(ResponseTemplate::new(401))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    common::mock_root(&server, "roothash", 1).await;
+    let client = common::client(&server).await;
+
+    assert_eq!(client.sync_root().await.unwrap().hash, "roothash");
+    let token_requests = server
+        .received_requests()
+        .await
+        .unwrap()
+        .into_iter()
+        .filter(|r| r.url.path() == "/token/json/2/user/new")
+        .count();
+    assert_eq!(token_requests, 2);
+}
+
+#[tokio::test]
+async fn refreshes_a_rejected_token_once_for_concurrent_requests() {
+    let server = MockServer::start().await;
+    common::mock_token_exchange(&server).await;
+    Mock::given(method("GET"))
+        .and(path("/sync/v4/root"))
+        .respond_with(ResponseTemplate::new(401))
+        .up_to_n_times(2)
+        .mount(&server)
+        .await;
+    common::mock_root(&server, "roothash", 1).await;
+    let client = common::client(&server).await;
+
+    let (first, second) = tokio::join!(client.sync_root(), client.sync_root());
+    assert_eq!(first.unwrap().hash, "roothash");
+    assert_eq!(second.unwrap().hash, "roothash");
+    let token_requests = server
+        .received_requests()
+        .await
+        .unwrap()
+        .into_iter()
+        .filter(|r| r.url.path() == "/token/json/2/user/new")
+        .count();
+    assert_eq!(token_requests, 2);
+}
+
+#[tokio::test]
+async fn sends_the_configured_user_agent() {
+    let server = MockServer::start().await;
+    common::mock_token_exchange(&server).await;
+    common::mock_root(&server, "roothash", 1).await;
+    let client = rmapi::Client::builder()
+        .config(rmapi::ClientConfig::local(&server.uri()))
+        .http(rmapi::HttpConfig::default().with_user_agent("nightly-sync/1.0"))
+        .from_token("device-token")
+        .await
+        .unwrap();
+
+    client.sync_root().await.unwrap();
+    let requests = server.received_requests().await.unwrap();
+    assert!(requests
+        .iter()
+        .all(|r| r.headers.get("user-agent").unwrap() == "nightly-sync/1.0"));
+}
@@ -0,0 +1,131 @@
+mod common;
+
+use rmapi::{Client, ClientConfig, RetryPolicy};
+use std::time::Duration;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn fast_retries() -> RetryPolicy {
+    RetryPolicy {
+        max_attempts: 3,
+        base_delay: Duration::from_millis(1),
+        max_delay: Duration::from_millis(50),
+        jitter: false,
+    }
+}
+
+async fn client(server: &MockServer) -> Client {
+    Client::builder()
+        .config(ClientConfig::local(&server.uri()))
+        .retry(fast_retries())
+        .from_token("device-token")
+        .await
+        .unwrap()
+}
+
+async fn requests_to(server: &MockServer, url_path: &str) -> usize {
+    server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .filter(|r| r.url.path() == url_path)
+        .count()
+}
+
+#[tokio::test]
+async fn retries_server_errors_on_downloads() {
+    let server = MockServer::start().await;
+    common::mock_token_exchange(&server).await;
+    let hash = rmapi::hash::sha256_hex(b"contents");
+    Mock::given(method("GET"))
+        .and(path(format!("/sync/v3/files/{}", hash)))
+        .respond_with(ResponseTemplate::new(503))
+        .up_to_n_times(2)
+        .mount(&server)
+        .await;
+    common::mock_blob(&server, &hash, "contents").await;
+    let client = client(&server).await;
+
+    assert_eq!(client.get_blob(&hash).await.unwrap(), b"contents");
+    assert_eq!(
+        requests_to(&server, &format!("/sync/v3/files/{}", hash)).await,
+        3
+    );
+}
+
+#[tokio::test]
+async fn honors_retry_after() {
+    let server = MockServer::start().await;
+    common::mock_token_exchange(&server).await;
+    Mock::given(method("GET"))
+        .and(path("/sync/v4/root"))
+        .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "0"))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    common::mock_root(&server, "roothash", 1).await;
+    let client = client(&server).await;
+
+    assert_eq!(client.sync_root().await.unwrap().hash, "roothash");
+    assert_eq!(requests_to(&server, "/sync/v4/root").await, 2);
+}
+
+#[tokio::test]
+async fn gives_up_after_max_attempts() {
+    let server = MockServer::start().await;
+    common::mock_token_exchange(&server).await;
+    Mock::given(method("GET"))
+        .and(path("/sync/v3/files/blobhash"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&server)
+        .await;
+    let client = client(&server).await;
+
+    assert!(client.get_blob("blobhash").await.is_err());
+    assert_eq!(requests_to(&server, "/sync/v3/files/blobhash").await, 3);
+}
+
+#[tokio::test]
+async fn does_not_retry_server_errors_on_registration() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/token/json/2/device/new"))
+        .respond_with(ResponseTemplate::new(502))
+        .mount(&server)
+        .await;
+
+    let result = Client::builder()
+        .config(ClientConfig::local(&server.uri()))
+        .retry(fast_retries())
+        .register("abcdefgh")
+        .await;
+    assert!(result.is_err());
+    assert_eq!(requests_to(&server, "/token/json/2/device/new").await, 1);
+}
+
+#[tokio::test]
+async fn retries_rate_limited_registration() {
+    let server = MockServer::start().await;
+    common::mock_token_exchange(&server).await;
+    Mock::given(method("POST"))
+        .and(path("/token/json/2/device/new"))
+        .respond_with(ResponseTemplate::new(429))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/token/json/2/device/new"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("device-token"))
+        .mount(&server)
+        .await;
+
+    let client = Client::builder()
+        .config(ClientConfig::local(&server.uri()))
+        .retry(fast_retries())
+        .register("abcdefgh")
+        .await
+        .unwrap();
+    assert_eq!(client.device_token().await, "device-token");
+    assert_eq!(requests_to(&server, "/token/json/2/device/new").await, 2);
+}
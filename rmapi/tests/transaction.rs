@@ -0,0 +1,219 @@
+mod common;
+
+use rmapi::objects::Parent;
+use rmapi::sync::Index;
+use rmapi::{Content, Error, LinesFile, Notebook, NotebookPage, Transaction};
+use uuid::Uuid;
+use wiremock::matchers::{method, path, path_regex};
+use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+const BOOKS: &str = "10000000-0000-0000-0000-000000000001";
+const DUNE: &str = "10000000-0000-0000-0000-000000000002";
+
+async fn mock_uploads(server: &MockServer) {
+    Mock::given(method("PUT"))
+        .and(path_regex("^/sync/v3/files/[0-9a-f]{64}$"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(server)
+        .await;
+}
+
+async fn mock_root_update(server: &MockServer, status: u16, times: u64) {
+    Mock::given(method("PUT"))
+        .and(path("/sync/v3/root"))
+        .respond_with(
+            ResponseTemplate::new(status)
+                .set_body_json(serde_json::json!({"hash": "newroot", "generation": 8})),
+        )
+        .up_to_n_times(times)
+        .mount(server)
+        .await;
+}
+
+async fn requests(server: &MockServer, method: &str, prefix: &str) -> Vec<Request> {
+    server
+        .received_requests()
+        .await
+        .unwrap()
+        .into_iter()
+        .filter(|r| r.method.as_str() == method && r.url.path().starts_with(prefix))
+        .collect()
+}
+
+/// Finds the uploaded blob that was sent with the given `rm-filename`.
+async fn uploaded(server: &MockServer, filename: &str) -> Vec<u8> {
+    requests(server, "PUT", "/sync/v3/files/")
+        .await
+        .into_iter()
+        .rev()
+        .find(|r| r.headers.get("rm-filename").unwrap() == filename)
+        .unwrap()
+        .body
+}
+
+#[tokio::test]
+async fn commits_staged_changes() {
+    let server = MockServer::start().await;
+    common::mock_token_exchange(&server).await;
+    common::mock_library(&server).await;
+    mock_uploads(&server).await;
+    mock_root_update(&server, 200, u64::MAX).await;
+    let client = common::client(&server).await;
+
+    let mut transaction = Transaction::new();
+    let papers = transaction
+        .create_collection("Papers", Parent::Root)
+        .unwrap();
+    let dune = Uuid::parse_str(DUNE).unwrap();
+    transaction.move_to(dune, Parent::Collection(papers));
+    let root = client.commit(&transaction).await.unwrap();
+    assert_eq!(root.generation, 8);
+
+    let updates = requests(&server, "PUT", "/sync/v3/root").await;
+    assert_eq!(updates.len(), 1);
+    let update: serde_json::Value = serde_json::from_slice(&updates[0].body).unwrap();
+    assert_eq!(update["generation"], 7);
+
+    let root_index = Index::parse(&uploaded(&server, "root.docSchema").await).unwrap();
+    assert_eq!(root_index.entries.len(), 3);
+    assert_eq!(
+        update["hash"],
+        rmapi::hash::index_hash(&root_index).unwrap()
+    );
+    let metadata: serde_json::Value =
+        serde_json::from_slice(&uploaded(&server, &format!("{}.metadata", DUNE)).await).unwrap();
+    assert_eq!(metadata["parent"], papers.to_string());
+    assert_eq!(metadata["visibleName"], "Dune");
+}
+
+#[tokio::test]
+async fn creates_notebooks_with_their_pages() {
+    let server = MockServer::start().await;
+    common::mock_token_exchange(&server).await;
+    common::mock_library(&server).await;
+    mock_uploads(&server).await;
+    mock_root_update(&server, 200, u64::MAX).await;
+    let client = common::client(&server).await;
+
+    let notebook = Notebook::new("Planner")
+        .with_page(NotebookPage::new().with_template("P Grid small"))
+        .with_pages(2, "P Lines small");
+    let id = client.create_notebook(&notebook).await.unwrap();
+    assert_eq!(id, notebook.id);
+
+    let root_index = Index::parse(&uploaded(&server, "root.docSchema").await).unwrap();
+    let entry = root_index
+        .entries
+        .iter()
+        .find(|e| e.id == id.to_string())
+        .unwrap();
+    assert_eq!(entry.subfiles, 6);
+
+    let content = uploaded(&server, &format!("{}.content", id)).await;
+    let pages = Content::from_bytes(&content).unwrap().pages();
+    assert_eq!(pages.len(), 3);
+    assert_eq!(pages[0].template.as_deref(), Some("P Grid small"));
+    let page = uploaded(&server, &format!("{}/{}.rm", id, pages[2].id)).await;
+    assert_eq!(LinesFile::parse(&page).unwrap().layers[0].name, "Layer 1");
+}
+
+#[tokio::test]
+async fn replaces_notebooks_committed_twice() {
+    let server = MockServer::start().await;
+    common::mock_token_exchange(&server).await;
+    common::mock_library(&server).await;
+    mock_uploads(&server).await;
+    mock_root_update(&server, 200, u64::MAX).await;
+    let client = common::client(&server).await;
+
+    let notebook = Notebook::new("Planner").with_pages(2, "P Lines small");
+    let mut transaction = Transaction::new();
+    transaction.create_notebook(&notebook).unwrap();
+    let id = transaction.create_notebook(&notebook).unwrap();
+    client.commit(&transaction).await.unwrap();
+
+    let index = Index::parse(&uploaded(&server, &format!("{}.docSchema", id)).await).unwrap();
+    let pages: Vec<_> = index
+        .entries
+        .iter()
+        .filter(|e| e.id.ends_with(".rm"))
+        .collect();
+    assert_eq!(pages.len(), 2);
+    assert_eq!(index.entries.len(), 5);
+}
+
+#[tokio::test]
+async fn rebases_on_generation_conflicts() {
+    let server = MockServer::start().await;
+    common::mock_token_exchange(&server).await;
+    common::mock_library(&server).await;
+    mock_uploads(&server).await;
+    mock_root_update(&server, 412, 1).await;
+    mock_root_update(&server, 200, u64::MAX).await;
+    let client = common::client(&server).await;
+
+    let mut transaction = Transaction::new();
+    transaction.trash(Uuid::parse_str(DUNE).unwrap());
+    client.commit(&transaction).await.unwrap();
+
+    assert_eq!(requests(&server, "PUT", "/sync/v3/root").await.len(), 2);
+    assert_eq!(requests(&server, "GET", "/sync/v4/root").await.len(), 2);
+}
+
+#[tokio::test]
+async fn rejects_moving_collections_inside_themselves() {
+    let server = MockServer::start().await;
+    common::mock_token_exchange(&server).await;
+    common::mock_library(&server).await;
+    let client = common::client(&server).await;
+    let books = Uuid::parse_str(BOOKS).unwrap();
+
+    let mut into_itself = Transaction::new();
+    into_itself.move_to(books, Parent::Collection(books));
+
+    let mut into_a_new_child = Transaction::new();
+    let inner = into_a_new_child
+        .create_collection("Inner", Parent::Collection(books))
+        .unwrap();
+    into_a_new_child.move_to(books, Parent::Collection(inner));
+
+    let mut into_a_moved_child = Transaction::new();
+    let papers = into_a_moved_child
+        .create_collection("Papers", Parent::Root)
+        .unwrap();
+    into_a_moved_child.move_to(papers, Parent::Collection(books));
+    into_a_moved_child.move_to(books, Parent::Collection(papers));
+
+    for transaction in [into_itself, into_a_new_child, into_a_moved_child] {
+        assert!(matches!(
+            client.commit(&transaction).await,
+            Err(Error::InvalidMove { .. })
+        ));
+    }
+    assert!(requests(&server, "PUT", "/").await.is_empty());
+
+    let mut into_a_sibling = Transaction::new();
+    let papers = into_a_sibling
+        .create_collection("Papers", Parent::Root)
+        .unwrap();
+    into_a_sibling.move_to(books, Parent::Collection(papers));
+    mock_uploads(&server).await;
+    mock_root_update(&server, 200, u64::MAX).await;
+    client.commit(&into_a_sibling).await.unwrap();
+}
+
+#[tokio::test]
+async fn rejects_changes_to_missing_items() {
+    let server = MockServer::start().await;
+    common::mock_token_exchange(&server).await;
+    common::mock_library(&server).await;
+    let client = common::client(&server).await;
+
+    let mut transaction = Transaction::new();
+    transaction.rename(Uuid::new_v4(), "Missing");
+    assert!(matches!(
+        client.commit(&transaction).await,
+        Err(Error::PathNotFound(_))
+    ));
+    assert!(requests(&server, "PUT", "/").await.is_empty());
+}
@@ -0,0 +1,284 @@
+mod common;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use rmapi::objects::Parent;
+use rmapi::{Error, UploadOptions, UploadProgress, UploadSource};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+use wiremock::matchers::{header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn temp_file(name: &str, contents: &[u8]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rmapi-test-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+#[tokio::test]
+async fn uploads_epubs_with_their_content_type() {
+    let server = MockServer::start().await;
+    common::mock_token_exchange(&server).await;
+    Mock::given(method("POST"))
+        .and(path("/doc/v2/files"))
+        .and(header("content-type", "application/epub+zip"))
+        .respond_with(ResponseTemplate::new(201).set_body_json(serde_json::json!({
+            "docID": "20000000-0000-0000-0000-000000000001",
+            "hash": "abc",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let client = common::client(&server).await;
+
+    let mut epub = b"PK\x03\x04".to_vec();
+    epub.resize(30, 0);
+    epub.extend_from_slice(b"mimetypeapplication/epub+zip rest of the book");
+    let local = temp_file("book.epub", &epub);
+    client
+        .upload_file(&local, &UploadOptions::new())
+        .await
+        .unwrap();
+
+    let uploads = server.received_requests().await.unwrap();
+    assert_eq!(uploads.last().unwrap().body, epub);
+    std::fs::remove_dir_all(local.parent().unwrap()).unwrap();
+}
+
+#[tokio::test]
+async fn rejects_unsupported_files_before_uploading() {
+    let server = MockServer::start().await;
+    common::mock_token_exchange(&server).await;
+    let client = common::client(&server).await;
+
+    let local = temp_file("notes.txt", b"just some notes");
+    assert!(matches!(
+        client.upload_file(&local, &UploadOptions::new()).await,
+        Err(Error::UnsupportedFileType { response: None, .. })
+    ));
+    assert!(server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .all(|r| r.url.path() != "/doc/v2/files"));
+    std::fs::remove_dir_all(local.parent().unwrap()).unwrap();
+}
+
+async fn mock_upload(server: &MockServer) {
+    Mock::given(method("POST"))
+        .and(path("/doc/v2/files"))
+        .respond_with(ResponseTemplate::new(201).set_body_json(serde_json::json!({
+            "docID": "20000000-0000-0000-0000-000000000001",
+        })))
+        .mount(server)
+        .await;
+}
+
+async fn sent_meta(server: &MockServer) -> serde_json::Value {
+    let requests = server.received_requests().await.unwrap();
+    let upload = requests
+        .iter()
+        .find(|r| r.url.path() == "/doc/v2/files")
+        .unwrap();
+    let meta = upload.headers.get("rm-Meta").unwrap().to_str().unwrap();
+    serde_json::from_slice(&STANDARD.decode(meta).unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn sends_upload_options_in_rm_meta() {
+    let server = MockServer::start().await;
+    common::mock_token_exchange(&server).await;
+    mock_upload(&server).await;
+    let client = common::client(&server).await;
+
+    let parent = Uuid::parse_str("10000000-0000-0000-0000-000000000001").unwrap();
+    let local = temp_file("report.pdf", b"%PDF-1.7");
+    let options = UploadOptions::new()
+        .with_visible_name("Weekly report")
+        .with_parent(Parent::Collection(parent))
+        .with_tag("work");
+    let id = client.upload_file(&local, &options).await.unwrap();
+
+    assert_eq!(id.to_string(), "20000000-0000-0000-0000-000000000001");
+    assert_eq!(
+        sent_meta(&server).await,
+        serde_json::json!({
+            "file_name": "Weekly report",
+            "parent": parent.to_string(),
+            "tags": ["work"],
+        })
+    );
+    std::fs::remove_dir_all(local.parent().unwrap()).unwrap();
+}
+
+#[tokio::test]
+async fn resolves_destination_paths_and_defaults_the_name() {
+    let server = MockServer::start().await;
+    common::mock_token_exchange(&server).await;
+    common::mock_library(&server).await;
+    mock_upload(&server).await;
+    let client = common::client(&server).await;
+
+    let local = temp_file("Foundation.pdf", b"%PDF-1.7");
+    let options = UploadOptions::new().with_parent_path("/Books");
+    client.upload_file(&local, &options).await.unwrap();
+
+    assert_eq!(
+        sent_meta(&server).await,
+        serde_json::json!({
+            "file_name": "Foundation",
+            "parent": "10000000-0000-0000-0000-000000000001",
+        })
+    );
+    std::fs::remove_dir_all(local.parent().unwrap()).unwrap();
+}
+
+#[tokio::test]
+async fn leaves_the_name_out_of_rm_meta_when_none_is_given() {
+    let server = MockServer::start().await;
+    common::mock_token_exchange(&server).await;
+    mock_upload(&server).await;
+    let client = common::client(&server).await;
+
+    client
+        .upload(
+            UploadSource::from_bytes(&b"%PDF-1.7"[..]),
+            &UploadOptions::new(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(sent_meta(&server).await, serde_json::json!({}));
+}
+
+#[tokio::test]
+async fn uploads_bytes_and_reports_progress() {
+    let server = MockServer::start().await;
+    common::mock_token_exchange(&server).await;
+    mock_upload(&server).await;
+    let client = common::client(&server).await;
+
+    let mut pdf = b"%PDF-1.7\n".to_vec();
+    pdf.resize(200 * 1024, b' ');
+    let reports = Arc::new(Mutex::new(Vec::new()));
+    let options = UploadOptions::new()
+        .with_visible_name("Generated")
+        .with_progress({
+            let reports = reports.clone();
+            move |progress| reports.lock().unwrap().push(progress)
+        });
+    client
+        .upload(UploadSource::from_bytes(pdf.clone()), &options)
+        .await
+        .unwrap();
+
+    let reports = reports.lock().unwrap().clone();
+    assert_eq!(reports.len(), 4);
+    assert_eq!(
+        *reports.last().unwrap(),
+        UploadProgress {
+            sent: pdf.len() as u64,
+            total: Some(pdf.len() as u64),
+        }
+    );
+    let requests = server.received_requests().await.unwrap();
+    let upload = requests.last().unwrap();
+    assert_eq!(upload.body, pdf);
+    assert_eq!(
+        upload.headers.get("content-length").unwrap(),
+        &pdf.len().to_string()
+    );
+}
+
+#[tokio::test]
+async fn uploads_from_readers_of_unknown_length() {
+    let server = MockServer::start().await;
+    common::mock_token_exchange(&server).await;
+    mock_upload(&server).await;
+    let client = common::client(&server).await;
+
+    let pdf = b"%PDF-1.7\n% streamed from somewhere".to_vec();
+    let reader = std::io::Cursor::new(pdf.clone());
+    client
+        .upload(
+            UploadSource::from_reader(reader, None),
+            &UploadOptions::new(),
+        )
+        .await
+        .unwrap();
+
+    let requests = server.received_requests().await.unwrap();
+    assert_eq!(requests.last().unwrap().body, pdf);
+}
+
+#[tokio::test]
+async fn uploads_again_after_refreshing_a_rejected_token() {
+    let server = MockServer::start().await;
+    common::mock_token_exchange(&server).await;
+    mock_upload(&server).await;
+    let client = common::client(&server).await;
+
+    let local = temp_file("report.pdf", b"%PDF-1.7 report");
+    reject_next_upload(&server).await;
+    client
+        .upload(
+            UploadSource::from_bytes(&b"%PDF-1.7 notes"[..]),
+            &UploadOptions::new().with_visible_name("Notes"),
+        )
+        .await
+        .unwrap();
+    reject_next_upload(&server).await;
+    client
+        .upload_file(&local, &UploadOptions::new())
+        .await
+        .unwrap();
+
+    let requests = server.received_requests().await.unwrap();
+    let count = |path: &str| requests.iter().filter(|r| r.url.path() == path).count();
+    assert_eq!(count("/doc/v2/files"), 4);
+    assert_eq!(count("/token/json/2/user/new"), 3);
+    assert_eq!(requests.last().unwrap().body, b"%PDF-1.7 report");
+    std::fs::remove_dir_all(local.parent().unwrap()).unwrap();
+}
+
+#[tokio::test]
+async fn refreshes_the_token_but_does_not_replay_streams() {
+    let server = MockServer::start().await;
+    common::mock_token_exchange(&server).await;
+    Mock::given(method("POST"))
+        .and(path("/doc/v2/files"))
+        .respond_with(ResponseTemplate::new(401))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    mock_upload(&server).await;
+    let client = common::client(&server).await;
+
+    let reader = std::io::Cursor::new(b"%PDF-1.7 streamed".to_vec());
+    let result = client
+        .upload(
+            UploadSource::from_reader(reader, None),
+            &UploadOptions::new().with_visible_name("Streamed"),
+        )
+        .await;
+    assert!(matches!(result, Err(Error::TokenExpired(_))));
+    let requests = server.received_requests().await.unwrap();
+    let count = |path: &str| requests.iter().filter(|r| r.url.path() == path).count();
+    assert_eq!(count("/doc/v2/files"), 1);
+    assert_eq!(count("/token/json/2/user/new"), 2);
+}
+
+/// Answers the next upload with a 401, ahead of any other upload mock.
+async fn reject_next_upload(server: &MockServer) {
+    Mock::given(method("POST"))
+        .and(path("/doc/v2/files"))
+        .respond_with(ResponseTemplate::new(401))
+        .up_to_n_times(1)
+        .with_priority(1)
+        .mount(server)
+        .await;
+}
@@ -1,14 +1,15 @@
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
 use std::path::Path;
 
 use dirs::cache_dir;
-use rmapi::Client;
+use rmapi::{BlobCache, Client, FileTokenStore};
 use std::path::PathBuf;
 use std::process;
+use std::sync::Arc;
 
 mod rmclient;
+use crate::rmclient::commands;
 use crate::rmclient::error::Error;
-use tokio::fs::File;
 
 pub fn default_token_file_path() -> PathBuf {
     cache_dir()
@@ -16,6 +17,12 @@ pub fn default_token_file_path() -> PathBuf {
         .join("rmapi/auth_token")
 }
 
+pub fn default_cache_dir_path() -> PathBuf {
+    cache_dir()
+        .unwrap_or_else(|| PathBuf::from("/tmp"))
+        .join("rmapi/blobs")
+}
+
 #[derive(Parser, Debug)]
 struct Args {
     #[arg(
@@ -32,23 +39,112 @@ struct Args {
         default_value = default_token_file_path().into_os_string()
     )]
     auth_token_file: PathBuf,
+
+    #[arg(
+        long = "cache-dir",
+        help = "Directory to cache downloaded blobs in",
+        default_value = default_cache_dir_path().into_os_string()
+    )]
+    cache_dir: PathBuf,
+
+    #[arg(long, help = "Do not cache downloaded blobs")]
+    no_cache: bool,
+
+    #[arg(
+        long = "cache-max-size",
+        value_name = "BYTES",
+        help = "Evict the least recently used blobs once the cache grows past this size"
+    )]
+    cache_max_size: Option<u64>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-async fn write_token_file(auth_token: &str, auth_token_file: &Path) -> Result<(), Error> {
-    if let Some(parent) = auth_token_file.parent() {
-        log::debug!("Making client cache dir {:?}", parent);
-        tokio::fs::create_dir_all(parent).await?;
-    }
-    tokio::fs::write(auth_token_file, auth_token).await?;
-    log::debug!("Saving auth token to: {:?}", auth_token_file);
-    Ok(())
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// List the items in a collection
+    Ls {
+        #[arg(default_value = "/", help = "Path of the collection to list")]
+        path: String,
+    },
+    /// Download a document
+    Get {
+        #[arg(help = "Path of the document to download")]
+        path: String,
+        #[arg(
+            short,
+            long,
+            help = "Where to write the document, defaults to its name"
+        )]
+        output: Option<PathBuf>,
+        #[arg(
+            long,
+            conflicts_with = "format",
+            help = "Download every file of the document as an .rmdoc archive instead of its PDF or EPUB"
+        )]
+        archive: bool,
+        #[arg(long, value_enum, default_value_t = GetFormat::Source, help = "What to download the document as")]
+        format: GetFormat,
+    },
+    /// Upload a PDF, EPUB or .rmdoc archive
+    Put {
+        #[arg(help = "Local file to upload")]
+        local: PathBuf,
+        #[arg(default_value = "/", help = "Path of the collection to upload into")]
+        dest: String,
+    },
+    /// Create a collection
+    Mkdir {
+        #[arg(help = "Path of the collection to create")]
+        path: String,
+    },
+    /// Move or rename an item
+    Mv {
+        #[arg(help = "Path of the item to move")]
+        source: String,
+        #[arg(help = "New path of the item, or an existing collection to move it into")]
+        dest: String,
+    },
+    /// Move an item to the trash
+    Rm {
+        #[arg(help = "Path of the item to remove")]
+        path: String,
+    },
+    /// Show details about an item
+    Stat {
+        #[arg(help = "Path of the item")]
+        path: String,
+    },
+    /// Print the highlights and typed text of a document
+    Highlights {
+        #[arg(help = "Path of the document")]
+        path: String,
+        #[arg(long, value_enum, default_value_t = NotesFormat::Markdown, help = "How to write the highlights")]
+        format: NotesFormat,
+        #[arg(
+            short,
+            long,
+            help = "Where to write the highlights, defaults to standard output"
+        )]
+        output: Option<PathBuf>,
+    },
+    /// Remove cached blobs that the library no longer uses
+    GcCache,
 }
 
-async fn refresh_client_token(client: &mut Client, auth_token_file: &Path) -> Result<(), Error> {
-    client.refresh_token().await?;
-    log::debug!("Saving new auth token to: {:?}", auth_token_file);
-    write_token_file(&client.auth_token, auth_token_file).await?;
-    Ok(())
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GetFormat {
+    /// The PDF or EPUB the document was created from
+    Source,
+    /// A PDF with everything drawn on the tablet, for PDFs and notebooks
+    Pdf,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum NotesFormat {
+    Markdown,
+    Json,
 }
 
 /// Creates a new `Client` instance from a token stored in a file.
@@ -56,6 +152,7 @@ async fn refresh_client_token(client: &mut Client, auth_token_file: &Path) -> Re
 /// # Arguments
 ///
 /// * `auth_token_file` - A `&Path` pointing to the file containing the authentication token.
+/// * `cache` - Where to cache downloaded blobs, if anywhere.
 ///
 /// # Returns
 ///
@@ -70,18 +167,25 @@ async fn refresh_client_token(client: &mut Client, auth_token_file: &Path) -> Re
 /// - The token file is not a regular file (`Error::TokenFileInvalid`).
 /// - Reading the token file fails.
 /// - Creating a new `Client` from the read token fails.
-async fn client_from_token_file(auth_token_file: &Path) -> Result<Client, Error> {
+async fn client_from_token_file(
+    auth_token_file: &Path,
+    cache: Option<BlobCache>,
+) -> Result<Client, Error> {
     if !auth_token_file.exists() {
         Err(Error::TokenFileNotFound)
     } else if !auth_token_file.is_file() {
         Err(Error::TokenFileInvalid)
     } else {
-        let auth_token = tokio::fs::read_to_string(&auth_token_file).await?;
         log::debug!(
             "Using token from {:?} to create a new client",
             auth_token_file
         );
-        Ok(Client::from_token(&auth_token).await?)
+        let token_store = Arc::new(FileTokenStore::new(auth_token_file));
+        let mut builder = Client::builder().token_store(token_store);
+        if let Some(cache) = cache {
+            builder = builder.cache(cache);
+        }
+        Ok(builder.connect().await?)
     }
 }
 
@@ -90,24 +194,50 @@ async fn main() -> Result<(), Error> {
     env_logger::init();
     let args = Args::parse();
 
-    let mut client;
+    let client;
+    let cache = (!args.no_cache).then(|| {
+        let cache = BlobCache::new(&args.cache_dir);
+        match args.cache_max_size {
+            Some(max_size) => cache.with_max_size(max_size),
+            None => cache,
+        }
+    });
 
     if let Some(code) = args.code {
-        client = Client::new(&code).await?;
+        let token_store = Arc::new(FileTokenStore::new(&args.auth_token_file));
+        let mut builder = Client::builder().token_store(token_store);
+        if let Some(cache) = cache {
+            builder = builder.cache(cache);
+        }
+        client = builder.register(&code).await?;
     } else if args.auth_token_file.exists() {
-        client = client_from_token_file(&args.auth_token_file).await?;
+        client = client_from_token_file(&args.auth_token_file, cache).await?;
     } else {
         eprintln!("No token file found at {:?}, please either correct the path with `-t` or provide a new verification code with `-c`", args.auth_token_file);
         process::exit(1);
     }
 
-    println!("Client token: {:?}", client.auth_token);
-    println!("Storage url: {:?}", client.storage_url);
-    //client.sync_root().await?;
+    log::debug!("Storage url: {:?}", client.config().storage_url);
 
-    let file_path = Path::new("test2.pdf");
-    let file = File::open(file_path).await?;
-    client.upload_file(file).await?;
-
-    Ok(())
+    match args.command {
+        Some(Command::Ls { path }) => commands::ls(&client, &path).await,
+        Some(Command::Get {
+            path,
+            output,
+            archive,
+            format,
+        }) => commands::get(&client, &path, output.as_deref(), archive, format).await,
+        Some(Command::Put { local, dest }) => commands::put(&client, &local, &dest).await,
+        Some(Command::Mkdir { path }) => commands::mkdir(&client, &path).await,
+        Some(Command::Mv { source, dest }) => commands::mv(&client, &source, &dest).await,
+        Some(Command::Rm { path }) => commands::rm(&client, &path).await,
+        Some(Command::Stat { path }) => commands::stat(&client, &path).await,
+        Some(Command::Highlights {
+            path,
+            format,
+            output,
+        }) => commands::highlights(&client, &path, format, output.as_deref()).await,
+        Some(Command::GcCache) => commands::gc_cache(&client).await,
+        None => Ok(()),
+    }
 }
@@ -0,0 +1,236 @@
+use crate::rmclient::error::Error;
+use crate::{GetFormat, NotesFormat};
+use indicatif::{ProgressBar, ProgressStyle};
+use rmapi::objects::{ObjectKind, Parent, RemarkableObject};
+use rmapi::sync::{Tree, TreeItem};
+use rmapi::{Archive, Client, Destination, FileType, RenderOptions, Transaction, UploadOptions};
+use std::path::{Path, PathBuf};
+
+/// Looks up the item at `path`, turning a miss into an `rmapi::Error::PathNotFound`.
+fn resolve<'a>(tree: &'a Tree, path: &str) -> Result<&'a TreeItem, Error> {
+    tree.resolve(path)
+        .ok_or_else(|| Error::Rmapi(rmapi::Error::PathNotFound(path.to_string())))
+}
+
+/// Looks up the collection `path` would be created in, and the name it would get.
+fn resolve_new<'a>(tree: &Tree, path: &'a str) -> Result<(Parent, &'a str), Error> {
+    let trimmed = path.trim_end_matches('/');
+    let (parent_path, name) = trimmed.rsplit_once('/').unwrap_or(("", trimmed));
+    if name.is_empty() {
+        return Err(Error::AlreadyExists(path.to_string()));
+    }
+    let parent = tree
+        .resolve_parent(parent_path)
+        .ok_or_else(|| Error::Rmapi(rmapi::Error::PathNotFound(parent_path.to_string())))?;
+    Ok((parent, name))
+}
+
+fn upload_progress_bar(length: u64) -> ProgressBar {
+    let style = ProgressStyle::with_template("{wide_bar} {bytes}/{total_bytes} ({eta})")
+        .unwrap_or_else(|_| ProgressStyle::default_bar());
+    ProgressBar::new(length).with_style(style)
+}
+
+fn kind_marker(object: &dyn RemarkableObject) -> &'static str {
+    match object.kind() {
+        ObjectKind::Collection => "[d]",
+        ObjectKind::Document => "[f]",
+    }
+}
+
+/// Lists the items in the collection at `path`, or the item itself if it is a document.
+pub async fn ls(client: &Client, path: &str) -> Result<(), Error> {
+    let tree = client.get_tree().await?;
+    let items = match tree.resolve_parent(path) {
+        Some(parent) => tree.children(parent),
+        None => vec![resolve(&tree, path)?],
+    };
+    for item in items {
+        let object = item.object.as_ref();
+        println!("{} {}", kind_marker(object), object.name());
+    }
+    Ok(())
+}
+
+/// Prints everything known about the item at `path`.
+pub async fn stat(client: &Client, path: &str) -> Result<(), Error> {
+    let tree = client.get_tree().await?;
+    let item = resolve(&tree, path)?;
+    let object = item.object.as_ref();
+    println!("Name:          {}", object.name());
+    println!("Id:            {}", object.id());
+    println!("Kind:          {:?}", object.kind());
+    println!(
+        "Path:          {}",
+        tree.path_of(&item.id).unwrap_or_default()
+    );
+    println!("Last modified: {}", object.last_modified());
+    println!("Hash:          {}", object.hash());
+    println!("Files:");
+    for file in &item.files {
+        println!("  {} ({} bytes)", file.id, file.size);
+    }
+    Ok(())
+}
+
+/// The extension of the archives the desktop app exports documents as.
+const ARCHIVE_EXTENSION: &str = "rmdoc";
+
+/// Downloads the document at `path` to `output`, or to a file named after it.
+///
+/// Only the PDF or EPUB the document was created from is downloaded, unless
+/// `archive` is set, in which case every file of the document is written as
+/// an `.rmdoc` archive, or `format` is `GetFormat::Pdf`, in which case the
+/// document is rendered as a PDF with everything drawn on it.
+pub async fn get(
+    client: &Client,
+    path: &str,
+    output: Option<&Path>,
+    archive: bool,
+    format: GetFormat,
+) -> Result<(), Error> {
+    let tree = client.get_tree().await?;
+    let item = resolve(&tree, path)?;
+    let (extension, contents) = if archive {
+        let archive = client.export_archive(item).await?;
+        (ARCHIVE_EXTENSION, archive.to_zip()?)
+    } else if format == GetFormat::Pdf {
+        let contents = client.export_pdf(item, &RenderOptions::new()).await?;
+        (FileType::Pdf.extension(), contents)
+    } else {
+        let (file_type, contents) = client.download(item).await?;
+        (file_type.extension(), contents)
+    };
+    let output = match output {
+        Some(output) => output.to_path_buf(),
+        None => PathBuf::from(format!(
+            "{}.{}",
+            item.object.name().replace('/', "_"),
+            extension
+        )),
+    };
+    std::fs::write(&output, contents)?;
+    println!("{}", output.display());
+    Ok(())
+}
+
+/// Writes the highlights and typed text of the document at `path` to
+/// `output`, or prints them.
+pub async fn highlights(
+    client: &Client,
+    path: &str,
+    format: NotesFormat,
+    output: Option<&Path>,
+) -> Result<(), Error> {
+    let tree = client.get_tree().await?;
+    let highlights = client.get_highlights(resolve(&tree, path)?).await?;
+    let notes = match format {
+        NotesFormat::Markdown => highlights.to_markdown(),
+        NotesFormat::Json => highlights.to_json()? + "\n",
+    };
+    match output {
+        Some(output) => std::fs::write(output, notes)?,
+        None => print!("{}", notes),
+    }
+    Ok(())
+}
+
+/// Uploads the local file at `local` to `dest`.
+///
+/// If `dest` is an existing collection the file is uploaded into it under its
+/// own name, otherwise it is uploaded to the parent of `dest` and takes its name.
+/// `.rmdoc` archives are imported with every file they hold.
+pub async fn put(client: &Client, local: &Path, dest: &str) -> Result<(), Error> {
+    let tree = client.get_tree().await?;
+    let options = match tree.resolve_parent(dest) {
+        Some(parent) => UploadOptions::new().with_parent(parent),
+        None if tree.resolve(dest).is_some() => return Err(Error::AlreadyExists(dest.to_string())),
+        None => {
+            let (parent, name) = resolve_new(&tree, dest)?;
+            UploadOptions::new()
+                .with_parent(parent)
+                .with_visible_name(name)
+        }
+    };
+    if local.extension().and_then(|e| e.to_str()) == Some(ARCHIVE_EXTENSION) {
+        return put_archive(client, local, &options).await;
+    }
+    let bar = upload_progress_bar(std::fs::metadata(local)?.len());
+    let options = options.with_progress({
+        let bar = bar.clone();
+        move |progress| bar.set_position(progress.sent)
+    });
+    let id = client.upload_file(local, &options).await;
+    bar.finish_and_clear();
+    println!("{}", id?);
+    Ok(())
+}
+
+/// Imports the `.rmdoc` archive at `local` with the destination and name in `options`.
+async fn put_archive(client: &Client, local: &Path, options: &UploadOptions) -> Result<(), Error> {
+    let mut archive = Archive::from_zip(&std::fs::read(local)?)?;
+    if let Some(ref name) = options.visible_name {
+        archive = archive.with_visible_name(name)?;
+    }
+    let parent = match options.destination {
+        Some(Destination::Parent(parent)) => parent,
+        _ => Parent::Root,
+    };
+    println!("{}", client.import_archive(archive, parent).await?);
+    Ok(())
+}
+
+/// Creates a collection at `path`.
+pub async fn mkdir(client: &Client, path: &str) -> Result<(), Error> {
+    let tree = client.get_tree().await?;
+    if tree.resolve(path).is_some() {
+        return Err(Error::AlreadyExists(path.to_string()));
+    }
+    let (parent, name) = resolve_new(&tree, path)?;
+    let mut transaction = Transaction::new();
+    transaction.create_collection(name, parent)?;
+    client.commit(&transaction).await?;
+    Ok(())
+}
+
+/// Moves or renames the item at `source` to `dest`.
+///
+/// If `dest` is an existing collection the item is moved into it, otherwise
+/// the item is moved to the parent of `dest` and takes its name. A
+/// collection cannot be moved into itself or any collection inside it.
+pub async fn mv(client: &Client, source: &str, dest: &str) -> Result<(), Error> {
+    let tree = client.get_tree().await?;
+    let object = resolve(&tree, source)?.object.as_ref();
+    let (parent, name) = match tree.resolve_parent(dest) {
+        Some(parent) => (parent, object.name()),
+        None if tree.resolve(dest).is_some() => return Err(Error::AlreadyExists(dest.to_string())),
+        None => resolve_new(&tree, dest)?,
+    };
+
+    let mut transaction = Transaction::new();
+    if parent != object.parent() {
+        transaction.move_to(object.id(), parent);
+    }
+    if name != object.name() {
+        transaction.rename(object.id(), name);
+    }
+    client.commit(&transaction).await?;
+    Ok(())
+}
+
+/// Moves the item at `path` to the trash.
+pub async fn rm(client: &Client, path: &str) -> Result<(), Error> {
+    let tree = client.get_tree().await?;
+    let item = resolve(&tree, path)?;
+    let mut transaction = Transaction::new();
+    transaction.trash(item.object.id());
+    client.commit(&transaction).await?;
+    Ok(())
+}
+
+/// Removes the cached blobs that are no longer reachable from the library.
+pub async fn gc_cache(client: &Client) -> Result<(), Error> {
+    let removed = client.gc_cache().await?;
+    println!("Removed {} blobs from the cache", removed);
+    Ok(())
+}
@@ -10,7 +10,8 @@ pub enum Error {
     Rmapi(rmapi::Error),
     Clap(clap::Error),
     TokenFileNotFound,
-    TokenFileInvalid
+    TokenFileInvalid,
+    AlreadyExists(String),
 }
 
 impl fmt::Display for Error {
@@ -21,7 +22,7 @@ impl fmt::Display for Error {
             Error::Clap(ref err) => err.fmt(f),
             Error::TokenFileNotFound => write!(f, "Token file not found"),
             Error::TokenFileInvalid => write!(f, "Token file is not valid"),
-
+            Error::AlreadyExists(ref path) => write!(f, "{:?} already exists", path),
         }
     }
 }
@@ -34,6 +35,7 @@ impl error::Error for Error {
             Error::Clap(ref err) => Some(err),
             Error::TokenFileNotFound => None,
             Error::TokenFileInvalid => None,
+            Error::AlreadyExists(_) => None,
         }
     }
 }